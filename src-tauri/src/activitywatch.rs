@@ -0,0 +1,137 @@
+use chrono::DateTime;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use crate::import::{get_known_app_paths, import_sessions, resolve_app_path, subtract_covered, ImportSummary, ImportedSession};
+
+/// An ActivityWatch export, as written by "Export all buckets as JSON" or `aw-client export`
+#[derive(Deserialize)]
+struct AwExport {
+    buckets: HashMap<String, AwBucket>,
+}
+
+#[derive(Deserialize)]
+struct AwBucket {
+    #[serde(rename = "type")]
+    bucket_type: String,
+    #[serde(default)]
+    events: Vec<AwEvent>,
+}
+
+#[derive(Deserialize)]
+struct AwEvent {
+    timestamp: String,
+    /// Seconds
+    duration: f64,
+    data: HashMap<String, serde_json::Value>,
+}
+
+impl AwEvent {
+    /// Get the start and end timestamps in milliseconds
+    fn interval(&self) -> Option<(u64, u64)> {
+        let start = DateTime::parse_from_rfc3339(&self.timestamp).ok()?.timestamp_millis();
+        if start < 0 || !self.duration.is_finite() || self.duration <= 0.0 {
+            return None;
+        }
+        let start = start as u64;
+        Some((start, start + (self.duration * 1000.0) as u64))
+    }
+
+    fn data_str(&self, key: &str) -> Option<&str> {
+        self.data.get(key).and_then(|value| value.as_str())
+    }
+}
+
+/// Convert an ActivityWatch export into sessions
+///
+/// Window events of `currentwindow` buckets become sessions, minus the time `afkstatus` buckets
+/// report as away from the keyboard.
+fn parse_export(content: &str, known_app_paths: &HashMap<String, String>) -> io::Result<Vec<ImportedSession>> {
+    let export: AwExport =
+        serde_json::from_str(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut sessions = Vec::new();
+    let mut afk_intervals = Vec::new();
+    for bucket in export.buckets.values() {
+        match bucket.bucket_type.as_str() {
+            "currentwindow" => {
+                for event in bucket.events.iter() {
+                    let (Some((start, end)), Some(app)) = (event.interval(), event.data_str("app")) else {
+                        continue;
+                    };
+                    sessions.push(ImportedSession {
+                        path: resolve_app_path(app, known_app_paths),
+                        start,
+                        end,
+                    });
+                }
+            }
+            "afkstatus" => {
+                for event in bucket.events.iter() {
+                    if event.data_str("status") == Some("afk") {
+                        if let Some(interval) = event.interval() {
+                            afk_intervals.push(interval);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    afk_intervals.sort();
+    sessions.sort_by_key(|session| session.start);
+    Ok(subtract_covered(sessions, &afk_intervals))
+}
+
+/// Import an ActivityWatch export file into the day logs
+pub fn import_activitywatch(file_path: &str) -> io::Result<ImportSummary> {
+    let content = fs::read_to_string(file_path)?;
+    let sessions = parse_export(&content, &get_known_app_paths()?)?;
+    import_sessions(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_export() {
+        let content = r#"{
+            "buckets": {
+                "aw-watcher-window_host": {
+                    "id": "aw-watcher-window_host",
+                    "type": "currentwindow",
+                    "events": [
+                        {"timestamp": "2024-05-01T10:00:00+00:00", "duration": 600.0, "data": {"app": "Code", "title": "main.rs"}},
+                        {"timestamp": "2024-05-01T10:10:00.500000+00:00", "duration": 0.0, "data": {"app": "Finder", "title": ""}},
+                        {"timestamp": "2024-05-01T10:10:00Z", "duration": 300.0, "data": {"app": "Safari", "title": "Docs"}}
+                    ]
+                },
+                "aw-watcher-afk_host": {
+                    "id": "aw-watcher-afk_host",
+                    "type": "afkstatus",
+                    "events": [
+                        {"timestamp": "2024-05-01T10:05:00+00:00", "duration": 120.0, "data": {"status": "afk"}},
+                        {"timestamp": "2024-05-01T10:07:00+00:00", "duration": 480.0, "data": {"status": "not-afk"}}
+                    ]
+                }
+            }
+        }"#;
+        let known_app_paths = HashMap::from([(
+            "code".to_string(),
+            "/Applications/Visual Studio Code.app/Contents/MacOS/Electron".to_string(),
+        )]);
+        let start = 1714557600000;
+
+        let sessions = parse_export(content, &known_app_paths).unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0].path, "/Applications/Visual Studio Code.app/Contents/MacOS/Electron");
+        assert_eq!((sessions[0].start, sessions[0].end), (start, start + 300000));
+        assert_eq!((sessions[1].start, sessions[1].end), (start + 420000, start + 600000));
+        assert_eq!((sessions[2].start, sessions[2].end), (start + 600000, start + 900000));
+        assert_eq!(sessions[2].path, resolve_app_path("Safari", &HashMap::new()));
+    }
+}
@@ -0,0 +1,253 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use tiny_http::{Header, Method, Response, Server};
+use tracing::{error, info};
+
+use crate::clock::get_report_clock;
+use crate::config::ApiConfig;
+use crate::metrics::render_metrics;
+use crate::monitor::Monitor;
+use crate::projects::get_project_usages;
+use crate::rules::get_category_usages;
+use crate::sys_monitor::{get_app_usages_from_log, get_events_from_log};
+use crate::timeline::get_timeline;
+use crate::utils::get_log_file_dir_str;
+
+/// The OpenAPI description of the endpoints below, served at `/api/v1/openapi.json`
+pub const OPENAPI: &str = include_str!("../openapi.json");
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub fn get_token_file_path() -> String {
+    format!("{}/api-token", get_log_file_dir_str())
+}
+
+/// Get the token from the config, or from the token file, generating one on first use
+pub fn get_api_token(config: &ApiConfig) -> io::Result<String> {
+    if let Some(token) = &config.token {
+        return Ok(token.clone());
+    }
+    let token_file = get_token_file_path();
+    match fs::read_to_string(&token_file) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    fs::create_dir_all(get_log_file_dir_str())?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&token_file)?;
+    writeln!(file, "{}", token)?;
+    info!("Generated API token in {}", token_file);
+    Ok(token)
+}
+
+/// An error answer: the HTTP status and a message for the `error` field
+#[derive(Debug)]
+struct ApiError(u16, String);
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> ApiError {
+        ApiError(400, message.into())
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(e: io::Error) -> ApiError {
+        ApiError(500, e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> ApiError {
+        ApiError(500, e.to_string())
+    }
+}
+
+/// Split a request url into its path and query parameters
+fn parse_url(url: &str) -> (&str, Vec<(&str, &str)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect();
+    (path, params)
+}
+
+fn get_timestamp_param(params: &[(&str, &str)], name: &str) -> Result<u64, ApiError> {
+    let value = params
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| *value)
+        .ok_or_else(|| ApiError::bad_request(format!("Missing query parameter: {}", name)))?;
+    value
+        .parse()
+        .map_err(|_| ApiError::bad_request(format!("Invalid {}: {}", name, value)))
+}
+
+fn get_range_params(params: &[(&str, &str)]) -> Result<(u64, u64), ApiError> {
+    let start_timestamp = get_timestamp_param(params, "start_timestamp")?;
+    let end_timestamp = get_timestamp_param(params, "end_timestamp")?;
+    if start_timestamp > end_timestamp {
+        return Err(ApiError::bad_request("start_timestamp is after end_timestamp"));
+    }
+    Ok((start_timestamp, end_timestamp))
+}
+
+/// Compare tokens in constant time, so the token can't be guessed from response times
+fn is_token_valid(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// A localhost-only HTTP server exposing the usage data as JSON
+pub struct ApiServer {
+    server: Server,
+    token: String,
+    metrics: bool,
+}
+
+impl ApiServer {
+    /// Listen on 127.0.0.1. Port 0 picks a free port.
+    pub fn bind(port: u16, token: String) -> io::Result<ApiServer> {
+        let server = Server::http(("127.0.0.1", port)).map_err(io::Error::other)?;
+        Ok(ApiServer {
+            server,
+            token,
+            metrics: false,
+        })
+    }
+
+    /// Also serve Prometheus metrics at `/metrics`
+    pub fn with_metrics(mut self, metrics: bool) -> ApiServer {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn port(&self) -> u16 {
+        self.server.server_addr().to_ip().map(|addr| addr.port()).unwrap_or(0)
+    }
+
+    /// Answer requests one by one. Blocks forever.
+    pub fn run(&self, monitor: &Monitor) {
+        info!("Serving the HTTP API on 127.0.0.1:{}", self.port());
+        for request in self.server.incoming_requests() {
+            let authorization = request
+                .headers()
+                .iter()
+                .find(|header| header.field.equiv("Authorization"))
+                .map(|header| header.value.as_str().to_string());
+            let (status, body, content_type) =
+                match self.handle(request.method(), request.url(), authorization.as_deref(), monitor) {
+                    Ok((body, content_type)) => (200, body, content_type),
+                    Err(ApiError(status, message)) => {
+                        if status == 500 {
+                            error!("Failed to answer {}: {}", request.url(), message);
+                        }
+                        (status, json!({ "error": message }).to_string(), JSON_CONTENT_TYPE)
+                    }
+                };
+            let content_type = Header::from_bytes("Content-Type", content_type).unwrap();
+            let response = Response::from_string(body)
+                .with_status_code(status)
+                .with_header(content_type);
+            if let Err(e) = request.respond(response) {
+                error!("Failed to send response: {}", e);
+            }
+        }
+    }
+
+    fn handle(
+        &self,
+        method: &Method,
+        url: &str,
+        authorization: Option<&str>,
+        monitor: &Monitor,
+    ) -> Result<(String, &'static str), ApiError> {
+        if *method != Method::Get {
+            return Err(ApiError(405, format!("Method not allowed: {}", method)));
+        }
+        let (path, params) = parse_url(url);
+        // The description is public, so clients can be generated without a token
+        if path == "/api/v1/openapi.json" {
+            return Ok((OPENAPI.to_string(), JSON_CONTENT_TYPE));
+        }
+        let provided = authorization.and_then(|value| value.strip_prefix("Bearer ")).unwrap_or("");
+        if !is_token_valid(&self.token, provided) {
+            return Err(ApiError(401, "Missing or invalid token".to_string()));
+        }
+
+        let body = match path {
+            "/api/v1/current" => serde_json::to_value(monitor.current_app())?,
+            "/api/v1/today" => {
+                let (start_timestamp, end_timestamp) = get_report_clock().get_today_so_far();
+                serde_json::to_value(get_app_usages_from_log(start_timestamp, end_timestamp)?)?
+            }
+            "/api/v1/usages" => {
+                let (start_timestamp, end_timestamp) = get_range_params(&params)?;
+                serde_json::to_value(get_app_usages_from_log(start_timestamp, end_timestamp)?)?
+            }
+            "/api/v1/events" => {
+                let (start_timestamp, end_timestamp) = get_range_params(&params)?;
+                serde_json::to_value(get_events_from_log(start_timestamp, end_timestamp)?)?
+            }
+            "/api/v1/projects" => {
+                let (start_timestamp, end_timestamp) = get_range_params(&params)?;
+                serde_json::to_value(get_project_usages(&get_timeline(start_timestamp, end_timestamp)?))?
+            }
+            "/api/v1/categories" => {
+                let (start_timestamp, end_timestamp) = get_range_params(&params)?;
+                serde_json::to_value(get_category_usages(&get_timeline(start_timestamp, end_timestamp)?))?
+            }
+            "/api/v1/timeline" => {
+                let (start_timestamp, end_timestamp) = get_range_params(&params)?;
+                serde_json::to_value(get_timeline(start_timestamp, end_timestamp)?)?
+            }
+            "/metrics" if self.metrics => return Ok((render_metrics(monitor)?, METRICS_CONTENT_TYPE)),
+            _ => return Err(ApiError(404, format!("Not found: {}", path))),
+        };
+        Ok((body.to_string(), JSON_CONTENT_TYPE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let (path, params) = parse_url("/api/v1/usages?start_timestamp=1&end_timestamp=2&flag");
+        assert_eq!(path, "/api/v1/usages");
+        assert_eq!(params, [("start_timestamp", "1"), ("end_timestamp", "2"), ("flag", "")]);
+        assert_eq!(get_range_params(&params).unwrap(), (1, 2));
+        assert!(get_range_params(&params[1..]).is_err());
+    }
+
+    #[test]
+    fn test_is_token_valid() {
+        assert!(is_token_valid("secret", "secret"));
+        assert!(!is_token_valid("secret", "secreT"));
+        assert!(!is_token_valid("secret", "secret2"));
+        assert!(!is_token_valid("secret", ""));
+    }
+}
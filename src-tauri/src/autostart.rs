@@ -0,0 +1,285 @@
+use plist::{Dictionary, Value};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info};
+
+use crate::utils::get_log_file_dir_str;
+
+/// How a program is started at login
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutostartBackend {
+    /// A plist in `~/Library/LaunchAgents` on macOS
+    LaunchAgent,
+    /// A `.desktop` file in `~/.config/autostart`, started by the desktop session on Linux
+    XdgAutostart,
+    /// A systemd user unit wanted by `graphical-session.target` on Linux
+    SystemdUser,
+}
+
+const APP_LAUNCH_AGENT_LABEL: &str = "com.entropy.flows";
+
+/// Starting one program at login: the app, or the daemon on its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct Autostart {
+    pub backend: AutostartBackend,
+    /// Name of the entry, e.g. `com.entropy.flows` for a LaunchAgent or `flows` for a `.desktop` file
+    pub label: String,
+    pub program: PathBuf,
+    /// Restart the program when it crashes, if the backend can
+    pub keep_alive: bool,
+    /// Also tell the running system: reload systemd, and remove the legacy login item on macOS.
+    /// Off for entries in a test home, so the real user session is left alone.
+    pub update_system: bool,
+}
+
+fn get_home_dir() -> PathBuf {
+    PathBuf::from(env::var("HOME").unwrap())
+}
+
+fn get_config_home() -> PathBuf {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+        _ => get_home_dir().join(".config"),
+    }
+}
+
+/// Quote an argument for the `Exec` key of a desktop entry
+fn quote_desktop_exec(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote an argument for `ExecStart` of a systemd unit
+fn quote_systemd_exec(arg: &str) -> String {
+    let escaped = arg.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+impl Autostart {
+    /// Start `flows` from next to this executable at login, the app, which in turn starts the daemon
+    ///
+    /// In the macOS bundle that is `Flows.app/Contents/MacOS/flows`, whether called from the app or the CLI.
+    pub fn app() -> io::Result<Autostart> {
+        let program = env::current_exe()?.with_file_name("flows");
+        Ok(if cfg!(target_os = "macos") {
+            Autostart {
+                backend: AutostartBackend::LaunchAgent,
+                label: APP_LAUNCH_AGENT_LABEL.to_string(),
+                program,
+                keep_alive: false,
+                update_system: true,
+            }
+        } else {
+            Autostart {
+                backend: AutostartBackend::XdgAutostart,
+                label: "flows".to_string(),
+                program,
+                keep_alive: false,
+                update_system: true,
+            }
+        })
+    }
+
+    /// Start `flows-daemon` from next to this executable at login, without the app
+    pub fn daemon() -> io::Result<Autostart> {
+        let program = env::current_exe()?.with_file_name("flows-daemon");
+        Ok(if cfg!(target_os = "macos") {
+            Autostart {
+                backend: AutostartBackend::LaunchAgent,
+                label: "com.entropy.flows.daemon".to_string(),
+                program,
+                keep_alive: true,
+                update_system: true,
+            }
+        } else {
+            Autostart {
+                backend: AutostartBackend::SystemdUser,
+                label: "flows-daemon".to_string(),
+                program,
+                keep_alive: true,
+                update_system: true,
+            }
+        })
+    }
+
+    /// The file that makes the program start at login
+    pub fn get_entry_path(&self) -> PathBuf {
+        match self.backend {
+            AutostartBackend::LaunchAgent => get_home_dir()
+                .join("Library/LaunchAgents")
+                .join(format!("{}.plist", self.label)),
+            AutostartBackend::XdgAutostart => get_config_home()
+                .join("autostart")
+                .join(format!("{}.desktop", self.label)),
+            AutostartBackend::SystemdUser => get_config_home()
+                .join("systemd/user")
+                .join(format!("{}.service", self.label)),
+        }
+    }
+
+    /// The link `systemctl --user enable` would create for the unit
+    fn get_systemd_wants_path(&self) -> PathBuf {
+        get_config_home()
+            .join("systemd/user/graphical-session.target.wants")
+            .join(format!("{}.service", self.label))
+    }
+
+    fn get_entry_content(&self) -> io::Result<String> {
+        let program = self.program.to_string_lossy();
+        match self.backend {
+            AutostartBackend::LaunchAgent => {
+                let mut dictionary = Dictionary::new();
+                dictionary.insert("Label".to_string(), Value::String(self.label.clone()));
+                dictionary.insert(
+                    "ProgramArguments".to_string(),
+                    Value::Array(vec![Value::String(program.to_string())]),
+                );
+                dictionary.insert("RunAtLoad".to_string(), Value::Boolean(true));
+                if self.keep_alive {
+                    let mut keep_alive = Dictionary::new();
+                    keep_alive.insert("SuccessfulExit".to_string(), Value::Boolean(false));
+                    dictionary.insert("KeepAlive".to_string(), Value::Dictionary(keep_alive));
+                }
+                let mut content = Vec::new();
+                Value::Dictionary(dictionary)
+                    .to_writer_xml(&mut content)
+                    .map_err(io::Error::other)?;
+                String::from_utf8(content).map_err(io::Error::other)
+            }
+            AutostartBackend::XdgAutostart => Ok(format!(
+                "[Desktop Entry]\nType=Application\nName=Flows\nComment=Screen time tracker\nExec={}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+                quote_desktop_exec(&program)
+            )),
+            AutostartBackend::SystemdUser => Ok(format!(
+                "[Unit]\nDescription=Flows screen time tracker\nAfter=graphical-session.target\nPartOf=graphical-session.target\n\n[Service]\nExecStart={}\n{}\n[Install]\nWantedBy=graphical-session.target\n",
+                quote_systemd_exec(&program),
+                if self.keep_alive { "Restart=on-failure\nRestartSec=5\n" } else { "" }
+            )),
+        }
+    }
+
+    /// Whether the program starts at login. Entries the user turned off in the desktop settings count as disabled.
+    pub fn is_enabled(&self) -> bool {
+        match self.backend {
+            AutostartBackend::LaunchAgent => self.get_entry_path().exists(),
+            AutostartBackend::XdgAutostart => match fs::read_to_string(self.get_entry_path()) {
+                Ok(content) => !content
+                    .lines()
+                    .any(|line| matches!(line.trim(), "Hidden=true" | "X-GNOME-Autostart-enabled=false")),
+                Err(_) => false,
+            },
+            AutostartBackend::SystemdUser => self.get_systemd_wants_path().exists(),
+        }
+    }
+
+    pub fn enable(&self) -> io::Result<()> {
+        let entry_path = self.get_entry_path();
+        if let Some(dir) = entry_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&entry_path, self.get_entry_content()?)?;
+
+        if self.backend == AutostartBackend::SystemdUser {
+            let wants_path = self.get_systemd_wants_path();
+            if let Some(dir) = wants_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            if fs::symlink_metadata(&wants_path).is_err() {
+                symlink(&entry_path, &wants_path)?;
+            }
+            if self.update_system {
+                reload_systemd();
+            }
+        }
+        if self.update_system && self.backend == AutostartBackend::LaunchAgent && self.label == APP_LAUNCH_AGENT_LABEL {
+            remove_legacy_login_item();
+        }
+        info!("Enabled autostart of {:?} in {:?}", self.program, entry_path);
+        Ok(())
+    }
+
+    /// Remove the entry. Does nothing if there is none.
+    pub fn disable(&self) -> io::Result<()> {
+        let mut paths = vec![self.get_entry_path()];
+        if self.backend == AutostartBackend::SystemdUser {
+            paths.insert(0, self.get_systemd_wants_path());
+        }
+        for path in paths.iter() {
+            remove_if_exists(path)?;
+        }
+        if self.update_system && self.backend == AutostartBackend::SystemdUser {
+            reload_systemd();
+        }
+        info!("Disabled autostart of {:?}", self.program);
+        Ok(())
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Let a running systemd know about the changed unit. It reads it at the next login anyway.
+fn reload_systemd() {
+    if let Err(e) = Command::new("systemctl").args(["--user", "daemon-reload"]).output() {
+        debug!("Failed to reload systemd: {}", e);
+    }
+}
+
+/// Remove the login item older versions added on macOS, so the app doesn't start twice
+fn remove_legacy_login_item() {
+    let script = r#"tell application "System Events" to delete (every login item whose name is "Flows")"#;
+    match Command::new("osascript").arg("-e").arg(script).output() {
+        Ok(output) if output.status.success() => debug!("Removed the legacy login item"),
+        Ok(output) => debug!(
+            "Failed to remove the legacy login item: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => debug!("Failed to run osascript: {}", e),
+    }
+}
+
+fn get_first_launch_marker_path() -> String {
+    format!("{}/autostart-set", get_log_file_dir_str())
+}
+
+/// Enable autostart on the first launch only, so removing the entry later sticks
+pub fn enable_on_first_launch(autostart: &Autostart) -> io::Result<()> {
+    let marker_path = get_first_launch_marker_path();
+    if Path::new(&marker_path).exists() {
+        return Ok(());
+    }
+    if !autostart.is_enabled() {
+        autostart.enable()?;
+    }
+    fs::create_dir_all(get_log_file_dir_str())?;
+    fs::write(marker_path, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_exec() {
+        assert_eq!(
+            quote_desktop_exec("/opt/My \"Apps\"/$flows"),
+            r#""/opt/My \"Apps\"/\$flows""#
+        );
+        assert_eq!(quote_systemd_exec("/home/me/100% flows"), r#""/home/me/100%% flows""#);
+    }
+}
@@ -0,0 +1,825 @@
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use chrono::LocalResult::Single;
+use std::fs;
+use std::io::{self, Write};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::autostart::Autostart;
+use crate::config::{init_from_config, Config};
+use crate::clock::{get_report_clock, Clock};
+use crate::corrections::{delete_correction, get_corrections, load_corrections, save_correction, Change, Correction};
+use crate::crypto::encrypt_existing_logs;
+use crate::export::{export, export_to_file, ExportFormat, ExportKind};
+use crate::import::{import_from_source, ImportSummary};
+use crate::daemon::DaemonStatus;
+use crate::ipc::{query_app_usages, query_timelines, request_or_else, send_request, subscribe, Request};
+use crate::monitor::CurrentApp;
+use crate::projects::get_project_usages;
+use crate::rules::{dry_run_rules, get_category_usages, RuleSet};
+use crate::status_bar::{get_bar_status, BarFormat};
+use crate::sync::{combine_app_usages, get_device_app_usages, run_sync, SyncSummary};
+use crate::sys_monitor::AppUsage;
+use crate::timeline::{EntryKind, TimelineEntry};
+use crate::utils::get_log_file_dir_str;
+
+const USAGE: &str = "Usage:
+  flows-cli today [--json] [--top N] [--all-devices | --per-device]
+  flows-cli week [--json] [--top N] [--all-devices | --per-device]
+  flows-cli range <start YYYY-MM-DD> <end YYYY-MM-DD> [--json] [--top N] [--all-devices | --per-device]
+  flows-cli app <name> [today | week | range <start> <end>] [--json]
+  flows-cli timeline [today | week | range <start> <end>] [--raw] [--json]
+  flows-cli projects [today | week | range <start> <end>] [--json]
+  flows-cli categories [today | week | range <start> <end>] [--json]
+  flows-cli rules dry-run [today | week | range <start> <end>] [--file <rules file>] [--json]
+  flows-cli correction list [today | week | range <start> <end>] [--json]
+  flows-cli correction add <start> <end> <name> [--category <category>]
+  flows-cli correction reassign <start> <end> <name> [--app <app>]
+  flows-cli correction remove <start> <end> [--app <app>]
+  flows-cli correction project <start> <end> <project> [--app <app>]
+  flows-cli correction edit <id> <start> <end> [name] [--category <category>] [--app <app>]
+  flows-cli correction delete <id>
+  flows-cli export <events|sessions|daily|projects> <csv|json|jsonl> <start YYYY-MM-DD> <end YYYY-MM-DD> [output file]
+  flows-cli import activitywatch <export file>
+  flows-cli import knowledgec <copy of knowledgeC.db>
+  flows-cli status [--format waybar|i3blocks|polybar] [--watch] [--interval SECONDS]
+  flows-cli encrypt-logs
+  flows-cli sync
+  flows-cli autostart <app|daemon> [status|enable|disable]
+  flows-cli daemon <status|pause|resume|current|subscribe|stop>
+  flows-cli daemon tag <label>";
+
+/// Run a `flows-cli` subcommand and return the exit code
+pub fn run(args: &[String]) -> i32 {
+    let Some(command) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    init_from_config(&config);
+
+    let result = match command.as_str() {
+        "today" | "week" | "range" => run_report(&config, args),
+        "app" => run_app_detail(&args[1..]),
+        "timeline" => run_timeline(&args[1..]),
+        "projects" => run_projects(&args[1..]),
+        "categories" => run_categories(&args[1..]),
+        "rules" => run_rules(&args[1..]),
+        "correction" => run_correction(&args[1..]),
+        "status" => run_status(&args[1..]),
+        "encrypt-logs" => request_or_else(&Request::EncryptLogs, encrypt_existing_logs)
+            .map(|count: usize| println!("Encrypted {} files", count)),
+        "sync" => request_or_else(&Request::Sync, || run_sync(&config.sync)).map(|summary: SyncSummary| {
+            println!(
+                "Uploaded {} and downloaded {} day logs",
+                summary.uploaded, summary.downloaded
+            )
+        }),
+        "autostart" => run_autostart(&args[1..]),
+        "export" => run_export(&args[1..]),
+        "import" => run_import(&args[1..]),
+        "daemon" => run_daemon_command(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(invalid_input(USAGE)),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}: {}", command, e);
+            1
+        }
+    }
+}
+
+fn invalid_input<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, e.to_string())
+}
+
+/// Get the timestamps of the start of `start` and the end of `end`, in the days of a clock
+fn parse_date_range(start: &str, end: &str, clock: &dyn Clock) -> io::Result<(u64, u64)> {
+    let start_date = NaiveDate::parse_from_str(start, "%Y-%m-%d").map_err(invalid_input)?;
+    let end_date = NaiveDate::parse_from_str(end, "%Y-%m-%d").map_err(invalid_input)?;
+    let start_timestamp = clock.get_date_start_timestamp(start_date).ok_or_else(|| invalid_input("Invalid start date"))?;
+    let end_timestamp = clock.get_date_start_timestamp(end_date + ChronoDuration::days(1))
+        .ok_or_else(|| invalid_input("Invalid end date"))?
+        - 1;
+    Ok((start_timestamp, end_timestamp))
+}
+
+fn run_export(args: &[String]) -> io::Result<()> {
+    if args.len() < 4 {
+        return Err(invalid_input(USAGE));
+    }
+    let kind: ExportKind = args[0].parse().map_err(invalid_input)?;
+    let format: ExportFormat = args[1].parse().map_err(invalid_input)?;
+    let (start_timestamp, end_timestamp) = parse_date_range(&args[2], &args[3], get_report_clock().as_ref())?;
+
+    match args.get(4) {
+        Some(file_path) => export_to_file(file_path, kind, format, start_timestamp, end_timestamp),
+        None => {
+            let mut stdout = io::stdout().lock();
+            export(&mut stdout, kind, format, start_timestamp, end_timestamp)?;
+            stdout.flush()
+        }
+    }
+}
+
+fn run_autostart(args: &[String]) -> io::Result<()> {
+    let autostart = match args.first().map(|arg| arg.as_str()) {
+        Some("app") => Autostart::app()?,
+        Some("daemon") => Autostart::daemon()?,
+        _ => return Err(invalid_input(USAGE)),
+    };
+    match args.get(1).map(|arg| arg.as_str()) {
+        None | Some("status") => {
+            let state = if autostart.is_enabled() { "enabled" } else { "disabled" };
+            println!("{} ({})", state, autostart.get_entry_path().display());
+            Ok(())
+        }
+        Some("enable") => autostart.enable().map(|()| println!("Enabled {}", autostart.get_entry_path().display())),
+        Some("disable") => autostart.disable().map(|()| println!("Disabled autostart")),
+        _ => Err(invalid_input(USAGE)),
+    }
+}
+
+fn run_status(args: &[String]) -> io::Result<()> {
+    let mut format = BarFormat::Polybar;
+    let mut watch = false;
+    let mut interval = Duration::from_secs(5);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().ok_or_else(|| invalid_input("--format needs a value"))?;
+                format = value.parse().map_err(invalid_input)?;
+            }
+            "--watch" => watch = true,
+            "--interval" => {
+                let value = args.next().ok_or_else(|| invalid_input("--interval needs a number"))?;
+                interval = parse_interval(value)?;
+            }
+            _ => return Err(invalid_input(USAGE)),
+        }
+    }
+
+    if !watch {
+        println!("{}", get_bar_status()?.format(format));
+        return Ok(());
+    }
+
+    // Update right away when the app in front changes, and at least every interval
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        if let Ok(events) = subscribe() {
+            for _ in events {
+                if sender.send(()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    let mut stdout = io::stdout().lock();
+    loop {
+        writeln!(stdout, "{}", get_bar_status()?.format(format))?;
+        stdout.flush()?;
+        if let Err(RecvTimeoutError::Disconnected) = receiver.recv_timeout(interval) {
+            // No events without the daemon, so only update every interval
+            thread::sleep(interval);
+        }
+        // Several events often come at once, e.g. idle and focus changes
+        while receiver.try_recv().is_ok() {}
+    }
+}
+
+/// Parse the seconds between status updates, at least one so watching never spins
+fn parse_interval(value: &str) -> io::Result<Duration> {
+    match value.parse().map_err(invalid_input)? {
+        0 => Err(invalid_input("--interval needs at least 1 second")),
+        secs => Ok(Duration::from_secs(secs)),
+    }
+}
+
+fn run_import(args: &[String]) -> io::Result<()> {
+    let (Some(source), Some(file_path)) = (args.first(), args.get(1)) else {
+        return Err(invalid_input(USAGE));
+    };
+    // The daemon runs in another working directory
+    let file_path = fs::canonicalize(file_path)?.to_string_lossy().to_string();
+    let request = Request::Import {
+        source: source.clone(),
+        file_path: file_path.clone(),
+    };
+    // The daemon owns the day logs, so it does the writing if it's running
+    let summary: ImportSummary = request_or_else(&request, || import_from_source(source, &file_path))?;
+    println!("Imported {} sessions ({} seconds)", summary.sessions, summary.secs);
+    Ok(())
+}
+
+/// Options shared by the report commands
+struct ReportOptions {
+    json: bool,
+    top: Option<usize>,
+    /// Add up the usages of all synced devices
+    all_devices: bool,
+    /// Show the usages of each synced device separately
+    per_device: bool,
+    /// Show the timeline as recorded, without smoothing
+    raw: bool,
+    /// A file to read instead of the usual one
+    file: Option<String>,
+    /// The arguments that are not options
+    positional: Vec<String>,
+}
+
+fn parse_report_options(args: &[String]) -> io::Result<ReportOptions> {
+    let mut options = ReportOptions {
+        json: false,
+        top: None,
+        all_devices: false,
+        per_device: false,
+        raw: false,
+        file: None,
+        positional: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--all-devices" => options.all_devices = true,
+            "--per-device" => options.per_device = true,
+            "--raw" => options.raw = true,
+            "--file" => options.file = Some(args.next().ok_or_else(|| invalid_input("--file needs a path"))?.clone()),
+            "--top" => {
+                let top = args.next().ok_or_else(|| invalid_input("--top needs a number"))?;
+                options.top = Some(top.parse().map_err(invalid_input)?);
+            }
+            _ => options.positional.push(arg.clone()),
+        }
+    }
+    Ok(options)
+}
+
+/// Get the time range of `today`, `week` or `range <start> <end>`, in the days of a clock
+fn parse_period(args: &[String], clock: &dyn Clock) -> io::Result<(u64, u64)> {
+    let today = clock.get_date(clock.now()).unwrap_or_else(|| Local::now().date_naive());
+    match args.first().map(|arg| arg.as_str()) {
+        None | Some("today") => {
+            let date = today.format("%Y-%m-%d").to_string();
+            parse_date_range(&date, &date, clock)
+        }
+        Some("week") => {
+            let monday = today - ChronoDuration::days(today.weekday().num_days_from_monday() as i64);
+            parse_date_range(&monday.format("%Y-%m-%d").to_string(), &today.format("%Y-%m-%d").to_string(), clock)
+        }
+        Some("range") => match (args.get(1), args.get(2)) {
+            (Some(start), Some(end)) => parse_date_range(start, end, clock),
+            _ => Err(invalid_input(USAGE)),
+        },
+        Some(period) => Err(invalid_input(format!("Unknown period: {}", period))),
+    }
+}
+
+/// Format seconds like the dashboard does, e.g. `1h 2m 3s`
+pub fn format_secs(secs: u64) -> String {
+    let mut parts = Vec::new();
+    if secs >= 3600 {
+        parts.push(format!("{}h", secs / 3600));
+    }
+    if secs >= 60 {
+        parts.push(format!("{}m", (secs % 3600) / 60));
+    }
+    parts.push(format!("{}s", secs % 60));
+    parts.join(" ")
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    match Local.timestamp_millis_opt(timestamp as i64) {
+        Single(date) => date.format("%Y-%m-%d %H:%M:%S").to_string(),
+        _ => timestamp.to_string(),
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout)
+}
+
+fn run_report(config: &Config, args: &[String]) -> io::Result<()> {
+    let options = parse_report_options(args)?;
+    let clock = get_report_clock();
+    let (start_timestamp, end_timestamp) = parse_period(&options.positional, clock.as_ref())?;
+    let end_timestamp = end_timestamp.min(clock.now());
+
+    if options.per_device {
+        let mut device_app_usages = get_device_app_usages(&config.sync, start_timestamp, end_timestamp)?;
+        if options.json {
+            for (_, app_usages) in device_app_usages.iter_mut() {
+                app_usages.truncate(options.top.unwrap_or(usize::MAX));
+            }
+            let devices: Vec<_> = device_app_usages
+                .iter()
+                .map(|(device, app_usages)| serde_json::json!({ "device": device, "apps": app_usages }))
+                .collect();
+            return print_json(&devices);
+        }
+        for (index, (device, app_usages)) in device_app_usages.into_iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            println!("{} ({})", device.name, device.id);
+            print_report_table(app_usages, options.top);
+        }
+        return Ok(());
+    }
+
+    let mut app_usages = if options.all_devices {
+        let device_app_usages = get_device_app_usages(&config.sync, start_timestamp, end_timestamp)?;
+        combine_app_usages(device_app_usages.into_iter().map(|(_, app_usages)| app_usages))
+    } else {
+        query_app_usages(start_timestamp, end_timestamp)?
+    };
+    if options.json {
+        app_usages.truncate(options.top.unwrap_or(usize::MAX));
+        return print_json(&app_usages);
+    }
+    print_report_table(app_usages, options.top);
+    Ok(())
+}
+
+/// Print app usages as a table, with the total of all of them, even those beyond `top`
+fn print_report_table(mut app_usages: Vec<AppUsage>, top: Option<usize>) {
+    let total_secs: u64 = app_usages.iter().map(|app| app.total_secs).sum();
+    if let Some(top) = top {
+        app_usages.truncate(top);
+    }
+
+    let name_width = app_usages.iter().map(|app| app.name.chars().count()).max().unwrap_or(0).max(5);
+    println!("{:<name_width$}  {:>12}  {:>8}", "App", "Time", "Sessions");
+    for app in app_usages.iter() {
+        println!(
+            "{:<name_width$}  {:>12}  {:>8}",
+            app.name,
+            format_secs(app.total_secs),
+            app.durations.len()
+        );
+    }
+    println!("{:<name_width$}  {:>12}", "Total", format_secs(total_secs));
+}
+
+fn run_app_detail(args: &[String]) -> io::Result<()> {
+    let options = parse_report_options(args)?;
+    let Some((name, period)) = options.positional.split_first() else {
+        return Err(invalid_input(USAGE));
+    };
+    let clock = get_report_clock();
+    let (start_timestamp, end_timestamp) = parse_period(period, clock.as_ref())?;
+    let app: AppUsage = query_app_usages(start_timestamp, end_timestamp.min(clock.now()))?
+        .into_iter()
+        .find(|app| app.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No usage of {} in this period", name)))?;
+
+    if options.json {
+        return print_json(&app);
+    }
+
+    println!("{}", app.name);
+    println!("Path:     {}", app.path);
+    println!("Total:    {}", format_secs(app.total_secs));
+    println!("Sessions: {}", app.durations.len());
+    for &(start, end) in app.durations.iter() {
+        println!(
+            "  {} - {}  {}",
+            format_timestamp(start),
+            format_timestamp(end),
+            format_secs((end - start) / 1000)
+        );
+    }
+    Ok(())
+}
+
+/// Describe a timeline entry, e.g. `Code · main.rs [flows] (Development) #work` or `(idle)`
+fn format_timeline_entry(entry: &TimelineEntry) -> String {
+    let mut description = match (entry.kind, &entry.name, &entry.title) {
+        (EntryKind::App, Some(name), Some(title)) => format!("{} · {}", name, title),
+        (EntryKind::App, Some(name), None) => name.clone(),
+        (EntryKind::App, None, _) => "-".to_string(),
+        (EntryKind::Idle, _, _) => "(idle)".to_string(),
+        (EntryKind::Away, _, _) => "(away)".to_string(),
+    };
+    if let Some(project) = &entry.project {
+        description.push_str(&format!(" [{}]", project));
+    }
+    if let Some(category) = &entry.category {
+        description.push_str(&format!(" ({})", category));
+    }
+    for tag in entry.tags.iter() {
+        description.push_str(&format!(" #{}", tag));
+    }
+    description
+}
+
+/// Print the time of each name as a table, e.g. of projects
+fn print_totals_table(heading: &str, totals: &[(&str, u64)]) {
+    let name_width = totals
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(heading.chars().count());
+    println!("{:<name_width$}  {:>12}", heading, "Time");
+    for (name, total_secs) in totals.iter() {
+        println!("{:<name_width$}  {:>12}", name, format_secs(*total_secs));
+    }
+}
+
+fn run_projects(args: &[String]) -> io::Result<()> {
+    let options = parse_report_options(args)?;
+    let clock = get_report_clock();
+    let (start_timestamp, end_timestamp) = parse_period(&options.positional, clock.as_ref())?;
+    let timelines = query_timelines(start_timestamp, end_timestamp.min(clock.now()))?;
+    let project_usages = get_project_usages(&timelines.smoothed);
+
+    if options.json {
+        return print_json(&project_usages);
+    }
+    let totals: Vec<(&str, u64)> =
+        project_usages.iter().map(|usage| (usage.project.as_str(), usage.total_secs)).collect();
+    print_totals_table("Project", &totals);
+    Ok(())
+}
+
+fn run_categories(args: &[String]) -> io::Result<()> {
+    let options = parse_report_options(args)?;
+    let clock = get_report_clock();
+    let (start_timestamp, end_timestamp) = parse_period(&options.positional, clock.as_ref())?;
+    let timelines = query_timelines(start_timestamp, end_timestamp.min(clock.now()))?;
+    let category_usages = get_category_usages(&timelines.smoothed);
+
+    if options.json {
+        return print_json(&category_usages);
+    }
+    let totals: Vec<(&str, u64)> =
+        category_usages.iter().map(|usage| (usage.category.as_str(), usage.total_secs)).collect();
+    print_totals_table("Category", &totals);
+    Ok(())
+}
+
+/// Show what the rules file, or another one, does to the reports of a period without changing anything
+fn run_rules(args: &[String]) -> io::Result<()> {
+    let Some(("dry-run", args)) = args.split_first().map(|(command, args)| (command.as_str(), args)) else {
+        return Err(invalid_input(USAGE));
+    };
+    let options = parse_report_options(args)?;
+    let clock = get_report_clock();
+    let (start_timestamp, end_timestamp) = parse_period(&options.positional, clock.as_ref())?;
+    let rules_path = options.file.unwrap_or_else(|| format!("{}/rules.json", get_log_file_dir_str()));
+    let rules = RuleSet::load(&rules_path)?;
+    let dry_run = dry_run_rules(&rules, start_timestamp, end_timestamp.min(clock.now()))?;
+
+    if options.json {
+        return print_json(&dry_run);
+    }
+    println!("{} rules in {}", rules.rules().count(), rules_path);
+    println!();
+    let rule_totals: Vec<(&str, u64)> =
+        dry_run.rules.iter().map(|usage| (usage.rule.as_str(), usage.total_secs)).collect();
+    print_totals_table("Rule", &rule_totals);
+    println!();
+    let mut category_totals: Vec<(&str, u64)> =
+        dry_run.categories.iter().map(|usage| (usage.category.as_str(), usage.total_secs)).collect();
+    category_totals.push(("(none)", dry_run.uncategorized_secs));
+    print_totals_table("Category", &category_totals);
+    if !dry_run.projects.is_empty() {
+        println!();
+        let project_totals: Vec<(&str, u64)> =
+            dry_run.projects.iter().map(|usage| (usage.project.as_str(), usage.total_secs)).collect();
+        print_totals_table("Project", &project_totals);
+    }
+    if !dry_run.tags.is_empty() {
+        println!();
+        let tag_totals: Vec<(&str, u64)> =
+            dry_run.tags.iter().map(|usage| (usage.tag.as_str(), usage.total_secs)).collect();
+        print_totals_table("Tag", &tag_totals);
+    }
+    Ok(())
+}
+
+fn run_timeline(args: &[String]) -> io::Result<()> {
+    let options = parse_report_options(args)?;
+    let clock = get_report_clock();
+    let (start_timestamp, end_timestamp) = parse_period(&options.positional, clock.as_ref())?;
+    let timelines = query_timelines(start_timestamp, end_timestamp.min(clock.now()))?;
+    let timeline = if options.raw { timelines.raw } else { timelines.smoothed };
+
+    if options.json {
+        return print_json(&timeline);
+    }
+    for entry in timeline.iter() {
+        println!(
+            "{} - {}  {:>12}  {}",
+            format_timestamp(entry.start),
+            format_timestamp(entry.end),
+            format_secs((entry.end - entry.start) / 1000),
+            format_timeline_entry(entry)
+        );
+    }
+    Ok(())
+}
+
+/// Parse a time in a timezone, `YYYY-MM-DDTHH:MM`, or `HH:MM` for today on a clock
+fn parse_time<Z: TimeZone>(value: &str, clock: &dyn Clock, timezone: &Z) -> io::Result<u64> {
+    let date_time = match NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M") {
+        Ok(date_time) => date_time,
+        Err(_) => {
+            let time = NaiveTime::parse_from_str(value, "%H:%M")
+                .map_err(|_| invalid_input(format!("Invalid time {}, expected HH:MM or YYYY-MM-DDTHH:MM", value)))?;
+            let today = clock.get_date(clock.now()).unwrap_or_else(|| Local::now().date_naive());
+            today.and_time(time)
+        }
+    };
+    timezone
+        .from_local_datetime(&date_time)
+        .earliest()
+        .map(|date_time| date_time.timestamp_millis() as u64)
+        .ok_or_else(|| invalid_input(format!("{} doesn't exist in the local timezone", value)))
+}
+
+/// Describe what a correction does, e.g. `add Meeting (Communication)`
+fn format_change(change: &Change) -> String {
+    match change {
+        Change::Add { name, category: Some(category) } => format!("add {} ({})", name, category),
+        Change::Add { name, category: None } => format!("add {}", name),
+        Change::Reassign { app: Some(app), name } => format!("reassign {} to {}", app, name),
+        Change::Reassign { app: None, name } => format!("reassign to {}", name),
+        Change::Remove { app: Some(app) } => format!("remove {}", app),
+        Change::Remove { app: None } => "remove".to_string(),
+        Change::Project { app: Some(app), project } => format!("count {} for {}", app, project),
+        Change::Project { app: None, project } => format!("count for {}", project),
+    }
+}
+
+fn run_correction(args: &[String]) -> io::Result<()> {
+    let Some((action, args)) = args.split_first() else {
+        return Err(invalid_input(USAGE));
+    };
+    let clock = get_report_clock();
+    if action == "list" {
+        let options = parse_report_options(args)?;
+        let (start_timestamp, end_timestamp) = parse_period(&options.positional, clock.as_ref())?;
+        let corrections = get_corrections(start_timestamp, end_timestamp)?;
+        if options.json {
+            return print_json(&corrections);
+        }
+        for correction in corrections.iter() {
+            println!(
+                "{:>4}  {} - {}  {}",
+                correction.id,
+                format_timestamp(correction.start),
+                format_timestamp(correction.end),
+                format_change(&correction.change)
+            );
+        }
+        return Ok(());
+    }
+    if action == "delete" {
+        let id = args.first().ok_or_else(|| invalid_input(USAGE))?.parse().map_err(invalid_input)?;
+        let deleted: Correction = request_or_else(&Request::DeleteCorrection { id }, || delete_correction(id))?;
+        println!("Deleted correction {}: {}", deleted.id, format_change(&deleted.change));
+        return Ok(());
+    }
+
+    let mut category = None;
+    let mut app = None;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--category" => category = Some(args.next().ok_or_else(|| invalid_input("--category needs a value"))?.clone()),
+            "--app" => app = Some(args.next().ok_or_else(|| invalid_input("--app needs a value"))?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let correction = if action == "edit" {
+        let [id, start, end, rest @ ..] = positional.as_slice() else {
+            return Err(invalid_input(USAGE));
+        };
+        let id: u64 = id.parse().map_err(invalid_input)?;
+        let mut correction = load_corrections(&get_log_file_dir_str())?
+            .into_iter()
+            .find(|correction| correction.id == id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No correction {}", id)))?;
+        correction.start = parse_time(start, clock.as_ref(), &Local)?;
+        correction.end = parse_time(end, clock.as_ref(), &Local)?;
+        let new_name = (!rest.is_empty()).then(|| rest.join(" "));
+        match &mut correction.change {
+            Change::Add { name, category: old_category } => {
+                *name = new_name.unwrap_or(name.clone());
+                *old_category = category.or(old_category.take());
+            }
+            Change::Reassign { app: old_app, name } => {
+                *name = new_name.unwrap_or(name.clone());
+                *old_app = app.or(old_app.take());
+            }
+            Change::Remove { app: old_app } => *old_app = app.or(old_app.take()),
+            Change::Project { app: old_app, project } => {
+                *project = new_name.unwrap_or(project.clone());
+                *old_app = app.or(old_app.take());
+            }
+        }
+        correction
+    } else {
+        let [start, end, rest @ ..] = positional.as_slice() else {
+            return Err(invalid_input(USAGE));
+        };
+        let name = rest.join(" ");
+        let change = match action.as_str() {
+            "add" => Change::Add { name, category },
+            "reassign" => Change::Reassign { app, name },
+            "remove" => Change::Remove { app },
+            "project" => Change::Project { app, project: name },
+            _ => return Err(invalid_input(USAGE)),
+        };
+        Correction {
+            id: 0,
+            start: parse_time(start, clock.as_ref(), &Local)?,
+            end: parse_time(end, clock.as_ref(), &Local)?,
+            change,
+        }
+    };
+
+    let request = Request::SaveCorrection {
+        correction: correction.clone(),
+    };
+    let saved: Correction = request_or_else(&request, || save_correction(correction))?;
+    println!("Saved correction {}: {}", saved.id, format_change(&saved.change));
+    Ok(())
+}
+
+fn format_current_app(current: &CurrentApp) -> String {
+    match (&current.name, &current.path) {
+        (Some(name), _) => name.clone(),
+        (None, Some(path)) => path.clone(),
+        (None, None) => "-".to_string(),
+    }
+}
+
+fn run_daemon_command(args: &[String]) -> io::Result<()> {
+    match args.first().map(|arg| arg.as_str()) {
+        Some("status") => {
+            match send_request(&Request::Status) {
+                Ok(data) => {
+                    let status: DaemonStatus = serde_json::from_value(data)?;
+                    let state = if status.paused { "paused" } else { "running" };
+                    println!("flows-daemon is {} (PID {})", state, status.pid);
+                    println!("Current app: {}", format_current_app(&status.current));
+                }
+                Err(_) => println!("flows-daemon is not running"),
+            }
+            Ok(())
+        }
+        Some("pause") => send_request(&Request::Pause).map(|data| {
+            if data == true {
+                println!("Paused tracking");
+            } else {
+                println!("Tracking is already paused");
+            }
+        }),
+        Some("resume") => send_request(&Request::Resume).map(|data| {
+            if data == true {
+                println!("Resumed tracking");
+            } else {
+                println!("Tracking is not paused");
+            }
+        }),
+        Some("current") => {
+            let current: CurrentApp = serde_json::from_value(send_request(&Request::Current)?)?;
+            println!("{}", format_current_app(&current));
+            Ok(())
+        }
+        Some("tag") if args.len() > 1 => {
+            let label = args[1..].join(" ");
+            send_request(&Request::Tag { label: label.clone() }).map(|_| println!("Tagged: {}", label))
+        }
+        Some("subscribe") => {
+            for event in subscribe()? {
+                println!("{}", serde_json::to_string(&event?)?);
+            }
+            Ok(())
+        }
+        Some("stop") => send_request(&Request::Stop).map(|_| println!("Stopped flows-daemon")),
+        _ => Err(invalid_input(USAGE)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use chrono_tz::Europe::Berlin;
+    use chrono_tz::Tz;
+
+    fn at(date: &str, time: &str) -> u64 {
+        let local = NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap();
+        Berlin.from_local_datetime(&local).earliest().unwrap().timestamp_millis() as u64
+    }
+
+    fn berlin_at(date: &str, time: &str) -> FixedClock<Tz> {
+        FixedClock {
+            now: at(date, time),
+            timezone: Berlin,
+        }
+    }
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_date_range() {
+        let clock = berlin_at("2026-06-10", "12:00");
+        assert_eq!(
+            parse_date_range("2026-06-01", "2026-06-02", &clock).unwrap(),
+            (at("2026-06-01", "00:00"), at("2026-06-03", "00:00") - 1)
+        );
+        // The days the clocks change are an hour shorter or longer
+        let (start, end) = parse_date_range("2026-03-29", "2026-03-29", &clock).unwrap();
+        assert_eq!(end + 1 - start, 23 * 3_600_000);
+        let (start, end) = parse_date_range("2026-10-25", "2026-10-25", &clock).unwrap();
+        assert_eq!(end + 1 - start, 25 * 3_600_000);
+        assert!(parse_date_range("2026-06-31", "2026-07-01", &clock).is_err());
+        assert!(parse_date_range("yesterday", "2026-07-01", &clock).is_err());
+    }
+
+    #[test]
+    fn test_parse_period() {
+        // A Wednesday
+        let clock = berlin_at("2026-06-10", "12:00");
+        let today = (at("2026-06-10", "00:00"), at("2026-06-11", "00:00") - 1);
+        assert_eq!(parse_period(&[], &clock).unwrap(), today);
+        assert_eq!(parse_period(&args(&["today"]), &clock).unwrap(), today);
+        assert_eq!(
+            parse_period(&args(&["week"]), &clock).unwrap(),
+            (at("2026-06-08", "00:00"), at("2026-06-11", "00:00") - 1)
+        );
+        assert_eq!(
+            parse_period(&args(&["range", "2026-06-01", "2026-06-01"]), &clock).unwrap(),
+            (at("2026-06-01", "00:00"), at("2026-06-02", "00:00") - 1)
+        );
+        assert!(parse_period(&args(&["range", "2026-06-01"]), &clock).is_err());
+        assert!(parse_period(&args(&["month"]), &clock).is_err());
+
+        // Right at midnight, today has just begun, in the timezone of the clock rather than the system one
+        let clock = berlin_at("2026-06-10", "00:00");
+        assert_eq!(parse_period(&[], &clock).unwrap().0, at("2026-06-10", "00:00"));
+        let clock = FixedClock {
+            now: at("2026-06-10", "00:00") - 1,
+            timezone: Berlin,
+        };
+        assert_eq!(parse_period(&[], &clock).unwrap().0, at("2026-06-09", "00:00"));
+
+        // A week with the change to summer time
+        let clock = berlin_at("2026-03-29", "12:00");
+        let (start, end) = parse_period(&args(&["week"]), &clock).unwrap();
+        assert_eq!(start, at("2026-03-23", "00:00"));
+        assert_eq!(end + 1 - start, (7 * 24 - 1) * 3_600_000);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let clock = berlin_at("2026-06-10", "12:00");
+        assert_eq!(parse_time("2026-06-01T09:30", &clock, &Berlin).unwrap(), at("2026-06-01", "09:30"));
+        assert_eq!(parse_time("09:30", &clock, &Berlin).unwrap(), at("2026-06-10", "09:30"));
+        assert_eq!(parse_time("00:00", &clock, &Berlin).unwrap(), at("2026-06-10", "00:00"));
+        assert!(parse_time("24:00", &clock, &Berlin).is_err());
+        assert!(parse_time("9.30", &clock, &Berlin).is_err());
+
+        // Skipped when the clocks are set forward, and the first of the two when they are set back
+        assert!(parse_time("2026-03-29T02:30", &clock, &Berlin).is_err());
+        assert_eq!(
+            parse_time("2026-10-25T02:30", &clock, &Berlin).unwrap(),
+            at("2026-10-25", "01:30") + 3_600_000
+        );
+        let two_hours_later = parse_time("2026-10-25T03:00", &clock, &Berlin).unwrap();
+        assert_eq!(two_hours_later - parse_time("2026-10-25T02:00", &clock, &Berlin).unwrap(), 2 * 3_600_000);
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("5").unwrap(), Duration::from_secs(5));
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("-1").is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::{Duration, Local, LocalResult, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::RwLock;
+use tracing::{error, info};
+
+use crate::config::ReportsConfig;
+use crate::crypto::seal_line;
+use crate::utils::{get_current_timestamp, get_log_file_dir_str, read_log_lines};
+
+/// The current time, and the timezone that decides which day a timestamp falls on
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch
+    fn now(&self) -> u64;
+
+    /// The date of a timestamp, which names its day log
+    fn get_date(&self, timestamp: u64) -> Option<NaiveDate>;
+
+    /// The timestamp of the start of a date: midnight, or the first time after it on days that skip midnight
+    fn get_date_start_timestamp(&self, date: NaiveDate) -> Option<u64>;
+
+    fn get_day_start_timestamp(&self, timestamp: u64) -> Option<u64> {
+        self.get_date_start_timestamp(self.get_date(timestamp)?)
+    }
+
+    /// The start of the current day and now, as the views of today query them
+    fn get_today_so_far(&self) -> (u64, u64) {
+        let now = self.now();
+        (self.get_day_start_timestamp(now).unwrap_or(now), now)
+    }
+
+    /// The day a timestamp falls on
+    fn get_day_range(&self, timestamp: u64) -> Option<DayRange> {
+        let date = self.get_date(timestamp)?;
+        let start_timestamp = self.get_date_start_timestamp(date)?;
+        let next_start_timestamp = self.get_date_start_timestamp(date.succ_opt()?)?;
+        Some(DayRange {
+            date: date.format("%Y-%m-%d").to_string(),
+            start_timestamp,
+            end_timestamp: next_start_timestamp.saturating_sub(1).max(start_timestamp),
+        })
+    }
+}
+
+/// A day as reports count it, from its first to its last millisecond
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayRange {
+    /// `%Y-%m-%d`
+    pub date: String,
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+}
+
+/// The system time in the system timezone
+pub struct SystemClock;
+
+/// The system time in a timezone of its own, e.g. the one chosen for reports
+pub struct ZonedClock {
+    pub timezone: Tz,
+}
+
+/// A clock stopped at `now`, in a timezone of its own, e.g. for tests
+pub struct FixedClock<Z: TimeZone> {
+    pub now: u64,
+    pub timezone: Z,
+}
+
+/// The system time in the timezones the user was in, from the zone changes the monitor recorded
+///
+/// Each timestamp falls on its date where the user was at the time. When flying west after midnight,
+/// the date would go back, so the day that already started goes on instead, and days never overlap.
+#[derive(Clone)]
+pub struct ZoneHistory {
+    /// When the user arrived in each timezone, the first one also covering all time before
+    changes: Vec<(u64, Tz)>,
+    /// The latest date before each change
+    floors: Vec<Option<NaiveDate>>,
+}
+
+fn get_date_in<Z: TimeZone>(timezone: &Z, timestamp: u64) -> Option<NaiveDate> {
+    timezone
+        .timestamp_millis_opt(timestamp as i64)
+        .single()
+        .map(|date_time| date_time.date_naive())
+}
+
+fn get_date_start_in<Z: TimeZone>(timezone: &Z, date: NaiveDate) -> Option<u64> {
+    let midnight = date.and_hms_opt(0, 0, 0)?;
+    // A DST change at midnight skips it, and the day starts when the clocks were set forward
+    (0..=24).find_map(|minutes| {
+        match (midnight + Duration::minutes(minutes * 5)).and_local_timezone(timezone.clone()) {
+            LocalResult::Single(start) | LocalResult::Ambiguous(start, _) => Some(start.timestamp_millis() as u64),
+            LocalResult::None => None,
+        }
+    })
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        get_current_timestamp()
+    }
+
+    fn get_date(&self, timestamp: u64) -> Option<NaiveDate> {
+        get_date_in(&Local, timestamp)
+    }
+
+    fn get_date_start_timestamp(&self, date: NaiveDate) -> Option<u64> {
+        get_date_start_in(&Local, date)
+    }
+}
+
+impl Clock for ZonedClock {
+    fn now(&self) -> u64 {
+        get_current_timestamp()
+    }
+
+    fn get_date(&self, timestamp: u64) -> Option<NaiveDate> {
+        get_date_in(&self.timezone, timestamp)
+    }
+
+    fn get_date_start_timestamp(&self, date: NaiveDate) -> Option<u64> {
+        get_date_start_in(&self.timezone, date)
+    }
+}
+
+impl<Z: TimeZone + Send + Sync> Clock for FixedClock<Z> {
+    fn now(&self) -> u64 {
+        self.now
+    }
+
+    fn get_date(&self, timestamp: u64) -> Option<NaiveDate> {
+        get_date_in(&self.timezone, timestamp)
+    }
+
+    fn get_date_start_timestamp(&self, date: NaiveDate) -> Option<u64> {
+        get_date_start_in(&self.timezone, date)
+    }
+}
+
+impl ZoneHistory {
+    /// `changes` are when the user arrived in each timezone, in order. Returns `None` without any.
+    pub fn new(changes: Vec<(u64, Tz)>) -> Option<ZoneHistory> {
+        if changes.is_empty() {
+            return None;
+        }
+        let mut floors = vec![None];
+        for window in changes.windows(2) {
+            let (_, previous_zone) = &window[0];
+            let (timestamp, _) = &window[1];
+            let previous_date = get_date_in(previous_zone, timestamp.saturating_sub(1));
+            floors.push(floors.last().copied().flatten().max(previous_date));
+        }
+        Some(ZoneHistory { changes, floors })
+    }
+
+    /// Load the zone changes recorded in a data directory
+    pub fn load(data_dir: &str) -> io::Result<Option<ZoneHistory>> {
+        let lines = match read_log_lines(get_zone_history_path(data_dir)) {
+            Ok(lines) => lines,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let changes = lines.iter().filter_map(|line| parse_zone_change(line)).collect();
+        Ok(ZoneHistory::new(changes))
+    }
+
+    /// The timezone the user was in at a timestamp
+    pub fn get_zone(&self, timestamp: u64) -> Tz {
+        self.changes[self.get_index(timestamp)].1
+    }
+
+    fn get_index(&self, timestamp: u64) -> usize {
+        self.changes
+            .partition_point(|(change_timestamp, _)| *change_timestamp <= timestamp)
+            .saturating_sub(1)
+    }
+}
+
+impl Clock for ZoneHistory {
+    fn now(&self) -> u64 {
+        get_current_timestamp()
+    }
+
+    fn get_date(&self, timestamp: u64) -> Option<NaiveDate> {
+        let index = self.get_index(timestamp);
+        let date = get_date_in(&self.changes[index].1, timestamp)?;
+        Some(self.floors[index].map_or(date, |floor| floor.max(date)))
+    }
+
+    fn get_date_start_timestamp(&self, date: NaiveDate) -> Option<u64> {
+        for (index, (change_timestamp, zone)) in self.changes.iter().enumerate() {
+            // The first zone also covers the time before it
+            let from = if index == 0 { 0 } else { *change_timestamp };
+            if self.floors[index].is_some_and(|floor| floor >= date) {
+                return Some(from);
+            }
+            let start = get_date_start_in(zone, date)?.max(from);
+            match self.changes.get(index + 1) {
+                Some((next_timestamp, _)) if start >= *next_timestamp => continue,
+                _ => return Some(start),
+            }
+        }
+        None
+    }
+}
+
+fn get_zone_history_path(data_dir: &str) -> String {
+    format!("{}/zones", data_dir)
+}
+
+/// Parse a line of the zone history, `timestamp,zone`
+fn parse_zone_change(line: &str) -> Option<(u64, Tz)> {
+    let (timestamp, zone) = line.split_once(',')?;
+    Some((timestamp.parse().ok()?, zone.parse().ok()?))
+}
+
+/// The zone history last loaded, with its data directory and the length of its file then
+static ZONE_HISTORY: RwLock<Option<(String, u64, Option<ZoneHistory>)>> = RwLock::new(None);
+
+/// Load the zone history of a data directory, reading the file again only when it has grown
+///
+/// The monitor may run in another process, so a cached history is only used while the file has the same length.
+fn load_zone_history(data_dir: &str) -> io::Result<Option<ZoneHistory>> {
+    let length = match fs::metadata(get_zone_history_path(data_dir)) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e),
+    };
+    if let Some((cached_dir, cached_length, history)) = ZONE_HISTORY.read().unwrap().as_ref() {
+        if cached_dir == data_dir && *cached_length == length {
+            return Ok(history.clone());
+        }
+    }
+    let history = ZoneHistory::load(data_dir)?;
+    *ZONE_HISTORY.write().unwrap() = Some((data_dir.to_string(), length, history.clone()));
+    Ok(history)
+}
+
+/// The IANA name of the system timezone, e.g. `Europe/Berlin`, if it is known
+pub fn get_system_zone() -> Option<Tz> {
+    iana_time_zone::get_timezone().ok()?.parse().ok()
+}
+
+/// Record that the user is in `zone` from `timestamp` on, unless that is the last recorded zone already
+pub fn record_zone(timestamp: u64, zone: Tz) -> io::Result<()> {
+    let data_dir = get_log_file_dir_str();
+    if let Some(history) = load_zone_history(&data_dir)? {
+        if history.changes.last().is_some_and(|(_, last_zone)| *last_zone == zone) {
+            return Ok(());
+        }
+    }
+    info!("Timezone changed to {}", zone.name());
+    let line = seal_line(&format!("{},{}", timestamp, zone.name()))?;
+    fs::create_dir_all(&data_dir)?;
+    let mut file = OpenOptions::new().append(true).create(true).open(get_zone_history_path(&data_dir))?;
+    writeln!(file, "{}", line)?;
+    file.sync_data()?;
+    // Load it again with the new zone next time
+    *ZONE_HISTORY.write().unwrap() = None;
+    Ok(())
+}
+
+/// The timezone chosen for reports, `None` for the zone the user was in at the time
+static REPORT_ZONE: RwLock<Option<Tz>> = RwLock::new(None);
+
+/// Use the report timezone of the config
+pub fn init_report_clock(config: &ReportsConfig) {
+    let zone = match config.timezone.as_deref() {
+        None | Some("local") => None,
+        Some(name) => match name.parse::<Tz>() {
+            Ok(zone) => Some(zone),
+            Err(e) => {
+                error!("Unknown report timezone {}: {}", name, e);
+                None
+            }
+        },
+    };
+    *REPORT_ZONE.write().unwrap() = zone;
+}
+
+/// The clock that decides which day events fall on in reports
+///
+/// Without a timezone in the config, that is the recorded zone history, or the system timezone
+/// before any zone was recorded.
+pub fn get_report_clock() -> Box<dyn Clock> {
+    if let Some(timezone) = *REPORT_ZONE.read().unwrap() {
+        return Box::new(ZonedClock { timezone });
+    }
+    match load_zone_history(&get_log_file_dir_str()) {
+        Ok(Some(history)) => Box::new(history),
+        Ok(None) => Box::new(SystemClock),
+        Err(e) => {
+            error!("Failed to load the zone history: {}", e);
+            Box::new(SystemClock)
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use tracing::info;
+
+use crate::clock::init_report_clock;
+use crate::crypto::init_encryption;
+use crate::projects::init_projects;
+use crate::sys_monitor::init_session_gap;
+use crate::timeline::init_smoothing;
+use crate::utils::get_log_file_dir_str;
+
+/// What to do with raw day logs once they have been rolled up into summaries
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    Delete,
+    Archive,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportsConfig {
+    /// IANA name of the timezone whose days reports use, e.g. `Europe/Berlin`. `None` or `local` uses the
+    /// timezone the user was in when the events were recorded.
+    pub timezone: Option<String>,
+    /// Sessions shorter than this, e.g. switching past an app, count for the app before or after. 0 keeps all.
+    pub min_session_secs: u64,
+    /// Sessions of the same app at most this far apart are joined, the gap counting for the app. 0 joins none.
+    pub merge_gap_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Keep raw events for this many days. `None`, the default, keeps them forever.
+    pub raw_days: Option<u32>,
+    pub action: RetentionAction,
+    /// Keep the rolling `app.log` and `daemon.log` tracing files for this many days. `None` keeps them forever.
+    pub tracing_log_days: Option<u32>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            raw_days: None,
+            action: RetentionAction::Archive,
+            tracing_log_days: Some(14),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Encrypt new events and summaries before they are written
+    pub enabled: bool,
+    /// Read the key from this file instead of the OS keychain
+    pub key_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Stop counting time after this many seconds without keyboard or mouse input. `None`, the default, never does.
+    pub idle_threshold_secs: Option<u64>,
+    /// Record that the app in front is still in front this often, so sessions of a killed process can be ended
+    pub heartbeat_interval_secs: u64,
+    /// A check this much later than expected means the machine slept or the process was suspended.
+    /// Also how much later than expected a heartbeat may be before reports end the session at the one before.
+    pub gap_threshold_secs: u64,
+    /// Record the title of the window in front with each app, e.g. for the timeline. Off by default, as titles
+    /// can hold URLs, document names and message subjects.
+    pub record_titles: bool,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig {
+            idle_threshold_secs: None,
+            heartbeat_interval_secs: 60,
+            gap_threshold_secs: 10,
+            record_titles: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Serve the HTTP API from the daemon
+    pub enabled: bool,
+    /// Port on 127.0.0.1 to listen on
+    pub port: u16,
+    /// Token clients must send as `Authorization: Bearer <token>`. `None` uses a generated one.
+    pub token: Option<String>,
+    /// Also serve Prometheus metrics at `/metrics`
+    pub metrics: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            enabled: false,
+            port: 5617,
+            token: None,
+            metrics: false,
+        }
+    }
+}
+
+/// Kinds of events a webhook can be sent for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    AppSwitch,
+    IdleStart,
+    IdleEnd,
+    LimitExceeded,
+    DailySummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// URL to POST the JSON payloads to
+    pub url: String,
+    /// Only send these events. Empty sends all of them.
+    pub events: Vec<WebhookEventKind>,
+    /// Only send events about these apps, by name. Empty sends all of them.
+    pub apps: Vec<String>,
+    /// Retries after a failed delivery, waiting twice as long before each one
+    pub max_retries: u32,
+    /// Wait before the first retry, in milliseconds
+    pub retry_delay_ms: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            url: String::new(),
+            events: Vec::new(),
+            apps: Vec::new(),
+            max_retries: 5,
+            retry_delay_ms: 1000,
+        }
+    }
+}
+
+/// A daily time limit for an app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppLimit {
+    /// App name, as shown in the reports
+    pub app: String,
+    pub daily_minutes: u64,
+}
+
+/// A project and the rules for the time that belongs to it, e.g. to bill a client
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub name: String,
+    /// Window titles containing any of these, ignoring case, e.g. the name of a repository
+    pub titles: Vec<String>,
+    /// Apps in any of these folders, or windows with a path in them in the title, e.g. `~/work/clientA`
+    pub paths: Vec<String>,
+    /// Windows with any of these domains or their subdomains in the title, e.g. browsers showing the URL
+    pub domains: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// A folder shared between devices, e.g. in Dropbox or Syncthing, or a WebDAV URL. `None` doesn't sync.
+    pub remote: Option<String>,
+    /// WebDAV user name
+    pub username: Option<String>,
+    /// WebDAV password
+    pub password: Option<String>,
+    /// Name of this device in reports. `None` uses the host name.
+    pub device_name: Option<String>,
+    /// Sync this often while the daemon runs
+    pub interval_secs: u64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            remote: None,
+            username: None,
+            password: None,
+            device_name: None,
+            interval_secs: 300,
+        }
+    }
+}
+
+/// User configuration, stored as `config.json` in the data directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub monitor: MonitorConfig,
+    pub retention: RetentionConfig,
+    pub encryption: EncryptionConfig,
+    pub api: ApiConfig,
+    pub webhooks: Vec<WebhookConfig>,
+    pub limits: Vec<AppLimit>,
+    pub sync: SyncConfig,
+    pub reports: ReportsConfig,
+    /// The first project whose rules match an app session gets its time
+    pub projects: Vec<ProjectConfig>,
+}
+
+pub fn get_config_file_path() -> String {
+    format!("{}/config.json", get_log_file_dir_str())
+}
+
+impl Config {
+    /// Load the config file. A missing file falls back to the defaults.
+    ///
+    /// A file that can't be read or parsed is an error rather than the defaults, which would e.g. turn
+    /// encryption off.
+    pub fn load() -> io::Result<Config> {
+        let config_path = get_config_file_path();
+        match fs::read_to_string(&config_path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Failed to parse {}: {}", config_path, e))
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No config file found at {}, using defaults", config_path);
+                Ok(Config::default())
+            }
+            Err(e) => Err(io::Error::new(e.kind(), format!("Failed to read {}: {}", config_path, e))),
+        }
+    }
+}
+
+/// Set up encryption, the session gap, the report clock, smoothing and projects from a config,
+/// before any day log is read or written
+pub fn init_from_config(config: &Config) {
+    init_encryption(&config.encryption);
+    init_session_gap(&config.monitor);
+    init_report_clock(&config.reports);
+    init_smoothing(&config.reports);
+    init_projects(&config.projects);
+}
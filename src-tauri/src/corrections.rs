@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+use crate::crypto::seal_line;
+use crate::sys_monitor::{set_total_secs, AppUsage};
+use crate::utils::{get_log_file_dir_str, lock_dir_of, read_log_lines};
+
+/// What a correction does to the time between its start and end
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Change {
+    /// Count the time for `name` instead of what was tracked, e.g. a meeting away from the desk
+    Add { name: String, category: Option<String> },
+    /// Count the tracked time of `app`, or of all apps, for `name`, an app or any other label
+    Reassign { app: Option<String>, name: String },
+    /// Don't count the tracked time of `app`, or of all apps, e.g. after forgetting to pause
+    Remove { app: Option<String> },
+    /// Count the time of `app`, or of all apps, for a project, whatever the project rules say
+    Project { app: Option<String>, project: String },
+}
+
+/// A manual change to the tracked time, kept apart from the day logs, which are never rewritten
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Correction {
+    /// 0 for a correction that hasn't been saved yet
+    #[serde(default)]
+    pub id: u64,
+    pub start: u64,
+    pub end: u64,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl Correction {
+    fn validate(&self) -> io::Result<()> {
+        let name = match &self.change {
+            Change::Add { name, .. } | Change::Reassign { name, .. } => Some(name),
+            Change::Project { project, .. } => Some(project),
+            Change::Remove { .. } => None,
+        };
+        if self.start >= self.end {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "A correction must end after it starts"));
+        }
+        if name.is_some_and(|name| name.trim().is_empty()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "A correction needs a name"));
+        }
+        Ok(())
+    }
+
+    /// Whether the correction applies to the time of an app
+    pub fn affects(&self, app_name: &str) -> bool {
+        match &self.change {
+            Change::Add { .. } => true,
+            Change::Reassign { app, .. } | Change::Remove { app } | Change::Project { app, .. } => {
+                app.as_ref().is_none_or(|app| app.eq_ignore_ascii_case(app_name))
+            }
+        }
+    }
+}
+
+fn get_corrections_path(data_dir: &str) -> String {
+    format!("{}/corrections.json", data_dir)
+}
+
+/// Load the corrections of a data directory, in the order they were added
+pub fn load_corrections(data_dir: &str) -> io::Result<Vec<Correction>> {
+    let content = match read_log_lines(get_corrections_path(data_dir)) {
+        Ok(lines) => lines.concat(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn save_corrections(data_dir: &str, corrections: &[Correction]) -> io::Result<()> {
+    fs::create_dir_all(data_dir)?;
+    let file_path = get_corrections_path(data_dir);
+    let temp_path = format!("{}.tmp", file_path);
+    fs::write(&temp_path, seal_line(&serde_json::to_string(corrections)?)? + "\n")?;
+    fs::rename(&temp_path, &file_path)
+}
+
+/// Get the corrections that overlap the time between start_timestamp and end_timestamp
+pub fn get_corrections(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<Correction>> {
+    let mut corrections = load_corrections(&get_log_file_dir_str())?;
+    corrections.retain(|correction| correction.start <= end_timestamp && correction.end >= start_timestamp);
+    Ok(corrections)
+}
+
+/// Add a correction, or replace the one with the same id. Returns it with its id.
+pub fn save_correction(mut correction: Correction) -> io::Result<Correction> {
+    correction.validate()?;
+    let data_dir = get_log_file_dir_str();
+    // Another process may be editing the corrections too
+    let _lock = lock_dir_of(get_corrections_path(&data_dir))?;
+    let mut corrections = load_corrections(&data_dir)?;
+    if correction.id == 0 {
+        correction.id = corrections.iter().map(|correction| correction.id).max().unwrap_or(0) + 1;
+        corrections.push(correction.clone());
+    } else {
+        let saved = corrections
+            .iter_mut()
+            .find(|saved| saved.id == correction.id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No correction {}", correction.id)))?;
+        *saved = correction.clone();
+    }
+    save_corrections(&data_dir, &corrections)?;
+    Ok(correction)
+}
+
+/// Delete a correction, so the time counts as tracked again. Returns the deleted correction.
+pub fn delete_correction(id: u64) -> io::Result<Correction> {
+    let data_dir = get_log_file_dir_str();
+    let _lock = lock_dir_of(get_corrections_path(&data_dir))?;
+    let mut corrections = load_corrections(&data_dir)?;
+    let index = corrections
+        .iter()
+        .position(|correction| correction.id == id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No correction {}", id)))?;
+    let deleted = corrections.remove(index);
+    save_corrections(&data_dir, &corrections)?;
+    Ok(deleted)
+}
+
+/// Cut the parts between start and end out of durations, and return them
+fn cut_durations(durations: &mut Vec<(u64, u64)>, start: u64, end: u64) -> Vec<(u64, u64)> {
+    let mut outside = Vec::new();
+    let mut inside = Vec::new();
+    for &(duration_start, duration_end) in durations.iter() {
+        if duration_start >= end || duration_end <= start {
+            outside.push((duration_start, duration_end));
+            continue;
+        }
+        if duration_start < start {
+            outside.push((duration_start, start));
+        }
+        inside.push((duration_start.max(start), duration_end.min(end)));
+        if duration_end > end {
+            outside.push((end, duration_end));
+        }
+    }
+    *durations = outside;
+    inside
+}
+
+/// Give durations to an app, adding it if it wasn't tracked
+fn add_durations(app_usages: &mut Vec<AppUsage>, name: &str, durations: Vec<(u64, u64)>) {
+    if durations.is_empty() {
+        return;
+    }
+    match app_usages.iter_mut().find(|app| app.name == name) {
+        Some(app) => app.durations.extend(durations),
+        None => app_usages.push(AppUsage {
+            name: name.to_string(),
+            path: String::new(),
+            total_secs: 0,
+            durations,
+        }),
+    }
+}
+
+/// Apply corrections to app usages aggregated from the day logs, later corrections over earlier ones
+///
+/// Apps without any time left are dropped. Durations that now touch are joined, so a session
+/// reassigned to the app it interrupted becomes part of it.
+pub fn apply_corrections(mut app_usages: Vec<AppUsage>, corrections: &[Correction]) -> Vec<AppUsage> {
+    if corrections.is_empty() {
+        return app_usages;
+    }
+    // Projects don't change which app the time counts for
+    for correction in corrections.iter().filter(|correction| !matches!(correction.change, Change::Project { .. })) {
+        let mut corrected = Vec::new();
+        for app in app_usages.iter_mut().filter(|app| correction.affects(&app.name)) {
+            corrected.extend(cut_durations(&mut app.durations, correction.start, correction.end));
+        }
+        match &correction.change {
+            Change::Add { name, .. } => add_durations(&mut app_usages, name, vec![(correction.start, correction.end)]),
+            Change::Reassign { name, .. } => add_durations(&mut app_usages, name, corrected),
+            Change::Remove { .. } | Change::Project { .. } => {}
+        }
+    }
+
+    app_usages.retain(|app| !app.durations.is_empty());
+    for app in app_usages.iter_mut() {
+        app.durations.sort();
+        let mut joined: Vec<(u64, u64)> = Vec::with_capacity(app.durations.len());
+        for &(start, end) in app.durations.iter() {
+            match joined.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => joined.push((start, end)),
+            }
+        }
+        app.durations = joined;
+        set_total_secs(app);
+    }
+    app_usages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_usage(name: &str, durations: Vec<(u64, u64)>) -> AppUsage {
+        let mut app = AppUsage {
+            name: name.to_string(),
+            path: format!("/usr/bin/{}", name),
+            total_secs: 0,
+            durations,
+        };
+        set_total_secs(&mut app);
+        app
+    }
+
+    fn correction(start_secs: u64, end_secs: u64, change: Change) -> Correction {
+        Correction {
+            id: 0,
+            start: start_secs * 1000,
+            end: end_secs * 1000,
+            change,
+        }
+    }
+
+    fn durations(app_usages: &[AppUsage], name: &str) -> Vec<(u64, u64)> {
+        app_usages
+            .iter()
+            .find(|app| app.name == name)
+            .map(|app| app.durations.iter().map(|(start, end)| (start / 1000, end / 1000)).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_apply_corrections() {
+        let app_usages = vec![
+            app_usage("firefox", vec![(0, 3_600_000)]),
+            app_usage("code", vec![(3_600_000, 3_900_000), (4_000_000, 7_200_000)]),
+            app_usage("slack", vec![(3_900_000, 4_000_000)]),
+        ];
+        let corrections = vec![
+            // The browser was left open during a meeting
+            correction(1800, 5400, Change::Add { name: "Meeting".to_string(), category: Some("Communication".to_string()) }),
+            // Looked something up in the chat for the code
+            correction(3900, 4000, Change::Reassign { app: Some("Slack".to_string()), name: "code".to_string() }),
+            // Forgot to pause
+            correction(6600, 7200, Change::Remove { app: None }),
+        ];
+        let corrected = apply_corrections(app_usages, &corrections);
+
+        assert_eq!(durations(&corrected, "firefox"), vec![(0, 1800)]);
+        assert_eq!(durations(&corrected, "Meeting"), vec![(1800, 5400)]);
+        assert_eq!(durations(&corrected, "code"), vec![(5400, 6600)]);
+        assert!(corrected.iter().all(|app| app.name != "slack"));
+        assert_eq!(corrected.iter().map(|app| app.total_secs).sum::<u64>(), 6600);
+    }
+
+    #[test]
+    fn test_reassign_joins_sessions() {
+        let app_usages = vec![
+            app_usage("code", vec![(0, 100_000), (101_000, 200_000)]),
+            app_usage("terminal", vec![(100_000, 101_000)]),
+        ];
+        let corrections = vec![correction(0, 200, Change::Reassign { app: Some("terminal".to_string()), name: "code".to_string() })];
+        let corrected = apply_corrections(app_usages, &corrections);
+        assert_eq!(corrected.len(), 1);
+        assert_eq!(durations(&corrected, "code"), vec![(0, 200)]);
+        assert_eq!(corrected[0].total_secs, 200);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(correction(10, 10, Change::Remove { app: None }).validate().is_err());
+        assert!(correction(0, 10, Change::Add { name: " ".to_string(), category: None }).validate().is_err());
+        assert!(correction(0, 10, Change::Remove { app: None }).validate().is_ok());
+    }
+}
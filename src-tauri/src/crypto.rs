@@ -0,0 +1,225 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::info;
+
+use crate::config::EncryptionConfig;
+use crate::retention::get_summary_dir_str;
+use crate::utils::{get_log_file_dir_str, rewrite_log_file};
+
+const KEYCHAIN_SERVICE: &str = "com.entropy.flows";
+const KEYCHAIN_USER: &str = "event-store-key";
+const ENCRYPTED_PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+static ENCRYPTION_CONFIG: OnceLock<EncryptionConfig> = OnceLock::new();
+/// Only set once a key was loaded, e.g. the keychain may still be locked when the daemon starts at login
+static CIPHER: OnceLock<ChaCha20Poly1305> = OnceLock::new();
+
+/// Set the encryption settings used by `seal_line` and `open_line`
+///
+/// Must be called before the first event is written. Later calls are ignored.
+pub fn init_encryption(config: &EncryptionConfig) {
+    let _ = ENCRYPTION_CONFIG.set(config.clone());
+}
+
+fn is_encryption_enabled() -> bool {
+    ENCRYPTION_CONFIG.get().is_some_and(|config| config.enabled)
+}
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn decode_key(encoded: &str) -> io::Result<[u8; 32]> {
+    let bytes = STANDARD.decode(encoded.trim()).map_err(invalid_data)?;
+    bytes.try_into().map_err(|_| invalid_data("Encryption key must be 32 bytes"))
+}
+
+fn generate_key() -> String {
+    STANDARD.encode(ChaCha20Poly1305::generate_key(&mut OsRng))
+}
+
+/// Load the key from the key file or the OS keychain
+///
+/// A new key is only created if `create` is set, so reading never creates one.
+fn load_key(config: &EncryptionConfig, create: bool) -> io::Result<Option<[u8; 32]>> {
+    let key_file = config.key_file.clone().or_else(|| std::env::var("FLOWS_KEY_FILE").ok());
+    if let Some(key_file) = key_file {
+        return match fs::read_to_string(&key_file) {
+            Ok(encoded) => decode_key(&encoded).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && create => {
+                let encoded = generate_key();
+                write_key_file(&key_file, &encoded)?;
+                info!("Created encryption key file {}", key_file);
+                decode_key(&encoded).map(Some)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        };
+    }
+
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_USER).map_err(invalid_data)?;
+    match entry.get_password() {
+        Ok(encoded) => decode_key(&encoded).map(Some),
+        Err(keyring::Error::NoEntry) if create => {
+            let encoded = generate_key();
+            entry.set_password(&encoded).map_err(invalid_data)?;
+            info!("Stored a new encryption key in the keychain");
+            decode_key(&encoded).map(Some)
+        }
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(invalid_data(e)),
+    }
+}
+
+fn write_key_file(key_file: &str, encoded: &str) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(key_file)?;
+        file.write_all(encoded.as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    {
+        fs::write(key_file, encoded)
+    }
+}
+
+/// Get the cipher, loading the key again on every call until that works
+fn get_cipher() -> io::Result<&'static ChaCha20Poly1305> {
+    if let Some(cipher) = CIPHER.get() {
+        return Ok(cipher);
+    }
+    let config = ENCRYPTION_CONFIG.get().cloned().unwrap_or_default();
+    match load_key(&config, config.enabled) {
+        Ok(Some(key)) => Ok(CIPHER.get_or_init(|| ChaCha20Poly1305::new(Key::from_slice(&key)))),
+        Ok(None) => Err(invalid_data("Encryption key is not available")),
+        Err(e) => Err(invalid_data(format!("Failed to load encryption key: {}", e))),
+    }
+}
+
+/// Encrypt a line of the event store if encryption is enabled
+///
+/// Encrypted lines are stored as `enc:` followed by the base64 encoded nonce and ciphertext,
+/// so they can still be appended one at a time.
+pub fn seal_line(line: &str) -> io::Result<String> {
+    if !is_encryption_enabled() {
+        return Ok(line.to_string());
+    }
+    let cipher = get_cipher()?;
+    encrypt_line(cipher, line)
+}
+
+fn encrypt_line(cipher: &ChaCha20Poly1305, line: &str) -> io::Result<String> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(cipher.encrypt(&nonce, line.as_bytes()).map_err(invalid_data)?);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(sealed)))
+}
+
+/// Decrypt a line of the event store. Plain text lines are returned as they are.
+pub fn open_line(line: &str) -> io::Result<String> {
+    let Some(encoded) = line.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(line.to_string());
+    };
+    let cipher = get_cipher()?;
+    let sealed = STANDARD.decode(encoded).map_err(invalid_data)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(invalid_data("Encrypted line is too short"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| invalid_data("Failed to decrypt line"))?;
+    String::from_utf8(plaintext).map_err(invalid_data)
+}
+
+/// Encrypt the plain text lines of all existing day logs, summaries, corrections and the zone history in place
+///
+/// Returns the number of rewritten files. Encryption must be enabled in the config.
+pub fn encrypt_existing_logs() -> io::Result<usize> {
+    if !is_encryption_enabled() {
+        return Err(io::Error::other(
+            "Enable encryption in the config before encrypting existing logs",
+        ));
+    }
+
+    let mut file_paths: Vec<PathBuf> = ["corrections.json", "zones"]
+        .iter()
+        .map(|file_name| Path::new(&get_log_file_dir_str()).join(file_name))
+        .filter(|path| path.exists())
+        .collect();
+    for (dir, extension) in [(get_log_file_dir_str(), "log"), (get_summary_dir_str(), "json")] {
+        if !Path::new(&dir).exists() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let is_store_file = path.extension().is_some_and(|ext| ext == extension);
+            if is_store_file {
+                file_paths.push(path);
+            }
+        }
+    }
+
+    let mut count = 0;
+    for file_path in file_paths {
+        let changed = rewrite_log_file(&file_path, |lines| {
+            let mut changed = false;
+            for line in lines.iter_mut() {
+                if !line.is_empty() && !line.starts_with(ENCRYPTED_PREFIX) {
+                    *line = seal_line(line)?;
+                    changed = true;
+                }
+            }
+            Ok(changed)
+        })?;
+        if changed {
+            info!("Encrypted {:?}", file_path);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_line() {
+        let key_file = std::env::temp_dir().join(format!("flows-test-key-{}", std::process::id()));
+        let _ = fs::remove_file(&key_file);
+        init_encryption(&EncryptionConfig {
+            enabled: true,
+            key_file: Some(key_file.to_string_lossy().to_string()),
+        });
+
+        let line = "0,1720000000000,/Applications/Safari.app/Contents/MacOS/Safari";
+        let sealed = seal_line(line).unwrap();
+        assert!(sealed.starts_with(ENCRYPTED_PREFIX));
+        assert!(!sealed.contains("Safari"));
+        assert_eq!(open_line(&sealed).unwrap(), line);
+        assert_eq!(open_line("1,1720000000000").unwrap(), "1,1720000000000");
+
+        let mut tampered = sealed.clone();
+        tampered.pop();
+        tampered.push(if sealed.ends_with('A') { 'B' } else { 'A' });
+        assert!(open_line(&tampered).is_err());
+
+        fs::remove_file(&key_file).unwrap();
+    }
+}
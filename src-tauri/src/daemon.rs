@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::api::{get_api_token, ApiServer};
+use crate::config::{init_from_config, Config};
+use crate::corrections::{delete_correction, save_correction};
+use crate::crypto::encrypt_existing_logs;
+use crate::import::import_from_source;
+use crate::instance::InstanceLock;
+use crate::ipc::{serve, Request};
+use crate::log_writer::recover_logs;
+use crate::monitor::{CurrentApp, Monitor};
+use crate::retention::run_retention;
+use crate::sync::{run_sync, start_sync};
+use crate::sys_monitor::{get_app_usages_from_log, EventType};
+use crate::timeline::get_timelines;
+use crate::utils::{get_current_timestamp, get_log_file_dir_str, init_tracing, write_to_file};
+use crate::webhooks::start_webhooks;
+
+/// The answer to a `status` request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub paused: bool,
+    pub current: CurrentApp,
+}
+
+fn handle_request(monitor: &Arc<Monitor>, request: Request) -> io::Result<Value> {
+    match request {
+        Request::Ping => Ok(json!("pong")),
+        Request::Status => Ok(serde_json::to_value(DaemonStatus {
+            pid: std::process::id(),
+            paused: monitor.is_paused(),
+            current: monitor.current_app(),
+        })?),
+        Request::Pause => Ok(json!(monitor.pause())),
+        Request::Resume => Ok(json!(monitor.resume())),
+        Request::Current => Ok(serde_json::to_value(monitor.current_app())?),
+        Request::Tag { label } => {
+            let label = label.trim();
+            // A line break would split the event into two lines
+            if label.is_empty() || label.contains(['\n', '\r']) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid tag label"));
+            }
+            let timestamp = get_current_timestamp();
+            write_to_file(EventType::Tag, timestamp, label);
+            Ok(json!(timestamp))
+        }
+        // Streamed by the IPC layer itself
+        Request::Subscribe => Err(io::Error::new(io::ErrorKind::InvalidInput, "Unexpected subscribe")),
+        Request::Usages {
+            start_timestamp,
+            end_timestamp,
+        } => Ok(serde_json::to_value(get_app_usages_from_log(start_timestamp, end_timestamp)?)?),
+        Request::Timeline {
+            start_timestamp,
+            end_timestamp,
+        } => Ok(serde_json::to_value(get_timelines(start_timestamp, end_timestamp)?)?),
+        Request::EncryptLogs => Ok(json!(encrypt_existing_logs()?)),
+        Request::SaveCorrection { correction } => Ok(serde_json::to_value(save_correction(correction)?)?),
+        Request::DeleteCorrection { id } => Ok(serde_json::to_value(delete_correction(id)?)?),
+        Request::Import { source, file_path } => Ok(serde_json::to_value(import_from_source(&source, &file_path)?)?),
+        // Reloaded, so a remote added since the daemon started is used
+        Request::Sync => Ok(serde_json::to_value(run_sync(&Config::load()?.sync)?)?),
+        Request::ShowDashboard => Err(io::Error::new(io::ErrorKind::InvalidInput, "Not served by the daemon")),
+        Request::Stop => {
+            info!("Stop requested");
+            monitor.stop();
+            // Exit after the response has been sent
+            thread::spawn(|| {
+                thread::sleep(Duration::from_millis(100));
+                std::process::exit(0);
+            });
+            Ok(Value::Null)
+        }
+    }
+}
+
+/// Run the tracking daemon: the only process that writes to the event store
+///
+/// Returns the exit code.
+pub fn run_daemon() -> i32 {
+    // Held until the process exits, which also makes replacing a stale socket file safe
+    let _instance_lock = match InstanceLock::acquire("flows-daemon") {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            eprintln!("flows-daemon is already running");
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("Failed to lock the data directory: {}", e);
+            return 1;
+        }
+    };
+
+    let _guard = init_tracing(&format!("{}/logs", get_log_file_dir_str()), "daemon.log");
+
+    // Never track with a config that was meant to differ, e.g. without the encryption it turns on
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Not tracking: {}", e);
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    init_from_config(&config);
+
+    // Compact old day logs and prune tracing logs once a day
+    let retention_config = config.retention.clone();
+    thread::spawn(move || loop {
+        run_retention(&retention_config);
+        thread::sleep(Duration::from_secs(86400));
+    });
+
+    if let Err(e) = recover_logs() {
+        error!("Failed to recover the day logs: {}", e);
+    }
+
+    let monitor = Arc::new(Monitor::with_config(&config.monitor));
+
+    // Close the current session when the service manager stops the daemon
+    let stopping_monitor = Arc::clone(&monitor);
+    if let Err(e) = ctrlc::set_handler(move || {
+        info!("Stopping");
+        stopping_monitor.stop();
+        std::process::exit(0);
+    }) {
+        error!("Failed to set signal handler: {}", e);
+    }
+
+    start_webhooks(&config.webhooks, &config.limits);
+    start_sync(&config.sync);
+
+    // Desktop integrations on Linux talk to the daemon over the session bus, if there is one
+    #[cfg(target_os = "linux")]
+    let _dbus_connection = match crate::dbus::start_dbus_service(Arc::clone(&monitor), None) {
+        Ok(connection) => Some(connection),
+        Err(e) => {
+            info!("Not serving D-Bus: {}", e);
+            None
+        }
+    };
+
+    let running_monitor = Arc::clone(&monitor);
+    thread::spawn(move || running_monitor.run());
+
+    if config.api.enabled {
+        let server = get_api_token(&config.api).and_then(|token| ApiServer::bind(config.api.port, token));
+        match server {
+            Ok(server) => {
+                let server = server.with_metrics(config.api.metrics);
+                let api_monitor = Arc::clone(&monitor);
+                thread::spawn(move || server.run(&api_monitor));
+            }
+            Err(e) => error!("Failed to start the HTTP API on port {}: {}", config.api.port, e),
+        }
+    }
+
+    match serve(move |request| handle_request(&monitor, request)) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Failed to serve IPC socket: {}", e);
+            1
+        }
+    }
+}
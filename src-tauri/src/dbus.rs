@@ -0,0 +1,110 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::thread;
+use tracing::{error, info};
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::zvariant::DynamicType;
+use zbus::{dbus_interface, fdo, SignalContext};
+
+use crate::clock::get_report_clock;
+use crate::events::subscribe_events;
+use crate::monitor::Monitor;
+use crate::sys_monitor::{get_app_usages_from_log, EventType};
+use crate::webhooks::{EventTranslator, WebhookEvent};
+
+pub const BUS_NAME: &str = "com.entropy.Flows";
+pub const OBJECT_PATH: &str = "/com/entropy/Flows";
+pub const INTERFACE_NAME: &str = "com.entropy.Flows1";
+
+/// The `com.entropy.Flows1` interface, backed by the monitor of the daemon
+pub struct FlowsInterface {
+    monitor: Arc<Monitor>,
+}
+
+#[dbus_interface(name = "com.entropy.Flows1")]
+impl FlowsInterface {
+    /// Name and path of the app in front. Both are empty when nothing is tracked.
+    fn get_current_app(&self) -> (String, String) {
+        let current = self.monitor.current_app();
+        (current.name.unwrap_or_default(), current.path.unwrap_or_default())
+    }
+
+    /// Seconds per app today, the day as reports count it, longest first
+    fn get_today_totals(&self) -> fdo::Result<Vec<(String, u64)>> {
+        let (start_timestamp, end_timestamp) = get_report_clock().get_today_so_far();
+        let app_usages =
+            get_app_usages_from_log(start_timestamp, end_timestamp).map_err(|e| fdo::Error::IOError(e.to_string()))?;
+        Ok(app_usages.into_iter().map(|app| (app.name, app.total_secs)).collect())
+    }
+
+    /// Stop recording. Returns false if already paused.
+    fn pause(&self) -> bool {
+        self.monitor.pause()
+    }
+
+    /// Start recording again. Returns false if not paused.
+    fn resume(&self) -> bool {
+        self.monitor.resume()
+    }
+
+    /// Another app came to front, or nothing is tracked anymore if both are empty
+    #[dbus_interface(signal)]
+    async fn focus_changed(ctxt: &SignalContext<'_>, name: &str, path: &str) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn idle_changed(ctxt: &SignalContext<'_>, idle: bool) -> zbus::Result<()>;
+}
+
+fn emit<B>(connection: &Connection, signal_name: &str, body: &B)
+where
+    B: Serialize + DynamicType,
+{
+    if let Err(e) = connection.emit_signal(None::<&str>, OBJECT_PATH, INTERFACE_NAME, signal_name, body) {
+        error!("Failed to emit {}: {}", signal_name, e);
+    }
+}
+
+/// Emit the signals for the events the monitor writes, forever
+///
+/// They follow the webhook events, so both agree on when the user is idle.
+fn emit_signals(connection: &Connection) {
+    let mut translator = EventTranslator::default();
+    for event in subscribe_events() {
+        for webhook_event in translator.translate(&event) {
+            match webhook_event {
+                WebhookEvent::AppSwitch { name, path, .. } => {
+                    emit(connection, "FocusChanged", &(name.as_str(), path.as_str()))
+                }
+                WebhookEvent::IdleStart { .. } => {
+                    emit(connection, "IdleChanged", &(true,));
+                    emit(connection, "FocusChanged", &("", ""));
+                }
+                WebhookEvent::IdleEnd { .. } => emit(connection, "IdleChanged", &(false,)),
+                WebhookEvent::LimitExceeded { .. } | WebhookEvent::DailySummary { .. } => {}
+            }
+        }
+        // Nothing is in front anymore, which webhooks don't report
+        if matches!(event.event_type, EventType::ShutDown | EventType::StopMonitoring) {
+            emit(connection, "FocusChanged", &("", ""));
+        }
+    }
+}
+
+/// Own `com.entropy.Flows` on the session bus, or on the bus at `address`, and emit its signals
+///
+/// The connection serves requests from its own thread, so keep it alive for as long as the service should run.
+pub fn start_dbus_service(monitor: Arc<Monitor>, address: Option<&str>) -> zbus::Result<Connection> {
+    let builder = match address {
+        Some(address) => ConnectionBuilder::address(address)?,
+        None => ConnectionBuilder::session()?,
+    };
+    let connection = builder
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, FlowsInterface { monitor })?
+        .build()?;
+    info!("Owning {} on D-Bus", BUS_NAME);
+
+    let signal_connection = connection.clone();
+    thread::spawn(move || emit_signals(&signal_connection));
+    Ok(connection)
+}
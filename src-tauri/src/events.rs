@@ -0,0 +1,46 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use crate::sys_monitor::Event;
+
+/// Everyone waiting for new events, e.g. `subscribe` connections of the socket API
+static SUBSCRIBERS: Mutex<Vec<Sender<Event>>> = Mutex::new(Vec::new());
+
+/// Receive every event written to the day logs from now on
+///
+/// Dropping the receiver unsubscribes on the next event.
+pub fn subscribe_events() -> Receiver<Event> {
+    let (sender, receiver) = channel();
+    SUBSCRIBERS.lock().unwrap().push(sender);
+    receiver
+}
+
+/// Send an event to all subscribers, forgetting the ones that are gone
+pub(crate) fn publish_event(event: &Event) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|sender| sender.send(event.clone()).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys_monitor::EventType;
+
+    #[test]
+    fn test_publish_event() {
+        let receiver = subscribe_events();
+        let dropped = subscribe_events();
+        drop(dropped);
+
+        let event = Event {
+            event_type: EventType::Tag,
+            timestamp: 1,
+            path: "focus".to_string(),
+        };
+        publish_event(&event);
+        assert_eq!(receiver.try_recv().unwrap().path, "focus");
+        assert!(receiver.try_recv().is_err());
+    }
+}
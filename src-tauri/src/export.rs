@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+use crate::projects::get_project_usages;
+use crate::sys_monitor::{get_app_usages_from_log, get_events_from_log, Event};
+use crate::timeline::{get_timeline, TimelineEntry};
+use crate::utils::{get_date, get_day_start_timestamp};
+
+/// What to export
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportKind {
+    /// Raw events as they are stored in the day logs
+    Events,
+    /// One record per app session, ordered by start time
+    Sessions,
+    /// Total time per app and day
+    Daily,
+    /// Total time per project and day, e.g. for timesheets
+    Projects,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Jsonl,
+}
+
+impl FromStr for ExportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "events" => Ok(ExportKind::Events),
+            "sessions" => Ok(ExportKind::Sessions),
+            "daily" => Ok(ExportKind::Daily),
+            "projects" => Ok(ExportKind::Projects),
+            _ => Err(format!("Unknown export kind: {}", s)),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            _ => Err(format!("Unknown export format: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionRecord {
+    pub name: String,
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    pub secs: u64,
+}
+
+#[derive(Serialize)]
+pub struct DailyRecord {
+    pub date: String,
+    pub name: String,
+    pub path: String,
+    pub total_secs: u64,
+}
+
+#[derive(Serialize)]
+pub struct ProjectDailyRecord {
+    pub date: String,
+    pub project: String,
+    pub total_secs: u64,
+}
+
+/// A record that can be written as a CSV row
+trait CsvRecord {
+    const HEADER: &'static str;
+
+    fn fields(&self) -> Vec<String>;
+}
+
+impl CsvRecord for Event {
+    const HEADER: &'static str = "event_type,timestamp,path";
+
+    fn fields(&self) -> Vec<String> {
+        let event_type = serde_json::to_value(self.event_type)
+            .ok()
+            .and_then(|value| value.as_str().map(String::from))
+            .unwrap_or_default();
+        vec![event_type, self.timestamp.to_string(), self.path.clone()]
+    }
+}
+
+impl CsvRecord for SessionRecord {
+    const HEADER: &'static str = "name,path,start,end,secs";
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.path.clone(),
+            self.start.to_string(),
+            self.end.to_string(),
+            self.secs.to_string(),
+        ]
+    }
+}
+
+impl CsvRecord for DailyRecord {
+    const HEADER: &'static str = "date,name,path,total_secs";
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.date.clone(),
+            self.name.clone(),
+            self.path.clone(),
+            self.total_secs.to_string(),
+        ]
+    }
+}
+
+impl CsvRecord for ProjectDailyRecord {
+    const HEADER: &'static str = "date,project,total_secs";
+
+    fn fields(&self) -> Vec<String> {
+        vec![self.date.clone(), self.project.clone(), self.total_secs.to_string()]
+    }
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_records<W: Write, T: Serialize + CsvRecord>(
+    writer: &mut W,
+    records: &[T],
+    format: ExportFormat,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "{}", T::HEADER)?;
+            for record in records {
+                let fields: Vec<String> = record.fields().iter().map(|f| escape_csv_field(f)).collect();
+                writeln!(writer, "{}", fields.join(","))?;
+            }
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, records)?;
+            writeln!(writer)?;
+        }
+        ExportFormat::Jsonl => {
+            for record in records {
+                serde_json::to_writer(&mut *writer, record)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get all app sessions between start_timestamp and end_timestamp, ordered by start time
+///
+/// Sessions are cut to the range, and split at midnight so each record belongs to one day.
+pub fn get_sessions(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<SessionRecord>> {
+    let days = get_days(start_timestamp, end_timestamp);
+    let mut sessions = Vec::new();
+    for app in get_app_usages_from_log(start_timestamp, end_timestamp)? {
+        for &(session_start, session_end) in app.durations.iter() {
+            for &(_, range_start, range_end) in days.iter() {
+                // The ranges end at the last millisecond of the day
+                let start = session_start.max(range_start);
+                let end = session_end.min(range_end.saturating_add(1));
+                if start >= end {
+                    continue;
+                }
+                sessions.push(SessionRecord {
+                    name: app.name.clone(),
+                    path: app.path.clone(),
+                    start,
+                    end,
+                    secs: (end - start) / 1000,
+                });
+            }
+        }
+    }
+    sessions.sort_by_key(|session| session.start);
+    Ok(sessions)
+}
+
+/// Get the date, and the part of the range on it, of each day between start_timestamp and end_timestamp
+fn get_days(start_timestamp: u64, end_timestamp: u64) -> Vec<(String, u64, u64)> {
+    let mut days = Vec::new();
+    let mut day_start = get_day_start_timestamp(start_timestamp).unwrap_or(start_timestamp);
+    while day_start <= end_timestamp {
+        // Step by 26 hours and snap back, so days with DST changes are not skipped or repeated
+        let next_day_start = get_day_start_timestamp(day_start + 93600000).unwrap_or(day_start + 86400000);
+        let date = match get_date(day_start) {
+            Some(date) => date.format("%Y-%m-%d").to_string(),
+            None => break,
+        };
+        days.push((date, day_start.max(start_timestamp), (next_day_start - 1).min(end_timestamp)));
+        day_start = next_day_start;
+    }
+    days
+}
+
+/// Get the total time per app for each day between start_timestamp and end_timestamp
+pub fn get_daily_totals(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<DailyRecord>> {
+    let mut records = Vec::new();
+    for (date, range_start, range_end) in get_days(start_timestamp, end_timestamp) {
+        for app in get_app_usages_from_log(range_start, range_end)? {
+            records.push(DailyRecord {
+                date: date.clone(),
+                name: app.name,
+                path: app.path,
+                total_secs: app.total_secs,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Get the total time per project for each day between start_timestamp and end_timestamp
+///
+/// Compacted days have no sessions to assign to projects, so they have no project time.
+pub fn get_daily_project_totals(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<ProjectDailyRecord>> {
+    // One timeline for the whole range, so smoothing and sessions crossing midnight are the same as in reports
+    let timeline = get_timeline(start_timestamp, end_timestamp)?;
+    let mut records = Vec::new();
+    for (date, range_start, range_end) in get_days(start_timestamp, end_timestamp) {
+        // The ranges end at the last millisecond of the day
+        let day_end = range_end.saturating_add(1);
+        let day_timeline: Vec<TimelineEntry> = timeline
+            .iter()
+            .filter(|entry| entry.start < day_end && entry.end > range_start)
+            .map(|entry| TimelineEntry {
+                start: entry.start.max(range_start),
+                end: entry.end.min(day_end),
+                ..entry.clone()
+            })
+            .collect();
+        for usage in get_project_usages(&day_timeline) {
+            records.push(ProjectDailyRecord {
+                date: date.clone(),
+                project: usage.project,
+                total_secs: usage.total_secs,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Write the data of the given kind between start_timestamp and end_timestamp
+pub fn export<W: Write>(
+    writer: &mut W,
+    kind: ExportKind,
+    format: ExportFormat,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> io::Result<()> {
+    match kind {
+        ExportKind::Events => {
+            let events = get_events_from_log(start_timestamp, end_timestamp)?;
+            write_records(writer, &events, format)
+        }
+        ExportKind::Sessions => {
+            let sessions = get_sessions(start_timestamp, end_timestamp)?;
+            write_records(writer, &sessions, format)
+        }
+        ExportKind::Daily => {
+            let records = get_daily_totals(start_timestamp, end_timestamp)?;
+            write_records(writer, &records, format)
+        }
+        ExportKind::Projects => {
+            let records = get_daily_project_totals(start_timestamp, end_timestamp)?;
+            write_records(writer, &records, format)
+        }
+    }
+}
+
+pub fn export_to_file(
+    file_path: &str,
+    kind: ExportKind,
+    format: ExportFormat,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(file_path)?);
+    export(&mut writer, kind, format, start_timestamp, end_timestamp)?;
+    writer.flush()
+}
@@ -0,0 +1,268 @@
+use chrono::{Local, TimeZone};
+use chrono::LocalResult::Single;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::info;
+
+use crate::activitywatch::import_activitywatch;
+use crate::crypto::{open_line, seal_line};
+use crate::knowledgec::import_knowledgec;
+use crate::retention::get_summary_dir_str;
+use crate::sys_monitor::{get_app_usages_from_log, get_raw_app_usages, Event, EventType};
+use crate::utils::{get_current_timestamp, get_log_file_dir_str, rewrite_log_file};
+
+/// A session recorded by another tracker, to be backfilled as Flows events
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedSession {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub sessions: usize,
+    pub secs: u64,
+}
+
+/// Import a file from another tracker. `source` is `activitywatch` or `knowledgec`.
+pub fn import_from_source(source: &str, file_path: &str) -> io::Result<ImportSummary> {
+    match source {
+        "activitywatch" => import_activitywatch(file_path),
+        "knowledgec" => import_knowledgec(file_path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unknown import source: {}", source),
+        )),
+    }
+}
+
+/// Get the paths Flows has recorded in the last 30 days, by lowercase app name
+pub fn get_known_app_paths() -> io::Result<HashMap<String, String>> {
+    let now = get_current_timestamp();
+    let app_usages = get_app_usages_from_log(now.saturating_sub(30 * 86400000), now)?;
+    Ok(app_usages
+        .into_iter()
+        .map(|app| (app.name.to_lowercase(), app.path))
+        .collect())
+}
+
+/// Map an app name from another tracker to the path Flows would have recorded for it
+pub fn resolve_app_path(app_name: &str, known_app_paths: &HashMap<String, String>) -> String {
+    let app_name = app_name.trim_end_matches(".app").trim_end_matches(".exe");
+    if let Some(path) = known_app_paths.get(&app_name.to_lowercase()) {
+        return path.clone();
+    }
+    if cfg!(target_os = "macos") {
+        format!("/Applications/{}.app", app_name)
+    } else {
+        app_name.to_string()
+    }
+}
+
+/// Sort sessions and cut their overlaps, so every moment belongs to one session at most
+pub fn normalize_sessions(mut sessions: Vec<ImportedSession>) -> Vec<ImportedSession> {
+    sessions.sort_by_key(|session| session.start);
+    let mut normalized: Vec<ImportedSession> = Vec::new();
+    for mut session in sessions {
+        if let Some(last) = normalized.last() {
+            session.start = session.start.max(last.end);
+        }
+        if session.end > session.start {
+            normalized.push(session);
+        }
+    }
+    normalized
+}
+
+/// Remove the parts of the sessions that are already covered by other intervals
+///
+/// `covered` must be sorted by start time.
+pub fn subtract_covered(sessions: Vec<ImportedSession>, covered: &[(u64, u64)]) -> Vec<ImportedSession> {
+    let mut result = Vec::new();
+    for session in sessions {
+        let mut start = session.start;
+        for &(covered_start, covered_end) in covered {
+            if covered_end <= start || covered_start >= session.end {
+                continue;
+            }
+            if covered_start > start {
+                result.push(ImportedSession {
+                    path: session.path.clone(),
+                    start,
+                    end: covered_start,
+                });
+            }
+            start = start.max(covered_end);
+            if start >= session.end {
+                break;
+            }
+        }
+        if start < session.end {
+            result.push(ImportedSession {
+                path: session.path,
+                start,
+                end: session.end,
+            });
+        }
+    }
+    result
+}
+
+/// Write sessions from another tracker into the day logs
+///
+/// Time Flows has already recorded is left alone, so importing the same data twice is harmless.
+/// Days that have been compacted by the retention policy are skipped.
+pub fn import_sessions(sessions: Vec<ImportedSession>) -> io::Result<ImportSummary> {
+    let sessions = normalize_sessions(sessions);
+    let (Some(first), Some(last)) = (sessions.first(), sessions.last()) else {
+        return Ok(ImportSummary::default());
+    };
+
+    // Everything Flows has recorded around the imported period
+    let mut covered: Vec<(u64, u64)> = get_raw_app_usages(first.start.saturating_sub(86400000), last.end + 86400000)?
+        .into_iter()
+        .flat_map(|app| app.durations)
+        .filter(|(start, end)| end > start)
+        .collect();
+    covered.sort();
+    let sessions = subtract_covered(sessions, &covered);
+
+    // Group the new events by the day log they belong to
+    let mut days: BTreeMap<String, Vec<(u64, String)>> = BTreeMap::new();
+    let mut summary = ImportSummary::default();
+    for session in sessions {
+        let (Some(start_date), Some(end_date)) = (get_log_date(session.start), get_log_date(session.end)) else {
+            continue;
+        };
+        let is_compacted = [&start_date, &end_date]
+            .iter()
+            .any(|date| Path::new(&format!("{}/{}.json", get_summary_dir_str(), date)).exists());
+        if is_compacted {
+            continue;
+        }
+
+        // Like the live monitor, each event goes to the day log of its own timestamp
+        days.entry(start_date).or_default().push((
+            session.start,
+            format!("{},{},{}", EventType::CameToFront.to_int(), session.start, session.path),
+        ));
+        days.entry(end_date)
+            .or_default()
+            .push((session.end, format!("{},{}", EventType::ShutDown.to_int(), session.end)));
+        summary.sessions += 1;
+        summary.secs += (session.end - session.start) / 1000;
+    }
+
+    fs::create_dir_all(get_log_file_dir_str())?;
+    for (date, events) in days {
+        let log_file_path = format!("{}/{}.log", get_log_file_dir_str(), date);
+        rewrite_log_file(&log_file_path, |lines| {
+            merge_lines(lines, events)?;
+            Ok(true)
+        })?;
+    }
+
+    info!("Imported {} sessions, {} seconds", summary.sessions, summary.secs);
+    Ok(summary)
+}
+
+fn get_log_date(timestamp: u64) -> Option<String> {
+    match Local.timestamp_millis_opt(timestamp as i64) {
+        Single(date) => Some(date.format("%Y%m%d").to_string()),
+        _ => None,
+    }
+}
+
+/// Merge new plain text events into the stored lines of a day log, keeping it ordered by time
+///
+/// Events with the same timestamp are ordered like synced day logs are, so imported sessions end
+/// before recorded ones start and start after they end. Otherwise, existing lines come first.
+fn merge_lines(lines: &mut Vec<String>, events: Vec<(u64, String)>) -> io::Result<()> {
+    let mut order = (0, 0);
+    let mut merged: Vec<((u64, u8, bool), String)> = Vec::new();
+    for line in lines.drain(..) {
+        // Lines that can't be parsed stay right after the line before them
+        if let Some(event) = open_line(&line).ok().and_then(|line| Event::parse(&line)) {
+            order = (event.timestamp, event.event_type.get_merge_rank());
+        }
+        merged.push(((order.0, order.1, false), line));
+    }
+    for (timestamp, line) in events {
+        let rank = Event::parse(&line).map_or(0, |event| event.event_type.get_merge_rank());
+        merged.push(((timestamp, rank, true), seal_line(&line)?));
+    }
+
+    // Stable, so lines of the same kind keep their order
+    merged.sort_by_key(|(order, _)| *order);
+    lines.extend(merged.into_iter().map(|(_, line)| line));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::merge_event_lines;
+
+    fn session(path: &str, start: u64, end: u64) -> ImportedSession {
+        ImportedSession {
+            path: path.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_normalize_sessions() {
+        let sessions = vec![session("b", 50, 200), session("a", 0, 100), session("c", 120, 150)];
+        assert_eq!(
+            normalize_sessions(sessions),
+            vec![session("a", 0, 100), session("b", 100, 200)]
+        );
+    }
+
+    #[test]
+    fn test_merge_lines() {
+        let mut lines = vec!["0,1000,/usr/bin/code".to_string(), "1,2000".to_string()];
+        let events = vec![
+            (500, "0,500,/usr/bin/slack".to_string()),
+            (1000, "1,1000".to_string()),
+            (2000, "0,2000,/usr/bin/mail".to_string()),
+            (3000, "1,3000".to_string()),
+        ];
+        merge_lines(&mut lines, events).unwrap();
+        // The imported sessions end before the recorded ones start, and start after they end
+        let lines: Vec<String> = lines.iter().map(|line| open_line(line).unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec!["0,500,/usr/bin/slack", "1,1000", "0,1000,/usr/bin/code", "1,2000", "0,2000,/usr/bin/mail", "1,3000"]
+        );
+
+        // Going idle ends a session like shutting down does, as when syncing
+        let mut lines = vec!["0,1000,/usr/bin/code".to_string(), "4,2000".to_string()];
+        let events = vec![(2000, "0,2000,/usr/bin/slack".to_string()), (3000, "1,3000".to_string())];
+        merge_lines(&mut lines, events.clone()).unwrap();
+        let imported: Vec<String> = events.into_iter().map(|(_, line)| line).collect();
+        let synced = merge_event_lines(&["0,1000,/usr/bin/code".to_string(), "4,2000".to_string()], &imported).unwrap();
+        let lines: Vec<String> = lines.iter().map(|line| open_line(line).unwrap()).collect();
+        assert_eq!(lines, vec!["0,1000,/usr/bin/code", "4,2000", "0,2000,/usr/bin/slack", "1,3000"]);
+        assert_eq!(lines, synced);
+    }
+
+    #[test]
+    fn test_subtract_covered() {
+        let sessions = vec![session("a", 0, 100), session("b", 100, 200)];
+        let covered = [(20, 40), (60, 120), (150, 300)];
+        assert_eq!(
+            subtract_covered(sessions, &covered),
+            vec![
+                session("a", 0, 20),
+                session("a", 40, 60),
+                session("b", 120, 150)
+            ]
+        );
+    }
+}
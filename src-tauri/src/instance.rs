@@ -0,0 +1,53 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::utils::get_log_file_dir_str;
+
+/// An advisory lock on a file in the data directory, held for as long as a process runs
+///
+/// The OS releases it when the process exits, even after a crash, so a stale lock file never
+/// keeps the next instance from starting.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Lock `{name}.lock` in the data directory. Returns `None` if another process holds it.
+    pub fn acquire(name: &str) -> io::Result<Option<InstanceLock>> {
+        fs::create_dir_all(get_log_file_dir_str())?;
+        InstanceLock::acquire_file(format!("{}/{}.lock", get_log_file_dir_str(), name))
+    }
+
+    fn acquire_file<P: AsRef<Path>>(path: P) -> io::Result<Option<InstanceLock>> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        // Only informative, the lock is what counts
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Some(InstanceLock { _file: file }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_lock() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lock_path = temp_dir.path().join("flows.lock");
+
+        let lock = InstanceLock::acquire_file(&lock_path).unwrap();
+        assert!(lock.is_some());
+        assert!(InstanceLock::acquire_file(&lock_path).unwrap().is_none());
+        assert_eq!(fs::read_to_string(&lock_path).unwrap().trim(), std::process::id().to_string());
+
+        drop(lock);
+        assert!(InstanceLock::acquire_file(&lock_path).unwrap().is_some());
+    }
+}
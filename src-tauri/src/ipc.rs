@@ -0,0 +1,241 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+use crate::corrections::Correction;
+use crate::events::subscribe_events;
+use crate::sys_monitor::{get_app_usages_from_log, AppUsage, Event};
+use crate::timeline::{get_timelines, Timelines};
+use crate::utils::get_log_file_dir_str;
+
+/// A request to the daemon. Sent as one line of JSON, e.g. `{"command":"usages",...}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Ping,
+    /// Whether the daemon is paused, and the app in front
+    Status,
+    /// Stop recording until `resume`, e.g. during a screen share
+    Pause,
+    Resume,
+    /// The app in front
+    Current,
+    /// Record a label at the current time
+    Tag { label: String },
+    /// Stream every event as a line of JSON as it is written, after the first response
+    Subscribe,
+    Usages { start_timestamp: u64, end_timestamp: u64 },
+    /// The raw and smoothed timelines
+    Timeline { start_timestamp: u64, end_timestamp: u64 },
+    EncryptLogs,
+    /// Add a correction, or replace the one with its id
+    SaveCorrection { correction: Correction },
+    DeleteCorrection { id: u64 },
+    Import { source: String, file_path: String },
+    /// Sync with the remote of the config now
+    Sync,
+    /// Bring the dashboard to front. Served by the app on its own socket, not by the daemon.
+    ShowDashboard,
+    Stop,
+}
+
+/// The answer to a request, also sent as one line of JSON
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn from_result(result: io::Result<Value>) -> Response {
+        match result {
+            Ok(data) => Response {
+                ok: true,
+                data: Some(data),
+                error: None,
+            },
+            Err(e) => Response {
+                ok: false,
+                data: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+fn get_runtime_file_path(file_name: &str) -> PathBuf {
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(runtime_dir) if !runtime_dir.is_empty() => PathBuf::from(runtime_dir).join(file_name),
+        _ => PathBuf::from(get_log_file_dir_str()).join(file_name),
+    }
+}
+
+/// Get the path of the daemon socket, in the runtime directory if there is one
+pub fn get_socket_path() -> PathBuf {
+    get_runtime_file_path("flows.sock")
+}
+
+/// Get the path of the socket of the running app, where a second launch sends its request
+pub fn get_app_socket_path() -> PathBuf {
+    get_runtime_file_path("flows-app.sock")
+}
+
+/// Listen on the daemon socket, answering each request with `handler`
+///
+/// Every connection gets its own thread and may send any number of requests. Blocks forever.
+pub fn serve<F>(handler: F) -> io::Result<()>
+where
+    F: Fn(Request) -> io::Result<Value> + Send + Sync + 'static,
+{
+    serve_at(&get_socket_path(), handler)
+}
+
+/// Listen on the socket at `socket_path` like `serve`
+///
+/// The caller must hold the instance lock of the socket, since a socket file already there is replaced.
+pub fn serve_at<F>(socket_path: &Path, handler: F) -> io::Result<()>
+where
+    F: Fn(Request) -> io::Result<Value> + Send + Sync + 'static,
+{
+    if let Some(parent) = socket_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // A socket file left behind by a crashed daemon would make bind fail
+    if socket_path.exists() {
+        fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+    info!("Listening on {:?}", socket_path);
+
+    let handler = Arc::new(handler);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, handler.as_ref()) {
+                        debug!("Connection closed: {}", e);
+                    }
+                });
+            }
+            Err(e) => error!("Failed to accept connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection<F>(stream: UnixStream, handler: &F) -> io::Result<()>
+where
+    F: Fn(Request) -> io::Result<Value>,
+{
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Subscribe) => return stream_events(writer),
+            Ok(request) => Response::from_result(handler(request)),
+            Err(e) => Response::from_result(Err(io::Error::new(io::ErrorKind::InvalidInput, e))),
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write every new event to a subscriber until it disconnects
+fn stream_events(mut writer: UnixStream) -> io::Result<()> {
+    let receiver = subscribe_events();
+    serde_json::to_writer(&mut writer, &Response::from_result(Ok(Value::Null)))?;
+    writeln!(writer)?;
+    for event in receiver {
+        serde_json::to_writer(&mut writer, &event)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Send a request to the daemon and return the data of its response
+pub fn send_request(request: &Request) -> io::Result<Value> {
+    send_request_to(&get_socket_path(), request)
+}
+
+/// Send a request to the socket at `socket_path` and return the data of its response
+pub fn send_request_to(socket_path: &Path, request: &Request) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    serde_json::to_writer(&mut stream, request)?;
+    writeln!(stream)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response: Response = serde_json::from_str(&line)?;
+    if response.ok {
+        Ok(response.data.unwrap_or(Value::Null))
+    } else {
+        Err(io::Error::other(response.error.unwrap_or_default()))
+    }
+}
+
+/// Subscribe to the events the daemon writes. The iterator ends when the daemon stops.
+pub fn subscribe() -> io::Result<impl Iterator<Item = io::Result<Event>>> {
+    let mut stream = UnixStream::connect(get_socket_path())?;
+    serde_json::to_writer(&mut stream, &Request::Subscribe)?;
+    writeln!(stream)?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let first_line = lines.next().unwrap_or_else(|| Err(io::ErrorKind::UnexpectedEof.into()))?;
+    let response: Response = serde_json::from_str(&first_line)?;
+    if !response.ok {
+        return Err(io::Error::other(response.error.unwrap_or_default()));
+    }
+    Ok(lines.map(|line| Ok(serde_json::from_str(&line?)?)))
+}
+
+pub fn is_daemon_running() -> bool {
+    send_request(&Request::Ping).is_ok()
+}
+
+/// Send a request to the daemon, or run `fallback` in this process if the daemon isn't running
+pub fn request_or_else<T, F>(request: &Request, fallback: F) -> io::Result<T>
+where
+    T: DeserializeOwned,
+    F: FnOnce() -> io::Result<T>,
+{
+    match send_request(request) {
+        Ok(data) => Ok(serde_json::from_value(data)?),
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) => fallback(),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get app usages from the daemon, or from the day logs directly if it isn't running
+pub fn query_app_usages(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<AppUsage>> {
+    let request = Request::Usages {
+        start_timestamp,
+        end_timestamp,
+    };
+    request_or_else(&request, || get_app_usages_from_log(start_timestamp, end_timestamp))
+}
+
+/// Get the timelines from the daemon, or from the day logs directly if it isn't running
+pub fn query_timelines(start_timestamp: u64, end_timestamp: u64) -> io::Result<Timelines> {
+    let request = Request::Timeline {
+        start_timestamp,
+        end_timestamp,
+    };
+    request_or_else(&request, || get_timelines(start_timestamp, end_timestamp))
+}
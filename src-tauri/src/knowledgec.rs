@@ -0,0 +1,218 @@
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::import::{get_known_app_paths, import_sessions, resolve_app_path, ImportSummary, ImportedSession};
+
+/// Core Data timestamps count seconds from 2001-01-01 instead of 1970-01-01
+const CORE_DATA_EPOCH_OFFSET: f64 = 978307200.0;
+
+/// A row of the `/app/usage` stream
+#[derive(Debug, PartialEq)]
+struct AppUsageRecord {
+    bundle_id: String,
+    start: u64,
+    end: u64,
+}
+
+fn to_io_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+/// Read the `/app/usage` stream from a copy of `knowledgeC.db`
+fn read_app_usage_records(db_path: &Path) -> io::Result<Vec<AppUsageRecord>> {
+    let connection = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(to_io_error)?;
+    let mut statement = connection
+        .prepare(
+            "SELECT ZVALUESTRING, ZSTARTDATE, ZENDDATE FROM ZOBJECT \
+             WHERE ZSTREAMNAME = '/app/usage' AND ZVALUESTRING IS NOT NULL \
+             ORDER BY ZSTARTDATE",
+        )
+        .map_err(to_io_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?))
+        })
+        .map_err(to_io_error)?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let (bundle_id, start, end) = row.map_err(to_io_error)?;
+        if end <= start {
+            continue;
+        }
+        records.push(AppUsageRecord {
+            bundle_id,
+            start: ((start + CORE_DATA_EPOCH_OFFSET) * 1000.0) as u64,
+            end: ((end + CORE_DATA_EPOCH_OFFSET) * 1000.0) as u64,
+        });
+    }
+    Ok(records)
+}
+
+fn get_default_app_dirs() -> Vec<PathBuf> {
+    let mut app_dirs = vec![
+        PathBuf::from("/Applications"),
+        PathBuf::from("/System/Applications"),
+        PathBuf::from("/System/Applications/Utilities"),
+        PathBuf::from("/Applications/Utilities"),
+    ];
+    if let Ok(home_dir) = std::env::var("HOME") {
+        app_dirs.push(PathBuf::from(home_dir).join("Applications"));
+    }
+    app_dirs
+}
+
+/// Map bundle ids to the executable paths of the apps installed in `app_dirs`
+///
+/// These are the paths the monitor records for the frontmost app.
+fn get_bundle_app_paths(app_dirs: &[PathBuf]) -> HashMap<String, String> {
+    let mut bundle_app_paths = HashMap::new();
+    for app_dir in app_dirs {
+        let Ok(entries) = fs::read_dir(app_dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let app_path = entry.path();
+            if app_path.extension().is_none_or(|ext| ext != "app") {
+                continue;
+            }
+            let info = match plist::Value::from_file(app_path.join("Contents/Info.plist")) {
+                Ok(info) => info,
+                Err(e) => {
+                    debug!("Failed to read Info.plist of {:?}: {}", app_path, e);
+                    continue;
+                }
+            };
+            let Some(info) = info.as_dictionary() else {
+                continue;
+            };
+            let bundle_id = info.get("CFBundleIdentifier").and_then(|value| value.as_string());
+            let executable = info.get("CFBundleExecutable").and_then(|value| value.as_string());
+            if let (Some(bundle_id), Some(executable)) = (bundle_id, executable) {
+                let executable_path = app_path.join("Contents/MacOS").join(executable);
+                bundle_app_paths.insert(bundle_id.to_string(), executable_path.to_string_lossy().to_string());
+            }
+        }
+    }
+    bundle_app_paths
+}
+
+/// Map a bundle id to an app path, falling back to the last part of the id as the app name
+fn resolve_bundle_path(
+    bundle_id: &str,
+    bundle_app_paths: &HashMap<String, String>,
+    known_app_paths: &HashMap<String, String>,
+) -> String {
+    if let Some(path) = bundle_app_paths.get(bundle_id) {
+        return path.clone();
+    }
+    let app_name = bundle_id.rsplit('.').next().unwrap_or(bundle_id);
+    resolve_app_path(app_name, known_app_paths)
+}
+
+fn get_sessions(
+    db_path: &Path,
+    app_dirs: &[PathBuf],
+    known_app_paths: &HashMap<String, String>,
+) -> io::Result<Vec<ImportedSession>> {
+    let bundle_app_paths = get_bundle_app_paths(app_dirs);
+    Ok(read_app_usage_records(db_path)?
+        .into_iter()
+        .map(|record| ImportedSession {
+            path: resolve_bundle_path(&record.bundle_id, &bundle_app_paths, known_app_paths),
+            start: record.start,
+            end: record.end,
+        })
+        .collect())
+}
+
+/// Import the Screen Time history from a copy of `knowledgeC.db` into the day logs
+///
+/// The live database at `~/Library/Application Support/Knowledge/knowledgeC.db` is locked while
+/// macOS uses it, so copy it first.
+pub fn import_knowledgec(db_path: &str) -> io::Result<ImportSummary> {
+    let sessions = get_sessions(Path::new(db_path), &get_default_app_dirs(), &get_known_app_paths()?)?;
+    import_sessions(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_fixture_db(db_path: &Path) {
+        let connection = Connection::open(db_path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE ZOBJECT (Z_PK INTEGER PRIMARY KEY, ZSTREAMNAME VARCHAR, ZVALUESTRING VARCHAR, ZSTARTDATE TIMESTAMP, ZENDDATE TIMESTAMP);
+                 INSERT INTO ZOBJECT (ZSTREAMNAME, ZVALUESTRING, ZSTARTDATE, ZENDDATE) VALUES
+                     ('/app/usage', 'com.microsoft.VSCode', 736250400.0, 736251000.0),
+                     ('/app/usage', 'com.apple.Safari', 736250000.0, 736250400.0),
+                     ('/app/inFocus', 'com.apple.Safari', 736250000.0, 736250400.0),
+                     ('/app/usage', NULL, 736251000.0, 736251100.0),
+                     ('/app/usage', 'com.apple.finder', 736251100.0, 736251100.0);",
+            )
+            .unwrap();
+    }
+
+    fn create_fixture_app(app_dir: &Path, name: &str, bundle_id: &str, executable: &str) {
+        let contents_dir = app_dir.join(format!("{}.app/Contents", name));
+        fs::create_dir_all(&contents_dir).unwrap();
+        fs::write(
+            contents_dir.join("Info.plist"),
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleExecutable</key>
+    <string>{}</string>
+    <key>CFBundleIdentifier</key>
+    <string>{}</string>
+</dict>
+</plist>"#,
+                executable, bundle_id
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_sessions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("knowledgeC.db");
+        create_fixture_db(&db_path);
+        let app_dir = temp_dir.path().join("Applications");
+        create_fixture_app(&app_dir, "Visual Studio Code", "com.microsoft.VSCode", "Electron");
+
+        let known_app_paths = HashMap::from([(
+            "safari".to_string(),
+            "/Applications/Safari.app/Contents/MacOS/Safari".to_string(),
+        )]);
+        let sessions = get_sessions(&db_path, std::slice::from_ref(&app_dir), &known_app_paths).unwrap();
+
+        // 736250000 seconds after 2001-01-01
+        let start = 1714557200000;
+        assert_eq!(
+            sessions,
+            vec![
+                ImportedSession {
+                    path: "/Applications/Safari.app/Contents/MacOS/Safari".to_string(),
+                    start,
+                    end: start + 400000,
+                },
+                ImportedSession {
+                    path: app_dir
+                        .join("Visual Studio Code.app/Contents/MacOS/Electron")
+                        .to_string_lossy()
+                        .to_string(),
+                    start: start + 400000,
+                    end: start + 1000000,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+pub mod activitywatch;
+pub mod api;
+pub mod autostart;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod corrections;
+pub mod crypto;
+pub mod daemon;
+#[cfg(target_os = "linux")]
+pub mod dbus;
+pub mod events;
+pub mod export;
+pub mod import;
+pub mod instance;
+pub mod ipc;
+pub mod knowledgec;
+pub mod log_writer;
+pub mod metrics;
+pub mod monitor;
+pub mod projects;
+pub mod retention;
+pub mod rules;
+pub mod status_bar;
+pub mod sync;
+pub mod sys_monitor;
+pub mod timeline;
+pub mod utils;
+pub mod webhooks;
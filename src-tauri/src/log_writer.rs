@@ -0,0 +1,233 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::sys_monitor::{Event, EventType};
+use crate::utils::{get_dir_of, get_log_file_dir_str, open_lock_file, read_log_lines, write_to_file};
+
+/// Appended lines reach the OS right away, so readers see them, but only reach the disk this often
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Appends to the day logs through a handle that stays open, instead of opening the file for every event
+struct LogWriter {
+    /// The day log that is open
+    log_file: Option<(PathBuf, BufWriter<File>)>,
+    /// The `files.lock` of the directory of the day log, locked for each append
+    lock_file: Option<(PathBuf, File)>,
+    heartbeat_file: Option<File>,
+    /// When the open files were last synced to disk, `None` if nothing was written since
+    unsynced_since: Option<Instant>,
+}
+
+/// Also held while rewriting a day log, so rewrites never drop new events
+static LOG_WRITER: Mutex<LogWriter> = Mutex::new(LogWriter {
+    log_file: None,
+    lock_file: None,
+    heartbeat_file: None,
+    unsynced_since: None,
+});
+
+impl LogWriter {
+    fn get_log_file(&mut self, path: &Path) -> io::Result<&mut BufWriter<File>> {
+        if self.log_file.as_ref().is_some_and(|(open_path, _)| open_path != path) {
+            self.close()?;
+        }
+        // Another process may have replaced the day log since, e.g. when importing
+        if let Some((_, log_file)) = self.log_file.as_ref() {
+            let replaced = match (fs::metadata(path), log_file.get_ref().metadata()) {
+                (Ok(current), Ok(open)) => current.dev() != open.dev() || current.ino() != open.ino(),
+                _ => true,
+            };
+            if replaced {
+                self.log_file = None;
+            }
+        }
+        if self.log_file.is_none() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().append(true).create(true).open(path)?;
+            self.log_file = Some((path.to_path_buf(), BufWriter::new(file)));
+        }
+        Ok(&mut self.log_file.as_mut().unwrap().1)
+    }
+
+    /// Append a line while holding the lock on the directory, so other processes never rewrite the day log meanwhile
+    fn append_line(&mut self, path: &Path, line: &str) -> io::Result<()> {
+        let dir = get_dir_of(path);
+        let (lock_dir, lock_file) = match self.lock_file.take() {
+            Some((lock_dir, lock_file)) if lock_dir == dir => (lock_dir, lock_file),
+            _ => (dir.to_path_buf(), open_lock_file(dir)?),
+        };
+        lock_file.lock()?;
+        let result = self.get_log_file(path).and_then(|log_file| {
+            log_file.write_all(line.as_bytes())?;
+            log_file.write_all(b"\n")?;
+            log_file.flush()
+        });
+        let unlocked = lock_file.unlock();
+        self.lock_file = Some((lock_dir, lock_file));
+        result.and(unlocked)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        if let Some((_, log_file)) = self.log_file.as_mut() {
+            log_file.flush()?;
+            log_file.get_ref().sync_data()?;
+        }
+        if let Some(heartbeat_file) = self.heartbeat_file.as_ref() {
+            heartbeat_file.sync_data()?;
+        }
+        self.unsynced_since = None;
+        Ok(())
+    }
+
+    fn sync_if_due(&mut self) -> io::Result<()> {
+        match self.unsynced_since {
+            Some(since) if since.elapsed() >= SYNC_INTERVAL => self.sync(),
+            _ => Ok(()),
+        }
+    }
+
+    fn mark_unsynced(&mut self) {
+        self.unsynced_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Sync and close the day log, e.g. before it is replaced
+    fn close(&mut self) -> io::Result<()> {
+        let result = self.sync();
+        self.log_file = None;
+        result
+    }
+}
+
+/// Append a stored line to a day log
+///
+/// With `durable`, the line is on disk when this returns, e.g. for the last event before the process exits.
+pub(crate) fn append_log_line(path: &Path, line: &str, durable: bool) -> io::Result<()> {
+    let mut writer = LOG_WRITER.lock().unwrap();
+    if let Err(e) = writer.append_line(path, line) {
+        // Start over with a new handle next time, e.g. after the disk has been freed up
+        writer.log_file = None;
+        return Err(e);
+    }
+    writer.mark_unsynced();
+    if durable {
+        writer.sync()
+    } else {
+        writer.sync_if_due()
+    }
+}
+
+/// Run `update` with the day logs closed, and no events appended meanwhile
+pub(crate) fn with_log_files_closed<T, F>(update: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T>,
+{
+    let mut writer = LOG_WRITER.lock().unwrap();
+    writer.close()?;
+    update()
+}
+
+fn get_heartbeat_file_path() -> String {
+    format!("{}/heartbeat", get_log_file_dir_str())
+}
+
+/// Record that the monitor was still running at `timestamp`
+///
+/// If the process gets killed, the next start ends the open session at the last heartbeat.
+pub fn write_heartbeat(timestamp: u64) -> io::Result<()> {
+    let mut writer = LOG_WRITER.lock().unwrap();
+    if writer.heartbeat_file.is_none() {
+        fs::create_dir_all(get_log_file_dir_str())?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(get_heartbeat_file_path())?;
+        writer.heartbeat_file = Some(file);
+    }
+    // Always the same length in place, so it is never half written
+    let content = format!("{:020}\n", timestamp);
+    writer.heartbeat_file.as_ref().unwrap().write_all_at(content.as_bytes(), 0)?;
+    writer.mark_unsynced();
+    writer.sync_if_due()
+}
+
+pub fn read_heartbeat() -> Option<u64> {
+    fs::read_to_string(get_heartbeat_file_path()).ok()?.trim().parse().ok()
+}
+
+/// Split a day log into its complete lines and a last line cut off by a crash, if any
+fn split_torn_line(content: &str) -> (&str, &str) {
+    if content.is_empty() || content.ends_with('\n') {
+        return (content, "");
+    }
+    match content.rfind('\n') {
+        Some(index) => content.split_at(index + 1),
+        None => ("", content),
+    }
+}
+
+/// Repair the most recent day log after the last run ended abruptly
+///
+/// Removes a last line that was only partly written, and ends a session that was still open at the
+/// last heartbeat. Call before the monitor starts writing.
+pub fn recover_logs() -> io::Result<()> {
+    let log_dir = get_log_file_dir_str();
+    let latest_log = match fs::read_dir(&log_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.len() == 12 && name.ends_with(".log") && name[..8].bytes().all(|b| b.is_ascii_digit()))
+            .max(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let Some(latest_log) = latest_log else {
+        return Ok(());
+    };
+    let log_path = format!("{}/{}", log_dir, latest_log);
+
+    with_log_files_closed(|| {
+        let content = fs::read_to_string(&log_path)?;
+        let (complete, torn) = split_torn_line(&content);
+        if !torn.is_empty() {
+            warn!("Removing the partly written last line of {}: {:?}", log_path, torn);
+            let file = OpenOptions::new().write(true).open(&log_path)?;
+            file.set_len(complete.len() as u64)?;
+            file.sync_all()?;
+        }
+        Ok(())
+    })?;
+
+    // Tags, timezones and titles don't end sessions, so look at the last event that does or starts or continues one
+    let last_event = read_log_lines(&log_path)?
+        .iter()
+        .rev()
+        .filter_map(|line| Event::parse(line))
+        .find(|event| !matches!(event.event_type, EventType::Tag | EventType::Zone | EventType::Title));
+    let is_open = |event: &Event| matches!(event.event_type, EventType::CameToFront | EventType::Heartbeat);
+    if let Some(event) = last_event.filter(is_open) {
+        let end_timestamp = read_heartbeat().unwrap_or(0).max(event.timestamp);
+        info!("Ending the session left open by the last run at {}", end_timestamp);
+        write_to_file(EventType::ShutDown, end_timestamp, "");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_torn_line() {
+        assert_eq!(split_torn_line(""), ("", ""));
+        assert_eq!(split_torn_line("0,1,/a\n1,2\n"), ("0,1,/a\n1,2\n", ""));
+        assert_eq!(split_torn_line("0,1,/a\n0,2,/Appl"), ("0,1,/a\n", "0,2,/Appl"));
+        assert_eq!(split_torn_line("enc:AAB"), ("", "enc:AAB"));
+    }
+}
@@ -1,37 +1,47 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use chrono::Timelike;
+#[cfg(target_os = "macos")]
 use cocoa::base::nil;
+#[cfg(target_os = "macos")]
 use cocoa::foundation::NSString;
-use libproc::libproc::proc_pid;
+#[cfg(target_os = "macos")]
 use objc::runtime::{Class, Object};
+#[cfg(target_os = "macos")]
 use objc::{msg_send, sel, sel_impl};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use serde_json::Value;
+use std::io;
 use std::thread;
 use std::time::Duration;
 use tauri::{
     AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem, WindowEvent
 };
+use tauri::api::dialog::blocking::FileDialogBuilder;
 use tauri::api::notification::Notification;
 use tracing::{debug, error, info};
-use tracing_subscriber::fmt::format::FmtSpan;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::{fmt, Registry};
 
 mod app_management;
-mod sys_monitor;
-mod utils;
 
-use app_management::{add_app_to_login_items, is_app_in_login_items, terminate_previous_instance};
-use sys_monitor::{get_app_usages_from_log, get_frontmost_window_pid, AppUsage, EventType};
-use utils::{get_current_timestamp, write_to_file};
-
-static LAST_CHECK_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+use app_management::ensure_daemon_running;
+use flows::autostart::{enable_on_first_launch, Autostart};
+use flows::config::{init_from_config, Config};
+use flows::clock::{get_report_clock, DayRange};
+use flows::corrections::{delete_correction, get_corrections, save_correction, Correction};
+use flows::crypto::encrypt_existing_logs;
+use flows::export::{export_to_file, ExportFormat, ExportKind};
+use flows::instance::InstanceLock;
+use flows::ipc::{
+    get_app_socket_path, query_app_usages, query_timelines, request_or_else, send_request_to, serve_at, Request,
+};
+use flows::projects::{get_project_usages, ProjectUsage};
+use flows::rules::{get_category_usages, CategoryUsage};
+use flows::sys_monitor::AppUsage;
+use flows::timeline::Timelines;
+use flows::utils::init_tracing;
 
 #[tauri::command]
 fn get_app_usages_handler(start_timestamp: u64, end_timestamp: u64) -> Vec<AppUsage> {
-    match get_app_usages_from_log(start_timestamp, end_timestamp) {
+    match query_app_usages(start_timestamp, end_timestamp) {
         Ok(usage) => usage,
         Err(e) => {
             error!("Failed to get app usage: {}", e);
@@ -40,8 +50,125 @@ fn get_app_usages_handler(start_timestamp: u64, end_timestamp: u64) -> Vec<AppUs
     }
 }
 
+/// The day a timestamp falls on in reports, so the dashboard pages through the same days
 #[tauri::command]
-fn show_window_handler(app_handle: AppHandle) {
+fn get_day_range_handler(timestamp: u64) -> Option<DayRange> {
+    get_report_clock().get_day_range(timestamp)
+}
+
+/// The smoothed timeline of a range, and the raw one as recorded
+#[tauri::command]
+fn get_timeline_handler(start_timestamp: u64, end_timestamp: u64) -> Option<Timelines> {
+    match query_timelines(start_timestamp, end_timestamp) {
+        Ok(timelines) => Some(timelines),
+        Err(e) => {
+            error!("Failed to get the timeline: {}", e);
+            None
+        }
+    }
+}
+
+/// The time of each project in a range, longest first
+#[tauri::command]
+fn get_project_usages_handler(start_timestamp: u64, end_timestamp: u64) -> Vec<ProjectUsage> {
+    match query_timelines(start_timestamp, end_timestamp) {
+        Ok(timelines) => get_project_usages(&timelines.smoothed),
+        Err(e) => {
+            error!("Failed to get project usages: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// The time of each category in a range, longest first
+#[tauri::command]
+fn get_category_usages_handler(start_timestamp: u64, end_timestamp: u64) -> Vec<CategoryUsage> {
+    match query_timelines(start_timestamp, end_timestamp) {
+        Ok(timelines) => get_category_usages(&timelines.smoothed),
+        Err(e) => {
+            error!("Failed to get category usages: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[tauri::command]
+fn get_corrections_handler(start_timestamp: u64, end_timestamp: u64) -> Vec<Correction> {
+    match get_corrections(start_timestamp, end_timestamp) {
+        Ok(corrections) => corrections,
+        Err(e) => {
+            error!("Failed to get corrections: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Add a manual entry or correction, or edit the one with its id
+#[tauri::command]
+fn save_correction_handler(correction: Correction) -> Result<Correction, String> {
+    let request = Request::SaveCorrection {
+        correction: correction.clone(),
+    };
+    request_or_else(&request, || save_correction(correction)).map_err(|e| {
+        error!("Failed to save correction: {}", e);
+        e.to_string()
+    })
+}
+
+#[tauri::command]
+fn delete_correction_handler(id: u64) -> Result<Correction, String> {
+    request_or_else(&Request::DeleteCorrection { id }, || delete_correction(id)).map_err(|e| {
+        error!("Failed to delete correction {}: {}", id, e);
+        e.to_string()
+    })
+}
+
+/// Ask for a file and export the data of the given kind to it
+///
+/// The format follows the extension of the chosen file. Returns the file path, or `None` if cancelled.
+#[tauri::command]
+async fn export_handler(
+    kind: ExportKind,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> Result<Option<String>, String> {
+    let file_path = FileDialogBuilder::new()
+        .set_file_name("flows-export.csv")
+        .add_filter("CSV", &["csv"])
+        .add_filter("JSON", &["json"])
+        .add_filter("JSON Lines", &["jsonl"])
+        .save_file();
+    let Some(file_path) = file_path else {
+        return Ok(None);
+    };
+
+    let format = match file_path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => ExportFormat::Json,
+        Some("jsonl") => ExportFormat::Jsonl,
+        _ => ExportFormat::Csv,
+    };
+    let file_path = file_path.to_string_lossy().to_string();
+    match export_to_file(&file_path, kind, format, start_timestamp, end_timestamp) {
+        Ok(()) => {
+            info!("Exported {:?} to {}", kind, file_path);
+            Ok(Some(file_path))
+        }
+        Err(e) => {
+            error!("Failed to export {:?}: {}", kind, e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+fn encrypt_logs_handler() -> Result<usize, String> {
+    request_or_else(&Request::EncryptLogs, encrypt_existing_logs).map_err(|e| {
+        error!("Failed to encrypt logs: {}", e);
+        e.to_string()
+    })
+}
+
+fn show_dashboard(app_handle: &AppHandle) {
     if let Some(window) = app_handle.get_window("main") {
         window.emit("refresh_data", "").unwrap();
         window.show().unwrap();
@@ -51,53 +178,44 @@ fn show_window_handler(app_handle: AppHandle) {
     }
 }
 
-/// Check if the frontmost window has changed. If so, log the event.
-fn check(app: &AppHandle, shared_previous_path: Arc<Mutex<String>>) {
-    let current_timestamp = get_current_timestamp();
-
-    // Update the timestamp and check the time difference
-    let last_timestamp = LAST_CHECK_TIMESTAMP.load(Ordering::SeqCst);
-    if current_timestamp > last_timestamp + 10_000 {
-        info!("More than 10 seconds passed since last check. Last check: {}", last_timestamp);
-
-        write_to_file(EventType::ShutDown, last_timestamp, "");
-        let mut previous_path = shared_previous_path.lock().unwrap();
-        *previous_path = String::new();
-    }
+#[tauri::command]
+fn show_window_handler(app_handle: AppHandle) {
+    show_dashboard(&app_handle);
+}
 
-    match get_frontmost_window_pid() {
-        Ok(pid) => match proc_pid::pidpath(pid) {
-            Ok(current_path) => {
-                let mut previous_path = shared_previous_path.lock().unwrap();
-                if *previous_path != current_path {
-                    info!("New program: {}", current_path);
-                    write_to_file(EventType::CameToFront, current_timestamp, &current_path);
-                    *previous_path = current_path;
-                }
-            }
-            _ => error!("Failed to retrieve process path for PID {}", pid),
-        },
-        Err(e) => error!("{}", e),
+/// Answer the requests of later launches of the app, forever
+fn serve_later_launches(app_handle: AppHandle) {
+    let result = serve_at(&get_app_socket_path(), move |request| match request {
+        Request::ShowDashboard => {
+            show_dashboard(&app_handle);
+            Ok(Value::Null)
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Not served by the app")),
+    });
+    if let Err(e) = result {
+        error!("Failed to serve the app socket: {}", e);
     }
+}
 
-    // Update LAST_CHECK_TIMESTAMP with the current timestamp
-    LAST_CHECK_TIMESTAMP.store(current_timestamp, Ordering::SeqCst);
-
-    // Notify the user when 23:30
-    let now = chrono::Local::now();
+/// Notify the user at 23:30 every day
+fn notify_daily(app: &AppHandle) {
     let title = "Flows";
     let body = "Check your screen time!";
-
-    if now.hour() == 23 && now.minute() == 30 && now.second() == 0 {
-        let _ = Notification::new(&app.config().tauri.bundle.identifier)
-            .title(title)
-            .body(body)
-            .show();
-        debug!("Notified user: {}: {}", title, body);
+    loop {
+        let now = chrono::Local::now();
+        if now.hour() == 23 && now.minute() == 30 && now.second() == 0 {
+            let _ = Notification::new(&app.config().tauri.bundle.identifier)
+                .title(title)
+                .body(body)
+                .show();
+            debug!("Notified user: {}: {}", title, body);
+        }
+        thread::sleep(Duration::from_secs(1));
     }
 }
 
-fn show_alert(title: &str, message: &str) {
+#[cfg(target_os = "macos")]
+fn show_alert(_app: &AppHandle, title: &str, message: &str) {
     unsafe {
         let alert_class = Class::get("NSAlert").unwrap();
         let alert: *mut Object = msg_send![alert_class, alloc];
@@ -110,57 +228,75 @@ fn show_alert(title: &str, message: &str) {
     }
 }
 
-fn init_tracing(data_path: &str) -> tracing_appender::non_blocking::WorkerGuard {
-    let file_appender = tracing_appender::rolling::daily(data_path, "app.log");
-    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
-
-    let console_layer = fmt::layer()
-        .with_span_events(FmtSpan::CLOSE)
-        .with_writer(std::io::stdout);
-
-    let file_layer = fmt::layer()
-        .with_span_events(FmtSpan::CLOSE)
-        .with_ansi(false)
-        .with_writer(file_writer);
-
-    let subscriber = Registry::default().with(console_layer).with(file_layer);
-
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set subscriber");
-
-    guard
+/// Other desktops have no alert without a window, so show a notification instead
+#[cfg(not(target_os = "macos"))]
+fn show_alert(app: &AppHandle, title: &str, message: &str) {
+    let notification = Notification::new(&app.config().tauri.bundle.identifier)
+        .title(title)
+        .body(message);
+    if let Err(e) = notification.show() {
+        error!("Failed to show {}: {}", title, e);
+    }
 }
 
 fn main() {
     let app_name = "Flows";
-    let process_name = "flows";
-    let app_path = format!("/Applications/{}.app", app_name);
     let data_path = format!("{}/Documents/{}", std::env::var("HOME").unwrap(), app_name);
 
-    // Check if there has been a previous instance of the app running. If so, terminate it.
-    terminate_previous_instance(process_name);
+    // Only one instance runs. A second launch brings the dashboard of the first one to front and quits.
+    let _instance_lock = match InstanceLock::acquire("flows") {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            if let Err(e) = send_request_to(&get_app_socket_path(), &Request::ShowDashboard) {
+                println!("Failed to reach the running instance: {}", e);
+            }
+            return;
+        }
+        Err(e) => {
+            println!("Failed to lock the data directory: {}", e);
+            return;
+        }
+    };
 
     // Initialize tracing
-    let _guard = init_tracing(&(data_path + "/logs"));
+    let _guard = init_tracing(&(data_path + "/logs"), "app.log");
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Not starting: {}", e);
+            println!("{}", e);
+            return;
+        }
+    };
+    init_from_config(&config);
 
-    // Check if the app is in login items. If not, add it.
-    if !is_app_in_login_items(&app_path) {
-        info!("App is not in login items, adding...");
-        add_app_to_login_items(&app_path);
+    // Open at login by default, but only set that up once, so turning it off sticks
+    let autostart = Autostart::app();
+    match &autostart {
+        Ok(autostart) => {
+            if let Err(e) = enable_on_first_launch(autostart) {
+                error!("Failed to enable autostart: {}", e);
+            }
+        }
+        Err(e) => error!("Failed to get the app path for autostart: {}", e),
     }
 
-    // Start the system monitor thread
-    let shared_previous_path = Arc::new(Mutex::new(String::new()));
-    let current_timestamp = get_current_timestamp();
-    LAST_CHECK_TIMESTAMP.store(current_timestamp, Ordering::SeqCst);
+    // Tracking happens in flows-daemon, so it keeps running when the app quits
+    ensure_daemon_running();
 
     // Create the system tray
-    let shared_path_clone = Arc::clone(&shared_previous_path);
     let dashboard = CustomMenuItem::new("dashboard".to_string(), "Dashboard");
+    let mut open_at_login = CustomMenuItem::new("open_at_login".to_string(), "Open at Login");
+    if autostart.as_ref().is_ok_and(|autostart| autostart.is_enabled()) {
+        open_at_login = open_at_login.selected();
+    }
     let about = CustomMenuItem::new("about".to_string(), "About");
     let quit = CustomMenuItem::new("quit".to_string(), "Quit");
     let tray_menu = SystemTrayMenu::new()
         .add_item(dashboard)
         .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(open_at_login)
         .add_item(about)
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(quit);
@@ -169,35 +305,49 @@ fn main() {
     // Start the app
     tauri::Builder::default()
         .setup(move |app| {
-            let shared_path_clone = Arc::clone(&shared_previous_path);
             let app_handle = app.handle();
-            let _ = thread::spawn(move || {
-                loop {
-                    check(&app_handle, Arc::clone(&shared_path_clone));
-                    thread::sleep(Duration::from_secs(1));
-                }
-            });
-            Ok(app.set_activation_policy(tauri::ActivationPolicy::Accessory))
+            let _ = thread::spawn(move || notify_daily(&app_handle));
+            let socket_app_handle = app.handle();
+            thread::spawn(move || serve_later_launches(socket_app_handle));
+            // Only in the menu bar, without a Dock icon
+            #[cfg(target_os = "macos")]
+            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_app_usages_handler,
+            get_day_range_handler,
+            get_timeline_handler,
+            get_project_usages_handler,
+            get_category_usages_handler,
+            get_corrections_handler,
+            save_correction_handler,
+            delete_correction_handler,
+            encrypt_logs_handler,
+            export_handler,
             show_window_handler
         ])
         .system_tray(tray)
         .on_system_tray_event(move |app, event| match event {
             SystemTrayEvent::MenuItemClick { id, .. } => {
-                let _item_handle = app.tray_handle().get_item(&id);
+                let item_handle = app.tray_handle().get_item(&id);
                 match id.as_str() {
                     "quit" => {
-                        write_to_file(EventType::StopMonitoring, get_current_timestamp(), "");
                         std::process::exit(0);
                     }
-                    "dashboard" => {
-                        check(app, Arc::clone(&shared_path_clone));
-                        let window = app.get_window("main").unwrap();
-                        window.emit("refresh_data", "").unwrap();
-                        window.show().unwrap();
-                        window.set_focus().unwrap();
+                    "dashboard" => show_dashboard(app),
+                    "open_at_login" => {
+                        let Ok(autostart) = &autostart else {
+                            return;
+                        };
+                        let enabled = !autostart.is_enabled();
+                        let result = if enabled { autostart.enable() } else { autostart.disable() };
+                        match result {
+                            Ok(()) => {
+                                let _ = item_handle.set_selected(enabled);
+                            }
+                            Err(e) => error!("Failed to change autostart: {}", e),
+                        }
                     }
                     "about" => {
                         let version = "0.1.0";
@@ -207,6 +357,7 @@ fn main() {
                             "Release"
                         };
                         show_alert(
+                            app,
                             "About Flows",
                             &format!("Version: {} ({})", version, build_type),
                         );
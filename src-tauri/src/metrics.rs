@@ -0,0 +1,187 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::clock::get_report_clock;
+use crate::monitor::Monitor;
+use crate::sys_monitor::get_app_usages_from_log;
+
+static LOG_WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static SLEEP_GAPS: AtomicU64 = AtomicU64::new(0);
+static SLEEP_GAP_MILLIS: AtomicU64 = AtomicU64::new(0);
+static CHECKS: AtomicU64 = AtomicU64::new(0);
+static CHECK_MICROS: AtomicU64 = AtomicU64::new(0);
+static LAST_CHECK_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Count an event that could not be written to the day log
+pub fn record_log_write_error() {
+    LOG_WRITE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a gap between two checks of the monitor, usually because the computer was asleep
+pub fn record_sleep_gap(millis: u64) {
+    SLEEP_GAPS.fetch_add(1, Ordering::Relaxed);
+    SLEEP_GAP_MILLIS.fetch_add(millis, Ordering::Relaxed);
+}
+
+/// Record how long one check of the monitor took
+pub fn record_check(duration: Duration) {
+    let micros = duration.as_micros() as u64;
+    CHECKS.fetch_add(1, Ordering::Relaxed);
+    CHECK_MICROS.fetch_add(micros, Ordering::Relaxed);
+    LAST_CHECK_MICROS.store(micros, Ordering::Relaxed);
+}
+
+/// Escape a label value as the Prometheus text format requires
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Append one metric with its help text, type and samples
+///
+/// Each sample is the rest of the series name, e.g. `{app="Safari"}` or `_sum`, and the value.
+fn write_metric(out: &mut String, name: &str, metric_type: &str, help: &str, samples: &[(String, String)]) {
+    out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, metric_type));
+    for (series, value) in samples {
+        out.push_str(&format!("{}{} {}\n", name, series, value));
+    }
+}
+
+fn millis_to_secs(millis: u64) -> String {
+    (millis as f64 / 1000.0).to_string()
+}
+
+fn micros_to_secs(micros: u64) -> String {
+    (micros as f64 / 1e6).to_string()
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render_metrics(monitor: &Monitor) -> io::Result<String> {
+    let (day_start, now) = get_report_clock().get_today_so_far();
+    let app_usages = get_app_usages_from_log(day_start, now)?;
+    let current = monitor.current_app();
+
+    let mut out = String::new();
+    write_metric(
+        &mut out,
+        "flows_app_seconds_today",
+        "gauge",
+        "Seconds each app has been in front today, the day as reports count it (see reports.timezone).",
+        &app_usages
+            .iter()
+            .map(|app| (format!("{{app=\"{}\"}}", escape_label(&app.name)), app.total_secs.to_string()))
+            .collect::<Vec<_>>(),
+    );
+    write_metric(
+        &mut out,
+        "flows_current_app",
+        "gauge",
+        "The app in front, as labels. Absent while nothing is tracked.",
+        &current
+            .path
+            .iter()
+            .map(|path| {
+                let name = current.name.as_deref().unwrap_or("Unknown");
+                (
+                    format!("{{app=\"{}\",path=\"{}\"}}", escape_label(name), escape_label(path)),
+                    "1".to_string(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+    write_metric(
+        &mut out,
+        "flows_idle",
+        "gauge",
+        "Whether the user is idle.",
+        &[(String::new(), (monitor.is_idle() as u8).to_string())],
+    );
+    write_metric(
+        &mut out,
+        "flows_idle_seconds",
+        "gauge",
+        "Seconds since the last keyboard or mouse input.",
+        &[(String::new(), millis_to_secs(monitor.idle_millis()))],
+    );
+    write_metric(
+        &mut out,
+        "flows_paused",
+        "gauge",
+        "Whether tracking is paused.",
+        &[(String::new(), (monitor.is_paused() as u8).to_string())],
+    );
+    write_metric(
+        &mut out,
+        "flows_monitor_check_duration_seconds",
+        "summary",
+        "Time taken by the checks of the monitor loop.",
+        &[
+            ("_sum".to_string(), micros_to_secs(CHECK_MICROS.load(Ordering::Relaxed))),
+            ("_count".to_string(), CHECKS.load(Ordering::Relaxed).to_string()),
+        ],
+    );
+    write_metric(
+        &mut out,
+        "flows_monitor_last_check_duration_seconds",
+        "gauge",
+        "Time taken by the last check of the monitor loop.",
+        &[(String::new(), micros_to_secs(LAST_CHECK_MICROS.load(Ordering::Relaxed)))],
+    );
+    write_metric(
+        &mut out,
+        "flows_monitor_last_check_timestamp_seconds",
+        "gauge",
+        "When the monitor loop last ran.",
+        &[(String::new(), millis_to_secs(monitor.last_check_timestamp()))],
+    );
+    write_metric(
+        &mut out,
+        "flows_log_write_errors_total",
+        "counter",
+        "Events that could not be written to the day log.",
+        &[(String::new(), LOG_WRITE_ERRORS.load(Ordering::Relaxed).to_string())],
+    );
+    write_metric(
+        &mut out,
+        "flows_sleep_gaps_total",
+        "counter",
+        "Gaps between checks of the monitor longer than monitor.gap_threshold_secs, e.g. while asleep.",
+        &[(String::new(), SLEEP_GAPS.load(Ordering::Relaxed).to_string())],
+    );
+    write_metric(
+        &mut out,
+        "flows_sleep_gap_seconds_total",
+        "counter",
+        "Total length of the gaps between checks of the monitor.",
+        &[(String::new(), millis_to_secs(SLEEP_GAP_MILLIS.load(Ordering::Relaxed)))],
+    );
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("My \"App\"\\\n"), "My \\\"App\\\"\\\\\\n");
+    }
+
+    #[test]
+    fn test_write_metric() {
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "flows_app_seconds_today",
+            "gauge",
+            "Seconds per app.",
+            &[("{app=\"Safari\"}".to_string(), "42".to_string())],
+        );
+        assert_eq!(
+            out,
+            "# HELP flows_app_seconds_today Seconds per app.\n\
+             # TYPE flows_app_seconds_today gauge\n\
+             flows_app_seconds_today{app=\"Safari\"} 42\n"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use libproc::libproc::proc_pid;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::clock::{get_system_zone, record_zone, Clock, SystemClock};
+use crate::config::MonitorConfig;
+use crate::log_writer::write_heartbeat;
+use crate::metrics::{record_check, record_sleep_gap};
+use crate::sys_monitor::{
+    get_app_name_from_path, get_frontmost_window_pid, get_frontmost_window_title, get_idle_millis, EventType,
+};
+use crate::utils::{get_current_timestamp, write_to_file};
+
+/// The app in front. Both fields are `None` when nothing is tracked, e.g. while paused.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CurrentApp {
+    pub name: Option<String>,
+    pub path: Option<String>,
+}
+
+/// Tracks the frontmost app and writes an event whenever it changes
+pub struct Monitor {
+    previous_path: Mutex<String>,
+    last_check_timestamp: AtomicU64,
+    /// When the monitor last wrote an event
+    last_event_timestamp: AtomicU64,
+    paused: AtomicBool,
+    /// When the monitor last wrote a heartbeat
+    last_heartbeat_timestamp: AtomicU64,
+    heartbeat_interval_millis: u64,
+    gap_threshold_millis: u64,
+    /// Idle detection is off when this is 0
+    idle_threshold_millis: u64,
+    idle_millis: AtomicU64,
+    idle: AtomicBool,
+    /// The last error, so a failure that repeats every second is only logged once
+    last_error: Mutex<Option<String>>,
+    /// The last error getting the idle time, logged once for the same reason
+    last_idle_error: Mutex<Option<String>>,
+    /// The timezone last written, and the date of the day log it was written to
+    last_zone: Mutex<Option<(Tz, NaiveDate)>>,
+    record_titles: bool,
+    /// The window title last written for the app in front
+    previous_title: Mutex<Option<String>>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Monitor::new()
+    }
+}
+
+impl Monitor {
+    pub fn new() -> Monitor {
+        Monitor::with_config(&MonitorConfig::default())
+    }
+
+    pub fn with_config(config: &MonitorConfig) -> Monitor {
+        let idle_threshold_millis = config.idle_threshold_secs.map(|secs| secs * 1000).unwrap_or(0);
+        Monitor {
+            previous_path: Mutex::new(String::new()),
+            last_check_timestamp: AtomicU64::new(get_current_timestamp()),
+            last_event_timestamp: AtomicU64::new(0),
+            last_heartbeat_timestamp: AtomicU64::new(0),
+            heartbeat_interval_millis: config.heartbeat_interval_secs * 1000,
+            gap_threshold_millis: config.gap_threshold_secs * 1000,
+            paused: AtomicBool::new(false),
+            idle_threshold_millis,
+            idle_millis: AtomicU64::new(0),
+            idle: AtomicBool::new(false),
+            last_error: Mutex::new(None),
+            last_idle_error: Mutex::new(None),
+            last_zone: Mutex::new(None),
+            record_titles: config.record_titles,
+            previous_title: Mutex::new(None),
+        }
+    }
+
+    /// Get the path of the app that is currently in front, or an empty string if there is none
+    pub fn current_path(&self) -> String {
+        self.previous_path.lock().unwrap().clone()
+    }
+
+    pub fn current_app(&self) -> CurrentApp {
+        let path = self.current_path();
+        if path.is_empty() {
+            return CurrentApp::default();
+        }
+        CurrentApp {
+            name: get_app_name_from_path(&path),
+            path: Some(path),
+        }
+    }
+
+    pub fn last_check_timestamp(&self) -> u64 {
+        self.last_check_timestamp.load(Ordering::SeqCst)
+    }
+
+    /// Whether there has been no input for longer than the idle threshold
+    pub fn is_idle(&self) -> bool {
+        self.idle.load(Ordering::SeqCst)
+    }
+
+    /// Get the time since the last input, as of the last check
+    pub fn idle_millis(&self) -> u64 {
+        self.idle_millis.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Stop recording until `resume`. Returns `false` if already paused.
+    pub fn pause(&self) -> bool {
+        if self.paused.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        info!("Paused");
+        self.stop();
+        true
+    }
+
+    /// Start recording again. Returns `false` if not paused.
+    pub fn resume(&self) -> bool {
+        if !self.paused.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        info!("Resumed");
+        true
+    }
+
+    fn log_error(&self, message: String) {
+        let mut last_error = self.last_error.lock().unwrap();
+        if last_error.as_ref() != Some(&message) {
+            error!("{}", message);
+            *last_error = Some(message);
+        }
+    }
+
+    /// Check if the frontmost window has changed. If so, log the event.
+    pub fn check(&self) {
+        let current_timestamp = get_current_timestamp();
+        if self.is_paused() {
+            // Keep the timestamp fresh, so resuming isn't taken for a wake from sleep
+            self.last_check_timestamp.store(current_timestamp, Ordering::SeqCst);
+            return;
+        }
+
+        // Update the timestamp and check the time difference
+        let last_timestamp = self.last_check_timestamp.load(Ordering::SeqCst);
+        if current_timestamp > last_timestamp + self.gap_threshold_millis {
+            info!("More than {} ms passed since last check. Last check: {}", self.gap_threshold_millis, last_timestamp);
+            record_sleep_gap(current_timestamp - last_timestamp);
+
+            write_to_file(EventType::ShutDown, last_timestamp, "");
+            let mut previous_path = self.previous_path.lock().unwrap();
+            *previous_path = String::new();
+        }
+
+        self.write_zone_if_changed(current_timestamp);
+
+        if self.check_idle(current_timestamp) {
+            self.last_check_timestamp.store(current_timestamp, Ordering::SeqCst);
+            return;
+        }
+
+        match get_frontmost_window_pid() {
+            Ok(pid) => match proc_pid::pidpath(pid) {
+                Ok(current_path) => {
+                    *self.last_error.lock().unwrap() = None;
+                    let mut previous_path = self.previous_path.lock().unwrap();
+                    if *previous_path != current_path {
+                        info!("New program: {}", current_path);
+                        write_to_file(EventType::CameToFront, current_timestamp, &current_path);
+                        self.last_event_timestamp.store(current_timestamp, Ordering::SeqCst);
+                        *previous_path = current_path;
+                        *self.previous_title.lock().unwrap() = None;
+                    } else {
+                        self.write_heartbeat_if_due(current_timestamp);
+                    }
+                    self.write_title_if_changed(current_timestamp);
+                }
+                _ => self.log_error(format!("Failed to retrieve process path for PID {}", pid)),
+            },
+            Err(e) => self.log_error(e.to_string()),
+        }
+
+        // Update the last check timestamp with the current timestamp
+        self.last_check_timestamp.store(current_timestamp, Ordering::SeqCst);
+    }
+
+    /// Write the timezone at the start of each day log and whenever it changes, so every event has one
+    fn write_zone_if_changed(&self, current_timestamp: u64) {
+        let (Some(zone), Some(date)) = (get_system_zone(), SystemClock.get_date(current_timestamp)) else {
+            return;
+        };
+        let mut last_zone = self.last_zone.lock().unwrap();
+        if *last_zone == Some((zone, date)) {
+            return;
+        }
+        if last_zone.map(|(last_zone, _)| last_zone) != Some(zone) {
+            if let Err(e) = record_zone(current_timestamp, zone) {
+                self.log_error(format!("Failed to record the timezone: {}", e));
+            }
+        }
+        write_to_file(EventType::Zone, current_timestamp, zone.name());
+        *last_zone = Some((zone, date));
+    }
+
+    /// Write the title of the window in front after the app came to front, and whenever it changes
+    ///
+    /// Nothing is written while the title can't be read, e.g. without the screen recording permission.
+    fn write_title_if_changed(&self, current_timestamp: u64) {
+        if !self.record_titles {
+            return;
+        }
+        let Ok(title) = get_frontmost_window_title() else {
+            return;
+        };
+        let mut previous_title = self.previous_title.lock().unwrap();
+        if previous_title.as_ref() != Some(&title) {
+            write_to_file(EventType::Title, current_timestamp, &title);
+            *previous_title = Some(title);
+        }
+    }
+
+    /// Record that the app in front is still in front, every heartbeat interval
+    fn write_heartbeat_if_due(&self, current_timestamp: u64) {
+        let last_timestamp = self
+            .last_event_timestamp
+            .load(Ordering::SeqCst)
+            .max(self.last_heartbeat_timestamp.load(Ordering::SeqCst));
+        if current_timestamp >= last_timestamp + self.heartbeat_interval_millis {
+            write_to_file(EventType::Heartbeat, current_timestamp, "");
+            self.last_heartbeat_timestamp.store(current_timestamp, Ordering::SeqCst);
+        }
+    }
+
+    /// Update the idle state, closing the current session when the user became idle
+    ///
+    /// Returns whether the user is idle, in which case no app is recorded. If the idle time can't be read,
+    /// the user counts as active until the next check reads it.
+    fn check_idle(&self, current_timestamp: u64) -> bool {
+        let threshold = self.idle_threshold_millis;
+        if threshold == 0 {
+            return false;
+        }
+        let idle_millis = match get_idle_millis() {
+            Ok(idle_millis) => {
+                if self.last_idle_error.lock().unwrap().take().is_some() {
+                    info!("Idle detection works again");
+                }
+                idle_millis
+            }
+            Err(e) => {
+                let mut last_idle_error = self.last_idle_error.lock().unwrap();
+                if last_idle_error.as_deref() != Some(e) {
+                    warn!("Failed to get the idle time: {}", e);
+                    *last_idle_error = Some(e.to_string());
+                }
+                return false;
+            }
+        };
+        self.idle_millis.store(idle_millis, Ordering::SeqCst);
+
+        if idle_millis < threshold {
+            if self.idle.swap(false, Ordering::SeqCst) {
+                info!("No longer idle");
+            }
+            return false;
+        }
+        if !self.idle.swap(true, Ordering::SeqCst) {
+            // The session ended with the last input, unless the app changed since then
+            let idle_since = current_timestamp
+                .saturating_sub(idle_millis)
+                .max(self.last_event_timestamp.load(Ordering::SeqCst));
+            info!("Idle since {}", idle_since);
+            write_to_file(EventType::Idle, idle_since, "");
+            *self.previous_path.lock().unwrap() = String::new();
+        }
+        true
+    }
+
+    /// Close the current session, e.g. before the process exits
+    pub fn stop(&self) {
+        write_to_file(EventType::StopMonitoring, get_current_timestamp(), "");
+        let mut previous_path = self.previous_path.lock().unwrap();
+        *previous_path = String::new();
+    }
+
+    /// Check the frontmost window every second, forever, with a heartbeat after each check
+    pub fn run(&self) {
+        loop {
+            let check_start = Instant::now();
+            self.check();
+            record_check(check_start.elapsed());
+            if let Err(e) = write_heartbeat(self.last_check_timestamp()) {
+                self.log_error(format!("Failed to write heartbeat: {}", e));
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
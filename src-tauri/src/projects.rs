@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::RwLock;
+
+use crate::config::ProjectConfig;
+use crate::corrections::{Change, Correction};
+use crate::timeline::{add_up_labels, EntryKind, TimelineEntry};
+
+/// The projects of the config, in order
+static PROJECTS: RwLock<Vec<ProjectConfig>> = RwLock::new(Vec::new());
+
+/// Use the projects of the config for the reports
+pub fn init_projects(projects: &[ProjectConfig]) {
+    *PROJECTS.write().unwrap() = projects.to_vec();
+}
+
+pub fn get_projects() -> Vec<ProjectConfig> {
+    PROJECTS.read().unwrap().clone()
+}
+
+/// Time spent on a project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectUsage {
+    pub project: String,
+    pub total_secs: u64,
+}
+
+/// Replace a leading `~` with the home directory
+pub(crate) fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}{}", home, rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Whether a path is a folder or in it
+pub(crate) fn is_in_folder(path: &str, folder: &str) -> bool {
+    let folder = folder.trim_end_matches('/');
+    path.strip_prefix(folder).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Whether a text mentions a folder or a path in it, e.g. `~/work/client/api: bash` for `~/work/client`
+fn contains_folder(text: &str, folder: &str) -> bool {
+    let folder = folder.trim_end_matches('/');
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.');
+    text.match_indices(folder)
+        .any(|(index, _)| !text[index + folder.len()..].chars().next().is_some_and(is_name_char))
+}
+
+/// Whether a text mentions a domain or one of its subdomains, e.g. `docs.example.com` for `example.com`
+pub(crate) fn contains_domain(text: &str, domain: &str) -> bool {
+    let text = text.to_lowercase();
+    let domain = domain.to_lowercase();
+    let is_domain_char = |c: char| c.is_alphanumeric() || c == '-';
+    text.match_indices(&domain).any(|(index, _)| {
+        let before = text[..index].chars().next_back();
+        let after = text[index + domain.len()..].chars().next();
+        !before.is_some_and(is_domain_char) && !after.is_some_and(is_domain_char)
+    })
+}
+
+/// Whether the rules of a project match an app entry of the timeline
+pub fn matches_project(project: &ProjectConfig, entry: &TimelineEntry) -> bool {
+    let path = entry.path.as_deref().unwrap_or("");
+    let title = entry.title.as_deref().unwrap_or("");
+    let title_lowercase = title.to_lowercase();
+
+    project
+        .titles
+        .iter()
+        .any(|pattern| !pattern.is_empty() && title_lowercase.contains(&pattern.to_lowercase()))
+        || project.paths.iter().filter(|folder| !folder.is_empty()).any(|folder| {
+            let expanded = expand_home(folder);
+            is_in_folder(path, &expanded) || contains_folder(title, folder) || contains_folder(title, &expanded)
+        })
+        || project
+            .domains
+            .iter()
+            .any(|domain| !domain.is_empty() && contains_domain(title, domain))
+}
+
+/// Set the project of each app entry of a timeline
+///
+/// The project rules of the config win over a project from the rules file. Project corrections win
+/// over both, later ones over earlier ones, and split the entries they partly cover.
+pub fn assign_projects(
+    timeline: Vec<TimelineEntry>,
+    projects: &[ProjectConfig],
+    corrections: &[Correction],
+) -> Vec<TimelineEntry> {
+    let mut timeline: Vec<TimelineEntry> = timeline
+        .into_iter()
+        .map(|mut entry| {
+            if entry.kind == EntryKind::App {
+                entry.project = projects
+                    .iter()
+                    .find(|project| matches_project(project, &entry))
+                    .map(|project| project.name.clone())
+                    .or_else(|| entry.project.clone());
+            }
+            entry
+        })
+        .collect();
+
+    for correction in corrections {
+        let Change::Project { project, .. } = &correction.change else {
+            continue;
+        };
+        let mut assigned = Vec::with_capacity(timeline.len());
+        for entry in timeline {
+            let applies = entry.kind == EntryKind::App
+                && entry.start < correction.end
+                && entry.end > correction.start
+                && entry.name.as_deref().is_some_and(|name| correction.affects(name));
+            if !applies {
+                assigned.push(entry);
+                continue;
+            }
+            if entry.start < correction.start {
+                assigned.push(TimelineEntry {
+                    end: correction.start,
+                    ..entry.clone()
+                });
+            }
+            assigned.push(TimelineEntry {
+                start: entry.start.max(correction.start),
+                end: entry.end.min(correction.end),
+                project: Some(project.clone()),
+                ..entry.clone()
+            });
+            if entry.end > correction.end {
+                assigned.push(TimelineEntry {
+                    start: correction.end,
+                    ..entry
+                });
+            }
+        }
+        timeline = assigned;
+    }
+    timeline
+}
+
+/// Add up the time of each project in a timeline, longest first
+pub fn get_project_usages(timeline: &[TimelineEntry]) -> Vec<ProjectUsage> {
+    add_up_labels(timeline, |entry| entry.project.iter().map(String::as_str).collect())
+        .into_iter()
+        .map(|(project, total_secs)| ProjectUsage { project, total_secs })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, title: Option<&str>, start_secs: u64, end_secs: u64) -> TimelineEntry {
+        TimelineEntry {
+            kind: EntryKind::App,
+            name: Some(name.to_string()),
+            path: Some(format!("/usr/bin/{}", name)),
+            title: title.map(String::from),
+            project: None,
+            category: None,
+            tags: Vec::new(),
+            start: start_secs * 1000,
+            end: end_secs * 1000,
+        }
+    }
+
+    fn project(name: &str, titles: &[&str], paths: &[&str], domains: &[&str]) -> ProjectConfig {
+        ProjectConfig {
+            name: name.to_string(),
+            titles: titles.iter().map(|title| title.to_string()).collect(),
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            domains: domains.iter().map(|domain| domain.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_matches_project() {
+        let client = project("clientA", &["Flows"], &["~/work/clientA"], &["clienta.com"]);
+        assert!(matches_project(&client, &entry("code", Some("main.rs — flows"), 0, 1)));
+        assert!(matches_project(&client, &entry("terminal", Some("~/work/clientA/api: bash"), 0, 1)));
+        assert!(!matches_project(&client, &entry("terminal", Some("~/work/clientAB: bash"), 0, 1)));
+        assert!(matches_project(&client, &entry("firefox", Some("docs.clienta.com/setup — Firefox"), 0, 1)));
+        assert!(!matches_project(&client, &entry("firefox", Some("notclienta.com — Firefox"), 0, 1)));
+        assert!(!matches_project(&client, &entry("firefox", None, 0, 1)));
+
+        let tools = project("tools", &[], &["/usr/bin"], &[]);
+        assert!(matches_project(&tools, &entry("code", None, 0, 1)));
+        assert!(!matches_project(&project("empty", &[""], &[""], &[""]), &entry("code", Some("main.rs"), 0, 1)));
+    }
+
+    #[test]
+    fn test_assign_projects() {
+        let projects = vec![project("flows", &["flows"], &[], &[]), project("other", &["main.rs"], &[], &[])];
+        let timeline = vec![
+            entry("code", Some("main.rs — flows"), 0, 600),
+            TimelineEntry {
+                kind: EntryKind::Idle,
+                name: None,
+                path: None,
+                title: None,
+                project: None,
+                category: None,
+                tags: Vec::new(),
+                start: 600_000,
+                end: 900_000,
+            },
+            entry("firefox", Some("Inbox"), 900, 1800),
+        ];
+        // The mail between 20 and 25 minutes was for the project
+        let corrections = vec![Correction {
+            id: 1,
+            start: 1_200_000,
+            end: 1_500_000,
+            change: Change::Project {
+                app: Some("Firefox".to_string()),
+                project: "flows".to_string(),
+            },
+        }];
+        let timeline = assign_projects(timeline, &projects, &corrections);
+        let projects: Vec<(Option<&str>, u64, u64)> = timeline
+            .iter()
+            .map(|entry| (entry.project.as_deref(), entry.start / 1000, entry.end / 1000))
+            .collect();
+        assert_eq!(
+            projects,
+            vec![
+                (Some("flows"), 0, 600),
+                (None, 600, 900),
+                (None, 900, 1200),
+                (Some("flows"), 1200, 1500),
+                (None, 1500, 1800),
+            ]
+        );
+        assert_eq!(
+            get_project_usages(&timeline),
+            vec![ProjectUsage {
+                project: "flows".to_string(),
+                total_secs: 900
+            }]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::{Duration as ChronoDuration, Local, NaiveDate};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use tracing::{error, info};
+
+use crate::config::{RetentionAction, RetentionConfig};
+use crate::crypto::seal_line;
+use crate::sys_monitor::get_raw_app_usages;
+use crate::clock::{Clock, SystemClock};
+use crate::utils::{get_log_file_dir_str, read_log_lines};
+
+/// Usage of one app on one compacted day
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppDaySummary {
+    pub name: String,
+    pub path: String,
+    pub total_secs: u64,
+    /// Seconds spent in each hour of the day, starting at midnight in the system timezone, like the day log
+    pub hourly_secs: Vec<u64>,
+}
+
+pub fn get_summary_dir_str() -> String {
+    format!("{}/summaries", get_log_file_dir_str())
+}
+
+pub fn get_archive_dir_str() -> String {
+    format!("{}/archive", get_log_file_dir_str())
+}
+
+/// Read the summary of a compacted day, if there is one
+///
+/// `date` is formatted as `%Y%m%d`, like the day log file names.
+pub fn read_day_summary(date: &str) -> io::Result<Option<Vec<AppDaySummary>>> {
+    read_day_summary_in(&get_log_file_dir_str(), date)
+}
+
+/// Read the summary of a compacted day from the `summaries` folder of a data directory
+pub fn read_day_summary_in(data_dir: &str, date: &str) -> io::Result<Option<Vec<AppDaySummary>>> {
+    let summary_path = format!("{}/summaries/{}.json", data_dir, date);
+    let content = match read_log_lines(summary_path) {
+        Ok(lines) => lines.concat(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Apply the retention policy to the day logs and the tracing logs
+pub fn run_retention(config: &RetentionConfig) {
+    if let Some(raw_days) = config.raw_days {
+        if let Err(e) = compact_raw_logs(raw_days, config.action) {
+            error!("Failed to compact day logs: {}", e);
+        }
+    }
+    if let Some(tracing_log_days) = config.tracing_log_days {
+        if let Err(e) = prune_tracing_logs(tracing_log_days) {
+            error!("Failed to prune tracing logs: {}", e);
+        }
+    }
+}
+
+/// Roll raw day logs older than `raw_days` up into summaries, then archive or delete them
+fn compact_raw_logs(raw_days: u32, action: RetentionAction) -> io::Result<()> {
+    let cutoff = Local::now().date_naive() - ChronoDuration::days(raw_days as i64);
+    let mut dates = Vec::new();
+    for entry in fs::read_dir(get_log_file_dir_str())? {
+        let file_name = entry?.file_name();
+        let Some(date) = file_name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".log"))
+            .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y%m%d").ok())
+        else {
+            continue;
+        };
+        if date < cutoff {
+            dates.push(date);
+        }
+    }
+    if dates.is_empty() {
+        return Ok(());
+    }
+
+    // Sessions cross midnight, so every summary is written before any raw log is removed
+    fs::create_dir_all(get_summary_dir_str())?;
+    for date in dates.iter() {
+        write_day_summary(*date)?;
+    }
+
+    if action == RetentionAction::Archive {
+        fs::create_dir_all(get_archive_dir_str())?;
+    }
+    for date in dates.iter() {
+        let stem = date.format("%Y%m%d").to_string();
+        let raw_path = format!("{}/{}.log", get_log_file_dir_str(), stem);
+        if action == RetentionAction::Archive {
+            let archive_path = format!("{}/{}.log.gz", get_archive_dir_str(), stem);
+            let mut encoder = GzEncoder::new(File::create(archive_path)?, Compression::default());
+            io::copy(&mut File::open(&raw_path)?, &mut encoder)?;
+            encoder.finish()?;
+        }
+        fs::remove_file(&raw_path)?;
+    }
+
+    info!("Compacted {} day logs older than {}", dates.len(), cutoff);
+    Ok(())
+}
+
+/// Sum up what the day log of a date holds: its day in the system timezone, which named the file,
+/// whatever the report timezone is
+fn write_day_summary(date: NaiveDate) -> io::Result<()> {
+    let day_start = SystemClock
+        .get_date_start_timestamp(date)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid day start"))?;
+    let next_day_start = SystemClock
+        .get_date_start_timestamp(date + ChronoDuration::days(1))
+        .unwrap_or(day_start + 86400000);
+
+    // Include the neighbouring days so sessions crossing midnight are closed
+    let app_usages = get_raw_app_usages(day_start - 86400000, next_day_start + 86400000)?;
+
+    let mut summary = Vec::new();
+    for app in app_usages {
+        let mut hourly_millis = [0u64; 24];
+        for &(start_time, end_time) in app.durations.iter() {
+            let mut time = start_time.max(day_start);
+            let end_time = end_time.min(next_day_start);
+            while time < end_time {
+                let hour = ((time - day_start) / 3600000) as usize;
+                let segment_end = end_time.min(day_start + (hour as u64 + 1) * 3600000);
+                // A 25-hour day folds its last hour into the 23rd
+                hourly_millis[hour.min(23)] += segment_end - time;
+                time = segment_end;
+            }
+        }
+
+        let hourly_secs: Vec<u64> = hourly_millis.iter().map(|millis| millis / 1000).collect();
+        let total_secs = hourly_secs.iter().sum();
+        if total_secs > 0 {
+            summary.push(AppDaySummary {
+                name: app.name,
+                path: app.path,
+                total_secs,
+                hourly_secs,
+            });
+        }
+    }
+
+    // Written aside and renamed, so a crash never leaves half a summary once the raw log is gone
+    let summary_path = format!("{}/{}.json", get_summary_dir_str(), date.format("%Y%m%d"));
+    let temp_path = format!("{}.tmp", summary_path);
+    let content = serde_json::to_string(&summary).map_err(io::Error::other)?;
+    let mut file = File::create(&temp_path)?;
+    file.write_all((seal_line(&content)? + "\n").as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, &summary_path)
+}
+
+/// Delete rolling tracing files like `app.log.YYYY-MM-DD` older than `tracing_log_days`
+fn prune_tracing_logs(tracing_log_days: u32) -> io::Result<()> {
+    let cutoff = Local::now().date_naive() - ChronoDuration::days(tracing_log_days as i64);
+    let logs_dir = format!("{}/logs", get_log_file_dir_str());
+    if !Path::new(&logs_dir).exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(&logs_dir)? {
+        let entry = entry?;
+        let is_expired = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.rsplit_once(".log."))
+            .and_then(|(_, date)| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            .is_some_and(|date| date < cutoff);
+        if is_expired {
+            fs::remove_file(entry.path())?;
+            info!("Removed tracing log {:?}", entry.file_name());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,623 @@
+use chrono::{NaiveTime, Timelike};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::error;
+
+use crate::clock::{get_report_clock, Clock};
+use crate::corrections::{Change, Correction};
+use crate::projects::{contains_domain, expand_home, get_project_usages, is_in_folder, ProjectUsage};
+use crate::timeline::{add_up_labels, get_timelines_with_rules, EntryKind, TimelineEntry};
+use crate::utils::get_log_file_dir_str;
+
+/// Times of day a rule applies, `HH:MM` in the report timezone, going past midnight if `to` is earlier than `from`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeOfDay {
+    pub from: String,
+    pub to: String,
+}
+
+/// When a rule applies to a part of the timeline. All conditions that are set must hold.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Conditions {
+    /// App name, ignoring case
+    pub app: Option<String>,
+    /// The app is this executable or in this folder, e.g. `/Applications/Xcode.app` or `~/bin`
+    pub path: Option<String>,
+    /// Bundle id of a macOS app, e.g. `com.microsoft.VSCode`
+    pub bundle_id: Option<String>,
+    /// Regular expression the window title must match, e.g. `(?i)pull request`
+    pub title: Option<String>,
+    /// A domain or one of its subdomains in the window title
+    pub domain: Option<String>,
+    pub time: Option<TimeOfDay>,
+}
+
+/// What a rule gives the time it applies to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Actions {
+    pub category: Option<String>,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// A rule of the rules file, `rules.json` in the data directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub conditions: Conditions,
+    pub actions: Actions,
+}
+
+/// A rule ready to be evaluated
+struct CompiledRule {
+    rule: Rule,
+    title: Option<Regex>,
+    /// Minutes after midnight it starts and ends
+    time: Option<(u64, u64)>,
+}
+
+/// Rules in the order they are evaluated
+#[derive(Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+    /// Bundle ids of the app paths seen so far
+    bundle_ids: Mutex<HashMap<String, Option<String>>>,
+}
+
+/// Time spent in a category
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryUsage {
+    pub category: String,
+    pub total_secs: u64,
+}
+
+/// Time with a tag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagUsage {
+    pub tag: String,
+    pub total_secs: u64,
+}
+
+/// Time a rule matched, whether or not earlier rules already set what it sets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleUsage {
+    pub rule: String,
+    pub total_secs: u64,
+}
+
+/// What reports would show with a set of rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DryRun {
+    pub rules: Vec<RuleUsage>,
+    pub categories: Vec<CategoryUsage>,
+    pub projects: Vec<ProjectUsage>,
+    pub tags: Vec<TagUsage>,
+    /// Time in apps without a category
+    pub uncategorized_secs: u64,
+}
+
+fn invalid_rule<E: ToString>(rule: &Rule, e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Rule {}: {}", rule.name, e.to_string()))
+}
+
+/// Minutes after midnight of an `HH:MM` time
+fn parse_time_of_day(value: &str) -> Result<u64, chrono::ParseError> {
+    let time = NaiveTime::parse_from_str(value, "%H:%M")?;
+    Ok((time.hour() * 60 + time.minute()) as u64)
+}
+
+fn is_in_time(minute: u64, (from, to): (u64, u64)) -> bool {
+    if from < to {
+        from <= minute && minute < to
+    } else {
+        minute >= from || minute < to
+    }
+}
+
+/// Minutes after the start of the day of a timestamp
+fn get_minute_of_day(timestamp: u64, clock: &dyn Clock) -> Option<u64> {
+    clock
+        .get_day_start_timestamp(timestamp)
+        .map(|start_timestamp| timestamp.saturating_sub(start_timestamp) / 60_000)
+}
+
+/// The bundle id in the `Info.plist` of the `.app` bundle a path is in, if it is in one
+pub fn get_bundle_id(path: &str) -> Option<String> {
+    let app_path = Path::new(path)
+        .ancestors()
+        .find(|ancestor| ancestor.extension().is_some_and(|ext| ext == "app"))?;
+    let info = plist::Value::from_file(app_path.join("Contents/Info.plist")).ok()?;
+    info.as_dictionary()?
+        .get("CFBundleIdentifier")?
+        .as_string()
+        .map(String::from)
+}
+
+impl RuleSet {
+    /// Check and compile rules, so a broken rule is found before it is used
+    pub fn new(rules: Vec<Rule>) -> io::Result<RuleSet> {
+        let mut compiled_rules = Vec::with_capacity(rules.len());
+        for rule in rules {
+            if rule.name.trim().is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "A rule needs a name"));
+            }
+            let actions = &rule.actions;
+            if actions.category.is_none() && actions.project.is_none() && actions.tags.is_empty() {
+                return Err(invalid_rule(&rule, "no category, project or tags to give"));
+            }
+            let title = match &rule.conditions.title {
+                Some(pattern) => Some(Regex::new(pattern).map_err(|e| invalid_rule(&rule, e))?),
+                None => None,
+            };
+            let time = match &rule.conditions.time {
+                Some(time) => {
+                    let from = parse_time_of_day(&time.from).map_err(|e| invalid_rule(&rule, e))?;
+                    let to = parse_time_of_day(&time.to).map_err(|e| invalid_rule(&rule, e))?;
+                    if from == to {
+                        return Err(invalid_rule(&rule, "the time starts and ends at once"));
+                    }
+                    Some((from, to))
+                }
+                None => None,
+            };
+            compiled_rules.push(CompiledRule { rule, title, time });
+        }
+        Ok(RuleSet {
+            rules: compiled_rules,
+            bundle_ids: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Load the rules of a rules file. A missing file has no rules.
+    pub fn load<P: AsRef<Path>>(file_path: P) -> io::Result<RuleSet> {
+        let content = match fs::read_to_string(file_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(RuleSet::default()),
+            Err(e) => return Err(e),
+        };
+        let rules: Vec<Rule> =
+            serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        RuleSet::new(rules)
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().map(|compiled| &compiled.rule)
+    }
+
+    fn get_bundle_id(&self, path: &str) -> Option<String> {
+        let mut bundle_ids = self.bundle_ids.lock().unwrap();
+        bundle_ids.entry(path.to_string()).or_insert_with(|| get_bundle_id(path)).clone()
+    }
+
+    fn matches(&self, compiled: &CompiledRule, entry: &TimelineEntry, clock: &dyn Clock) -> bool {
+        compiled.time.is_none_or(|time| {
+            get_minute_of_day(entry.start, clock).is_some_and(|minute| is_in_time(minute, time))
+        }) && self.matches_apart_from_time(compiled, entry)
+    }
+
+    fn matches_apart_from_time(&self, compiled: &CompiledRule, entry: &TimelineEntry) -> bool {
+        let conditions = &compiled.rule.conditions;
+        let name = entry.name.as_deref().unwrap_or("");
+        let path = entry.path.as_deref().unwrap_or("");
+        let title = entry.title.as_deref();
+
+        entry.kind == EntryKind::App
+            && conditions.app.as_ref().is_none_or(|app| app.eq_ignore_ascii_case(name))
+            && conditions.path.as_ref().is_none_or(|folder| is_in_folder(path, &expand_home(folder)))
+            && compiled.title.as_ref().is_none_or(|regex| title.is_some_and(|title| regex.is_match(title)))
+            && conditions
+                .domain
+                .as_ref()
+                .is_none_or(|domain| title.is_some_and(|title| contains_domain(title, domain)))
+            // Reads the app bundle, so it comes last
+            && conditions.bundle_id.as_ref().is_none_or(|bundle_id| {
+                self.get_bundle_id(path).is_some_and(|found| found.eq_ignore_ascii_case(bundle_id))
+            })
+    }
+
+    /// The rules that apply to a timeline entry, in order
+    pub fn get_matching_rules(&self, entry: &TimelineEntry, clock: &dyn Clock) -> Vec<&Rule> {
+        self.rules
+            .iter()
+            .filter(|compiled| self.matches(compiled, entry, clock))
+            .map(|compiled| &compiled.rule)
+            .collect()
+    }
+
+    /// Split an entry where the time of a rule that could apply to it starts or ends, so each part has the
+    /// same rules
+    fn split_at_rule_times(&self, entry: TimelineEntry, clock: &dyn Clock) -> Vec<TimelineEntry> {
+        let minutes: Vec<u64> = self
+            .rules
+            .iter()
+            .filter(|compiled| compiled.time.is_some() && self.matches_apart_from_time(compiled, &entry))
+            .filter_map(|compiled| compiled.time)
+            .flat_map(|(from, to)| [from, to])
+            .collect();
+        let mut cuts = Vec::new();
+        let mut timestamp = entry.start;
+        while !minutes.is_empty() && timestamp < entry.end {
+            let Some(day) = clock.get_day_range(timestamp) else {
+                break;
+            };
+            for minute in minutes.iter() {
+                let cut = day.start_timestamp + minute * 60_000;
+                if cut > entry.start && cut < entry.end {
+                    cuts.push(cut);
+                }
+            }
+            timestamp = day.end_timestamp + 1;
+        }
+        if cuts.is_empty() {
+            return vec![entry];
+        }
+        cuts.sort();
+        cuts.dedup();
+
+        let mut parts = Vec::with_capacity(cuts.len() + 1);
+        let mut start = entry.start;
+        for cut in cuts {
+            parts.push(TimelineEntry {
+                start,
+                end: cut,
+                ..entry.clone()
+            });
+            start = cut;
+        }
+        parts.push(TimelineEntry { start, ..entry });
+        parts
+    }
+}
+
+fn get_rules_path(data_dir: &str) -> String {
+    format!("{}/rules.json", data_dir)
+}
+
+/// Load the rules file of the data directory. Broken rules are logged and none are used.
+pub fn get_rules() -> RuleSet {
+    let rules_path = get_rules_path(&get_log_file_dir_str());
+    RuleSet::load(&rules_path).unwrap_or_else(|e| {
+        error!("Failed to load the rules in {}: {}", rules_path, e);
+        RuleSet::default()
+    })
+}
+
+/// Give the app entries of a timeline the category, project and tags of the rules that apply to them
+///
+/// The first rule that sets a category or project decides it, the tags of all of them add up. Entries
+/// are split where the time of a rule starts or ends, if that changes their labels. Time added by a correction with a category
+/// gets that instead.
+pub fn apply_rules(
+    timeline: Vec<TimelineEntry>,
+    rules: &RuleSet,
+    corrections: &[Correction],
+    clock: &dyn Clock,
+) -> Vec<TimelineEntry> {
+    let mut applied = Vec::with_capacity(timeline.len());
+    for entry in timeline {
+        if entry.kind != EntryKind::App {
+            applied.push(entry);
+            continue;
+        }
+        let mut parts: Vec<TimelineEntry> = Vec::new();
+        for mut part in rules.split_at_rule_times(entry, clock) {
+            for rule in rules.get_matching_rules(&part, clock) {
+                let actions = &rule.actions;
+                part.category = part.category.or_else(|| actions.category.clone());
+                part.project = part.project.or_else(|| actions.project.clone());
+                for tag in actions.tags.iter() {
+                    if !part.tags.contains(tag) {
+                        part.tags.push(tag.clone());
+                    }
+                }
+            }
+            let added_category = corrections.iter().rev().find_map(|correction| match &correction.change {
+                Change::Add {
+                    name,
+                    category: Some(category),
+                } if part.name.as_ref() == Some(name) && correction.start < part.end && correction.end > part.start => {
+                    Some(category)
+                }
+                _ => None,
+            });
+            if let Some(category) = added_category {
+                part.category = Some(category.clone());
+            }
+            // Join the parts of the entry that ended up with the same labels again
+            match parts.last_mut() {
+                Some(last) if is_same_labels(last, &part) => last.end = part.end,
+                _ => parts.push(part),
+            }
+        }
+        applied.extend(parts);
+    }
+    applied
+}
+
+/// Whether two parts of a timeline entry got the same labels
+fn is_same_labels(a: &TimelineEntry, b: &TimelineEntry) -> bool {
+    a.category == b.category
+        && a.project == b.project
+        && a.tags == b.tags
+}
+
+/// Add up the time of each category in a timeline, longest first
+pub fn get_category_usages(timeline: &[TimelineEntry]) -> Vec<CategoryUsage> {
+    add_up_labels(timeline, |entry| entry.category.iter().map(String::as_str).collect())
+        .into_iter()
+        .map(|(category, total_secs)| CategoryUsage { category, total_secs })
+        .collect()
+}
+
+/// Add up the time of each tag in a timeline, longest first
+pub fn get_tag_usages(timeline: &[TimelineEntry]) -> Vec<TagUsage> {
+    add_up_labels(timeline, |entry| entry.tags.iter().map(String::as_str).collect())
+        .into_iter()
+        .map(|(tag, total_secs)| TagUsage { tag, total_secs })
+        .collect()
+}
+
+/// Sum up what a labelled timeline shows with a set of rules
+fn summarize_rules(rules: &RuleSet, timeline: &[TimelineEntry], clock: &dyn Clock) -> DryRun {
+    let rule_usages = rules
+        .rules
+        .iter()
+        .map(|compiled| RuleUsage {
+            rule: compiled.rule.name.clone(),
+            // Parts with the same labels may have been joined across the time of a rule
+            total_secs: timeline
+                .iter()
+                .flat_map(|entry| rules.split_at_rule_times(entry.clone(), clock))
+                .filter(|entry| rules.matches(compiled, entry, clock))
+                .map(|entry| entry.end - entry.start)
+                .sum::<u64>()
+                / 1000,
+        })
+        .collect();
+    let uncategorized_millis: u64 = timeline
+        .iter()
+        .filter(|entry| entry.kind == EntryKind::App && entry.category.is_none())
+        .map(|entry| entry.end - entry.start)
+        .sum();
+    DryRun {
+        rules: rule_usages,
+        categories: get_category_usages(timeline),
+        projects: get_project_usages(timeline),
+        tags: get_tag_usages(timeline),
+        uncategorized_secs: uncategorized_millis / 1000,
+    }
+}
+
+/// Show what reports between start_timestamp and end_timestamp would show with a set of rules,
+/// e.g. before replacing the rules file with them. Nothing is written.
+pub fn dry_run_rules(rules: &RuleSet, start_timestamp: u64, end_timestamp: u64) -> io::Result<DryRun> {
+    let timeline = get_timelines_with_rules(start_timestamp, end_timestamp, rules)?.smoothed;
+    Ok(summarize_rules(rules, &timeline, get_report_clock().as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use chrono::Utc;
+
+    const CLOCK: FixedClock<Utc> = FixedClock { now: 0, timezone: Utc };
+
+    fn entry(name: &str, title: Option<&str>, start_secs: u64, end_secs: u64) -> TimelineEntry {
+        TimelineEntry {
+            kind: EntryKind::App,
+            name: Some(name.to_string()),
+            path: Some(format!("/usr/bin/{}", name)),
+            title: title.map(String::from),
+            project: None,
+            category: None,
+            tags: Vec::new(),
+            start: start_secs * 1000,
+            end: end_secs * 1000,
+        }
+    }
+
+    fn rule(name: &str, conditions: Conditions, category: Option<&str>, tags: &[&str]) -> Rule {
+        Rule {
+            name: name.to_string(),
+            conditions,
+            actions: Actions {
+                category: category.map(String::from),
+                project: None,
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            },
+        }
+    }
+
+    fn labels(timeline: &[TimelineEntry]) -> Vec<(Option<&str>, Vec<&str>, u64, u64)> {
+        timeline
+            .iter()
+            .map(|entry| {
+                (
+                    entry.category.as_deref(),
+                    entry.tags.iter().map(String::as_str).collect(),
+                    entry.start / 1000,
+                    entry.end / 1000,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let rules = RuleSet::new(vec![
+            rule(
+                "Code reviews",
+                Conditions {
+                    title: Some("(?i)pull request #\\d+".to_string()),
+                    ..Default::default()
+                },
+                Some("Review"),
+                &["work"],
+            ),
+            rule(
+                "Docs",
+                Conditions {
+                    app: Some("Firefox".to_string()),
+                    domain: Some("docs.rs".to_string()),
+                    ..Default::default()
+                },
+                Some("Development"),
+                &[],
+            ),
+            rule(
+                "Tools",
+                Conditions {
+                    path: Some("/usr/bin".to_string()),
+                    ..Default::default()
+                },
+                Some("Other"),
+                &[],
+            ),
+            // Work hours in UTC, 8:00 to 18:00
+            rule(
+                "Work hours",
+                Conditions {
+                    time: Some(TimeOfDay {
+                        from: "08:00".to_string(),
+                        to: "18:00".to_string(),
+                    }),
+                    ..Default::default()
+                },
+                None,
+                &["work"],
+            ),
+        ])
+        .unwrap();
+        let timeline = vec![
+            entry("firefox", Some("Fix parser by ada · Pull Request #12"), 7 * 3600, 9 * 3600),
+            entry("firefox", Some("regex - Rust - docs.rs"), 9 * 3600, 10 * 3600),
+            entry("firefox", Some("Not docs.rsx"), 17 * 3600, 19 * 3600),
+        ];
+        // The meeting was added by hand
+        let corrections = vec![Correction {
+            id: 1,
+            start: 19 * 3600 * 1000,
+            end: 20 * 3600 * 1000,
+            change: Change::Add {
+                name: "firefox".to_string(),
+                category: Some("Meetings".to_string()),
+            },
+        }];
+        let timeline = apply_rules(timeline, &rules, &corrections, &CLOCK);
+        assert_eq!(
+            labels(&timeline),
+            vec![
+                (Some("Review"), vec!["work"], 7 * 3600, 9 * 3600),
+                (Some("Development"), vec!["work"], 9 * 3600, 10 * 3600),
+                (Some("Other"), vec!["work"], 17 * 3600, 18 * 3600),
+                (Some("Other"), vec![], 18 * 3600, 19 * 3600),
+            ]
+        );
+        assert_eq!(
+            get_category_usages(&timeline),
+            vec![
+                CategoryUsage {
+                    category: "Other".to_string(),
+                    total_secs: 7200
+                },
+                CategoryUsage {
+                    category: "Review".to_string(),
+                    total_secs: 7200
+                },
+                CategoryUsage {
+                    category: "Development".to_string(),
+                    total_secs: 3600
+                },
+            ]
+        );
+
+        let added = apply_rules(vec![entry("firefox", None, 19 * 3600, 20 * 3600)], &rules, &corrections, &CLOCK);
+        assert_eq!(added[0].category.as_deref(), Some("Meetings"));
+
+        let summary = summarize_rules(&rules, &timeline, &CLOCK);
+        let rule_secs: Vec<u64> = summary.rules.iter().map(|usage| usage.total_secs).collect();
+        assert_eq!(rule_secs, vec![7200, 3600, 5 * 3600, 3 * 3600]);
+        assert_eq!(summary.uncategorized_secs, 0);
+    }
+
+    #[test]
+    fn test_time_past_midnight() {
+        let night = Conditions {
+            time: Some(TimeOfDay {
+                from: "22:00".to_string(),
+                to: "06:00".to_string(),
+            }),
+            ..Default::default()
+        };
+        let rules = RuleSet::new(vec![rule("Night", night, Some("Night"), &[])]).unwrap();
+        let timeline = apply_rules(vec![entry("code", None, 21 * 3600, 31 * 3600)], &rules, &[], &CLOCK);
+        assert_eq!(
+            labels(&timeline),
+            vec![
+                (None, vec![], 21 * 3600, 22 * 3600),
+                (Some("Night"), vec![], 22 * 3600, 30 * 3600),
+                (None, vec![], 30 * 3600, 31 * 3600),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let title = Conditions {
+            title: Some("(unclosed".to_string()),
+            ..Default::default()
+        };
+        assert!(RuleSet::new(vec![rule("Broken", title, Some("Other"), &[])]).is_err());
+        let time = Conditions {
+            time: Some(TimeOfDay {
+                from: "9:00".to_string(),
+                to: "25:00".to_string(),
+            }),
+            ..Default::default()
+        };
+        assert!(RuleSet::new(vec![rule("Broken", time, Some("Other"), &[])]).is_err());
+        assert!(RuleSet::new(vec![rule("Nothing", Conditions::default(), None, &[])]).is_err());
+        assert!(RuleSet::new(vec![rule("", Conditions::default(), Some("Other"), &[])]).is_err());
+    }
+
+    #[test]
+    fn test_bundle_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents_dir = dir.path().join("Code.app/Contents");
+        fs::create_dir_all(contents_dir.join("MacOS")).unwrap();
+        fs::write(
+            contents_dir.join("Info.plist"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleIdentifier</key>
+    <string>com.microsoft.VSCode</string>
+</dict>
+</plist>"#,
+        )
+        .unwrap();
+        let path = contents_dir.join("MacOS/Electron").to_string_lossy().to_string();
+        assert_eq!(get_bundle_id(&path).as_deref(), Some("com.microsoft.VSCode"));
+        assert_eq!(get_bundle_id("/usr/bin/code"), None);
+
+        let conditions = Conditions {
+            bundle_id: Some("com.microsoft.vscode".to_string()),
+            ..Default::default()
+        };
+        let rules = RuleSet::new(vec![rule("Editor", conditions, Some("Development"), &[])]).unwrap();
+        let mut code = entry("code", None, 0, 60);
+        code.path = Some(path);
+        assert_eq!(rules.get_matching_rules(&code, &CLOCK).len(), 1);
+        assert!(rules.get_matching_rules(&entry("code", None, 0, 60), &CLOCK).is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use serde_json::json;
+use std::io;
+use std::str::FromStr;
+
+use crate::clock::get_report_clock;
+use crate::daemon::DaemonStatus;
+use crate::ipc::{query_app_usages, send_request, Request};
+use crate::sys_monitor::AppUsage;
+
+/// The status bars `flows-cli status` can write for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarFormat {
+    /// One line of JSON for a Waybar `custom` module with `"return-type": "json"`
+    Waybar,
+    /// Full text, short text and color lines
+    I3blocks,
+    /// One line of text
+    Polybar,
+}
+
+impl FromStr for BarFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "waybar" => Ok(BarFormat::Waybar),
+            "i3blocks" => Ok(BarFormat::I3blocks),
+            "polybar" => Ok(BarFormat::Polybar),
+            _ => Err(format!("Unknown status bar format: {}", s)),
+        }
+    }
+}
+
+/// What the status bar shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarStatus {
+    /// `None` if the daemon isn't running
+    pub daemon: Option<DaemonStatus>,
+    /// Name of the app in front
+    pub current: Option<String>,
+    /// How long the app in front has been in front
+    pub session_secs: u64,
+    pub today_secs: u64,
+    /// The apps with the most time today, longest first
+    pub top_apps: Vec<(String, u64)>,
+}
+
+/// Combine the daemon status with today's app usages into what the bar shows
+fn build_bar_status(daemon: Option<DaemonStatus>, app_usages: &[AppUsage], now: u64) -> BarStatus {
+    let current = daemon.as_ref().and_then(|status| status.current.name.clone());
+    // The session of the app in front is still open: it ends at its last heartbeat, not now
+    let open_session = current
+        .as_ref()
+        .and_then(|name| app_usages.iter().find(|app| &app.name == name))
+        .and_then(|app| app.durations.last());
+    let session_secs = open_session
+        .map(|(start, _)| now.saturating_sub(*start) / 1000)
+        .unwrap_or(0);
+    let unlogged_secs = open_session
+        .map(|(_, end)| now.saturating_sub(*end) / 1000)
+        .unwrap_or(0);
+
+    let mut top_apps: Vec<(String, u64)> = app_usages
+        .iter()
+        .map(|app| {
+            let secs = if current.as_ref() == Some(&app.name) {
+                app.total_secs + unlogged_secs
+            } else {
+                app.total_secs
+            };
+            (app.name.clone(), secs)
+        })
+        .collect();
+    top_apps.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+    top_apps.truncate(5);
+
+    BarStatus {
+        daemon,
+        current,
+        session_secs,
+        today_secs: app_usages.iter().map(|app| app.total_secs).sum::<u64>() + unlogged_secs,
+        top_apps,
+    }
+}
+
+/// Get the status from the daemon and today's usages
+pub fn get_bar_status() -> io::Result<BarStatus> {
+    let daemon = match send_request(&Request::Status) {
+        Ok(data) => Some(serde_json::from_value(data)?),
+        Err(_) => None,
+    };
+    let (day_start, now) = get_report_clock().get_today_so_far();
+    Ok(build_bar_status(daemon, &query_app_usages(day_start, now)?, now))
+}
+
+/// Format a duration for the small space of a bar, e.g. `2h 05m` or `12m`
+fn format_short_secs(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    } else {
+        format!("{}m", secs / 60)
+    }
+}
+
+impl BarStatus {
+    /// `stopped`, `paused`, `idle` or `active`, also used as the Waybar class
+    pub fn state(&self) -> &'static str {
+        match &self.daemon {
+            None => "stopped",
+            Some(status) if status.paused => "paused",
+            Some(_) if self.current.is_none() => "idle",
+            Some(_) => "active",
+        }
+    }
+
+    fn short_text(&self) -> String {
+        format_short_secs(self.today_secs)
+    }
+
+    fn full_text(&self) -> String {
+        match (&self.current, self.state()) {
+            (Some(current), "active") => format!(
+                "{} {} · {}",
+                current,
+                format_short_secs(self.session_secs),
+                self.short_text()
+            ),
+            (_, "active") | (_, "idle") => self.short_text(),
+            (_, state) => format!("{} · {}", state, self.short_text()),
+        }
+    }
+
+    fn tooltip(&self) -> String {
+        let mut lines = vec![format!("Today: {}", format_short_secs(self.today_secs))];
+        lines.extend(
+            self.top_apps
+                .iter()
+                .map(|(name, secs)| format!("{}: {}", name, format_short_secs(*secs))),
+        );
+        lines.join("\n")
+    }
+
+    /// Format the status as the bar expects it, without a trailing line break
+    pub fn format(&self, format: BarFormat) -> String {
+        match format {
+            BarFormat::Waybar => json!({
+                "text": self.full_text(),
+                "alt": self.state(),
+                "tooltip": self.tooltip(),
+                "class": self.state(),
+            })
+            .to_string(),
+            BarFormat::I3blocks => {
+                let mut lines = vec![self.full_text(), self.short_text()];
+                if self.state() != "active" {
+                    lines.push("#888888".to_string());
+                }
+                lines.join("\n")
+            }
+            BarFormat::Polybar => self.full_text(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::CurrentApp;
+
+    fn app_usage(name: &str, total_secs: u64, durations: Vec<(u64, u64)>) -> AppUsage {
+        AppUsage {
+            name: name.to_string(),
+            path: format!("/usr/bin/{}", name),
+            total_secs,
+            durations,
+        }
+    }
+
+    #[test]
+    fn test_bar_status() {
+        let daemon = DaemonStatus {
+            pid: 1,
+            paused: false,
+            current: CurrentApp {
+                name: Some("code".to_string()),
+                path: Some("/usr/bin/code".to_string()),
+            },
+        };
+        let app_usages = [
+            app_usage("firefox", 3600, vec![(0, 3_600_000)]),
+            app_usage("code", 600, vec![(3_600_000, 4_200_000), (4_800_000, 4_800_000)]),
+        ];
+        let status = build_bar_status(Some(daemon.clone()), &app_usages, 5_100_000);
+        assert_eq!(status.session_secs, 300);
+        assert_eq!(status.today_secs, 4500);
+        assert_eq!(status.top_apps[1], ("code".to_string(), 900));
+
+        assert_eq!(status.format(BarFormat::Polybar), "code 5m · 1h 15m");
+        assert_eq!(status.format(BarFormat::I3blocks), "code 5m · 1h 15m\n1h 15m");
+        let waybar: serde_json::Value = serde_json::from_str(&status.format(BarFormat::Waybar)).unwrap();
+        assert_eq!(waybar["class"], "active");
+        assert_eq!(waybar["tooltip"], "Today: 1h 15m\nfirefox: 1h 00m\ncode: 15m");
+
+        let status = build_bar_status(None, &app_usages, 5_100_000);
+        assert_eq!(status.format(BarFormat::Polybar), "stopped · 1h 10m");
+        assert_eq!(status.format(BarFormat::I3blocks), "stopped · 1h 10m\n1h 10m\n#888888");
+
+        // Heartbeats already logged part of the open session
+        let app_usages = [app_usage("code", 240, vec![(4_800_000, 5_040_000)])];
+        let status = build_bar_status(Some(daemon), &app_usages, 5_100_000);
+        assert_eq!(status.session_secs, 300);
+        assert_eq!(status.today_secs, 300);
+    }
+}
@@ -0,0 +1,645 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+use crate::clock::get_report_clock;
+use crate::config::SyncConfig;
+use crate::crypto::open_line;
+use crate::sys_monitor::{get_app_usages_from_log, AppUsage, Event, LogStore};
+use crate::utils::{get_log_file_dir_str, rewrite_log_file};
+
+/// Held while syncing, so the timer and a `sync` request never sync at the same time
+static SYNC_LOCK: Mutex<()> = Mutex::new(());
+
+/// A device whose events are synced, stored as `device.json` next to its day logs on the remote
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+}
+
+/// What a sync transferred
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncSummary {
+    /// Day logs of this device written to the remote
+    pub uploaded: usize,
+    /// Day logs of other devices merged into their local copies
+    pub downloaded: usize,
+}
+
+/// A file or folder on a remote
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub is_dir: bool,
+    /// Changes whenever the content changes, e.g. an ETag
+    pub version: String,
+}
+
+/// Where devices share their events: one folder per device id, holding its day logs
+pub trait Remote {
+    /// Entries of a folder, `""` being the root. A missing folder has none.
+    fn list(&self, dir: &str) -> io::Result<Vec<RemoteEntry>>;
+    /// Content of a file, `None` if it doesn't exist
+    fn read(&self, path: &str) -> io::Result<Option<String>>;
+    /// Write a file, creating its folder if needed
+    fn write(&self, path: &str, content: &str) -> io::Result<()>;
+}
+
+/// A local folder shared by other means, e.g. Syncthing, Dropbox or a network drive
+pub struct FolderRemote {
+    root: PathBuf,
+}
+
+impl FolderRemote {
+    pub fn new<P: Into<PathBuf>>(root: P) -> FolderRemote {
+        FolderRemote { root: root.into() }
+    }
+}
+
+fn file_version(metadata: &fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("{}-{}", metadata.len(), modified.as_nanos())
+}
+
+impl Remote for FolderRemote {
+    fn list(&self, dir: &str) -> io::Result<Vec<RemoteEntry>> {
+        let entries = match fs::read_dir(self.root.join(dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut remote_entries = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            remote_entries.push(RemoteEntry {
+                name,
+                is_dir: metadata.is_dir(),
+                version: file_version(&metadata),
+            });
+        }
+        Ok(remote_entries)
+    }
+
+    fn read(&self, path: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.root.join(path)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, path: &str, content: &str) -> io::Result<()> {
+        let file_path = self.root.join(path);
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Other devices may read the folder at any time, so never leave a half written file
+        let temp_path = file_path.with_extension("tmp");
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, &file_path)
+    }
+}
+
+/// A folder on a WebDAV server, e.g. Nextcloud
+pub struct WebDavRemote {
+    url: String,
+    authorization: Option<String>,
+}
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getetag/><d:getlastmodified/><d:getcontentlength/></d:prop></d:propfind>"#;
+
+fn webdav_error(e: ureq::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+impl WebDavRemote {
+    pub fn new(url: &str, username: Option<&str>, password: Option<&str>) -> WebDavRemote {
+        let authorization = username.map(|username| {
+            let credentials = format!("{}:{}", username, password.unwrap_or(""));
+            format!("Basic {}", STANDARD.encode(credentials))
+        });
+        WebDavRemote {
+            url: url.trim_end_matches('/').to_string(),
+            authorization,
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = ureq::request(method, url).timeout(Duration::from_secs(30));
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+}
+
+impl Remote for WebDavRemote {
+    fn list(&self, dir: &str) -> io::Result<Vec<RemoteEntry>> {
+        let url = if dir.is_empty() {
+            format!("{}/", self.url)
+        } else {
+            format!("{}/{}/", self.url, dir)
+        };
+        let result = self
+            .request("PROPFIND", &url)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml")
+            .send_string(PROPFIND_BODY);
+        match result {
+            Ok(response) => Ok(parse_propfind(&response.into_string()?, get_url_path(&url))),
+            Err(ureq::Error::Status(404, _)) => Ok(Vec::new()),
+            Err(e) => Err(webdav_error(e)),
+        }
+    }
+
+    fn read(&self, path: &str) -> io::Result<Option<String>> {
+        match self.request("GET", &format!("{}/{}", self.url, path)).call() {
+            Ok(response) => Ok(Some(response.into_string()?)),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(webdav_error(e)),
+        }
+    }
+
+    fn write(&self, path: &str, content: &str) -> io::Result<()> {
+        if let Some((dir, _)) = path.rsplit_once('/') {
+            match self.request("MKCOL", &format!("{}/{}/", self.url, dir)).call() {
+                // 405 means the folder already exists
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(e) => return Err(webdav_error(e)),
+            }
+        }
+        self.request("PUT", &format!("{}/{}", self.url, path))
+            .send_string(content)
+            .map(|_| ())
+            .map_err(webdav_error)
+    }
+}
+
+/// The path of a URL, e.g. `/dav/flows/` of `https://example.com/dav/flows/`
+fn get_url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|index| &rest[index..]).unwrap_or("/"),
+        None => url,
+    }
+}
+
+/// Contents of the elements with a local name, whatever their namespace prefix
+fn find_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        if tag_name.rsplit(':').next() != Some(name) {
+            continue;
+        }
+        if tag.ends_with('/') {
+            elements.push("");
+            continue;
+        }
+        let content = &rest[tag_end + 1..];
+        let close_tag = format!("</{}>", tag_name);
+        let Some(content_end) = content.find(&close_tag) else {
+            break;
+        };
+        elements.push(&content[..content_end]);
+        rest = &content[content_end + close_tag.len()..];
+    }
+    elements
+}
+
+/// Get the entries of a PROPFIND multistatus response, without the listed folder itself
+fn parse_propfind(xml: &str, dir_path: &str) -> Vec<RemoteEntry> {
+    let first = |response: &str, name: &str| find_elements(response, name).first().map(|value| value.trim().to_string());
+    let mut entries = Vec::new();
+    for response in find_elements(xml, "response") {
+        let Some(href) = first(response, "href") else {
+            continue;
+        };
+        let path = get_url_path(&href).trim_end_matches('/').to_string();
+        if path.is_empty() || path == dir_path.trim_end_matches('/') {
+            continue;
+        }
+        let Some(name) = path.rsplit('/').next() else {
+            continue;
+        };
+        let is_dir = find_elements(response, "resourcetype")
+            .first()
+            .is_some_and(|resource_type| !find_elements(resource_type, "collection").is_empty());
+        // Not every server sends ETags
+        let version = first(response, "getetag").unwrap_or_else(|| {
+            format!(
+                "{}-{}",
+                first(response, "getcontentlength").unwrap_or_default(),
+                first(response, "getlastmodified").unwrap_or_default()
+            )
+        });
+        entries.push(RemoteEntry {
+            name: name.to_string(),
+            is_dir,
+            version,
+        });
+    }
+    entries
+}
+
+/// Where the copies of other devices' day logs are kept, one folder per device id
+pub fn get_devices_dir_str() -> String {
+    format!("{}/devices", get_log_file_dir_str())
+}
+
+fn get_device_id_file_path() -> String {
+    format!("{}/device-id", get_log_file_dir_str())
+}
+
+/// Get the id of this device, generating one on first use
+pub fn get_device_id() -> io::Result<String> {
+    let id_file = get_device_id_file_path();
+    match fs::read_to_string(&id_file) {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    let id: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    fs::create_dir_all(get_log_file_dir_str())?;
+    fs::write(&id_file, format!("{}\n", id))?;
+    info!("Generated device id {}", id);
+    Ok(id)
+}
+
+fn get_host_name() -> String {
+    Command::new("hostname")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(String::from("Unknown"))
+}
+
+pub fn get_local_device(config: &SyncConfig) -> io::Result<DeviceInfo> {
+    Ok(DeviceInfo {
+        id: get_device_id()?,
+        name: config.device_name.clone().unwrap_or_else(get_host_name),
+    })
+}
+
+/// The other devices whose day logs have been synced
+pub fn get_other_devices() -> io::Result<Vec<DeviceInfo>> {
+    let entries = match fs::read_dir(get_devices_dir_str()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut devices = Vec::new();
+    for entry in entries {
+        let Ok(id) = entry?.file_name().into_string() else {
+            continue;
+        };
+        let info_path = format!("{}/{}/device.json", get_devices_dir_str(), id);
+        let name = fs::read_to_string(info_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<DeviceInfo>(&content).ok())
+            .map(|info| info.name)
+            .unwrap_or_else(|| id.clone());
+        devices.push(DeviceInfo { id, name });
+    }
+    devices.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    Ok(devices)
+}
+
+/// What has been transferred already, stored as `sync-state.json` in the data directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SyncState {
+    /// Version of each local day log when it was last uploaded
+    uploaded: BTreeMap<String, String>,
+    /// Remote version of each file of other devices when it was last downloaded, by remote path
+    downloaded: BTreeMap<String, String>,
+}
+
+fn get_sync_state_file_path() -> String {
+    format!("{}/sync-state.json", get_log_file_dir_str())
+}
+
+impl SyncState {
+    fn load() -> SyncState {
+        fs::read_to_string(get_sync_state_file_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        // A sync interrupted while saving must not forget what was transferred before
+        let file_path = get_sync_state_file_path();
+        let temp_path = format!("{}.tmp", file_path);
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, &file_path)
+    }
+}
+
+/// The union of the stored lines of two copies of a day log, ordered by time
+///
+/// Lines are compared decrypted, so an event sealed twice is only kept once. The order only
+/// depends on the events, so all devices end up with the same log whatever order they merge in.
+/// Lines that can't be decrypted fail the merge.
+pub fn merge_event_lines(lines: &[String], other_lines: &[String]) -> io::Result<Vec<String>> {
+    let mut merged: BTreeMap<(u64, u8, String), String> = BTreeMap::new();
+    for line in lines.iter().chain(other_lines) {
+        if line.is_empty() {
+            continue;
+        }
+        let plain_line = open_line(line)?;
+        // Malformed lines are kept, before all events
+        let key = match Event::parse(&plain_line) {
+            Some(event) => (event.timestamp, event.event_type.get_merge_rank(), plain_line),
+            None => (0, 0, plain_line),
+        };
+        merged.entry(key).or_insert_with(|| line.clone());
+    }
+    Ok(merged.into_values().collect())
+}
+
+/// Merge other lines into the stored lines of a day log. Returns whether that added any.
+fn merge_into(lines: &mut Vec<String>, other_lines: &[String]) -> io::Result<bool> {
+    let known: HashSet<String> = lines.iter().map(|line| open_line(line)).collect::<io::Result<_>>()?;
+    let mut is_new = false;
+    for line in other_lines.iter().filter(|line| !line.is_empty()) {
+        if !known.contains(&open_line(line)?) {
+            is_new = true;
+            break;
+        }
+    }
+    if is_new {
+        *lines = merge_event_lines(lines, other_lines)?;
+    }
+    Ok(is_new)
+}
+
+fn is_day_log_name(name: &str) -> bool {
+    name.strip_suffix(".log")
+        .is_some_and(|stem| NaiveDate::parse_from_str(stem, "%Y%m%d").is_ok())
+}
+
+fn get_day_log_names(dir: &str) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        if let Ok(name) = entry?.file_name().into_string() {
+            if is_day_log_name(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn split_lines(content: &str) -> Vec<String> {
+    content.lines().map(String::from).collect()
+}
+
+/// Upload the day logs of this device that changed, and merge in the ones of other devices that changed
+pub fn sync_with(remote: &dyn Remote, device: &DeviceInfo) -> io::Result<SyncSummary> {
+    let _lock = SYNC_LOCK.lock().unwrap();
+    let mut state = SyncState::load();
+    let mut summary = SyncSummary::default();
+
+    let info_path = format!("{}/device.json", device.id);
+    let info = serde_json::to_string(device)?;
+    if remote.read(&info_path)?.as_deref() != Some(info.as_str()) {
+        remote.write(&info_path, &info)?;
+    }
+
+    let log_dir = get_log_file_dir_str();
+    fs::create_dir_all(&log_dir)?;
+    for name in get_day_log_names(&log_dir)? {
+        let local_path = format!("{}/{}", log_dir, name);
+        if state.uploaded.get(&name) == Some(&file_version(&fs::metadata(&local_path)?)) {
+            continue;
+        }
+        // Keep what only the remote copy has, e.g. after restoring an older backup of the data directory
+        let remote_path = format!("{}/{}", device.id, name);
+        if let Some(content) = remote.read(&remote_path)? {
+            rewrite_log_file(&local_path, |lines| merge_into(lines, &split_lines(&content)))?;
+        }
+        // Taken before reading, so events written meanwhile are uploaded next time
+        let version = file_version(&fs::metadata(&local_path)?);
+        remote.write(&remote_path, &fs::read_to_string(&local_path)?)?;
+        state.uploaded.insert(name, version);
+        summary.uploaded += 1;
+    }
+
+    for device_entry in remote.list("")? {
+        if !device_entry.is_dir || device_entry.name == device.id {
+            continue;
+        }
+        let device_dir = format!("{}/{}", get_devices_dir_str(), device_entry.name);
+        for entry in remote.list(&device_entry.name)? {
+            let remote_path = format!("{}/{}", device_entry.name, entry.name);
+            let is_synced_file = entry.name == "device.json" || is_day_log_name(&entry.name);
+            if entry.is_dir || !is_synced_file || state.downloaded.get(&remote_path) == Some(&entry.version) {
+                continue;
+            }
+            let Some(content) = remote.read(&remote_path)? else {
+                continue;
+            };
+            fs::create_dir_all(&device_dir)?;
+            let local_path = format!("{}/{}", device_dir, entry.name);
+            if entry.name == "device.json" {
+                fs::write(&local_path, content)?;
+            } else {
+                if let Err(e) = rewrite_log_file(&local_path, |lines| merge_into(lines, &split_lines(&content))) {
+                    // e.g. encrypted with another key
+                    warn!("Skipping {}: {}", remote_path, e);
+                    continue;
+                }
+                summary.downloaded += 1;
+            }
+            state.downloaded.insert(remote_path, entry.version);
+        }
+    }
+
+    state.save()?;
+    Ok(summary)
+}
+
+/// The remote from the config: a WebDAV server for `http(s)://` URLs, a local folder otherwise
+pub fn get_remote(config: &SyncConfig) -> io::Result<Box<dyn Remote>> {
+    let Some(remote) = &config.remote else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "No sync remote in the config"));
+    };
+    if remote.starts_with("http://") || remote.starts_with("https://") {
+        Ok(Box::new(WebDavRemote::new(
+            remote,
+            config.username.as_deref(),
+            config.password.as_deref(),
+        )))
+    } else {
+        Ok(Box::new(FolderRemote::new(remote)))
+    }
+}
+
+pub fn run_sync(config: &SyncConfig) -> io::Result<SyncSummary> {
+    let summary = sync_with(get_remote(config)?.as_ref(), &get_local_device(config)?)?;
+    info!("Synced: uploaded {}, downloaded {}", summary.uploaded, summary.downloaded);
+    Ok(summary)
+}
+
+/// Sync every `interval_secs` if a remote is configured. Starts a thread and returns.
+pub fn start_sync(config: &SyncConfig) {
+    if config.remote.is_none() {
+        return;
+    }
+    let config = config.clone();
+    thread::spawn(move || loop {
+        if let Err(e) = run_sync(&config) {
+            error!("Failed to sync: {}", e);
+        }
+        thread::sleep(Duration::from_secs(config.interval_secs.max(10)));
+    });
+}
+
+/// App usages of this device and of every synced device, this device first
+pub fn get_device_app_usages(
+    config: &SyncConfig,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> io::Result<Vec<(DeviceInfo, Vec<AppUsage>)>> {
+    let mut device_app_usages = vec![(
+        get_local_device(config)?,
+        get_app_usages_from_log(start_timestamp, end_timestamp)?,
+    )];
+    let clock = get_report_clock();
+    for device in get_other_devices()? {
+        let device_dir = format!("{}/{}", get_devices_dir_str(), device.id);
+        let mut app_usages = LogStore::new(device_dir, clock.as_ref()).get_raw_app_usages(start_timestamp, end_timestamp)?;
+        app_usages.sort_by_key(|app| std::cmp::Reverse(app.total_secs));
+        device_app_usages.push((device, app_usages));
+    }
+    Ok(device_app_usages)
+}
+
+/// Add up the usages of several devices by app name, longest first
+pub fn combine_app_usages<I: IntoIterator<Item = Vec<AppUsage>>>(device_app_usages: I) -> Vec<AppUsage> {
+    let mut combined: Vec<AppUsage> = Vec::new();
+    for app in device_app_usages.into_iter().flatten() {
+        match combined.iter_mut().find(|combined_app| combined_app.name == app.name) {
+            Some(combined_app) => {
+                combined_app.total_secs += app.total_secs;
+                combined_app.durations.extend(app.durations);
+            }
+            None => combined.push(app),
+        }
+    }
+    for app in combined.iter_mut() {
+        app.durations.sort();
+    }
+    combined.sort_by_key(|app| std::cmp::Reverse(app.total_secs));
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_merge_event_lines() {
+        let a = lines(&["0,1000,/usr/bin/code", "4,3000", "0,5000,/usr/bin/firefox"]);
+        let b = lines(&["0,1000,/usr/bin/code", "0,3000,/usr/bin/vim", "1,3000", "garbage"]);
+
+        let merged = merge_event_lines(&a, &b).unwrap();
+        assert_eq!(
+            merged,
+            lines(&[
+                "garbage",
+                "0,1000,/usr/bin/code",
+                "1,3000",
+                "4,3000",
+                "0,3000,/usr/bin/vim",
+                "0,5000,/usr/bin/firefox",
+            ])
+        );
+        // The same whatever the order, and merging again changes nothing
+        assert_eq!(merge_event_lines(&b, &a).unwrap(), merged);
+        assert_eq!(merge_event_lines(&merged, &a).unwrap(), merged);
+
+        let mut local = a.clone();
+        assert!(!merge_into(&mut local, &a[..1]).unwrap());
+        assert_eq!(local, a);
+        assert!(merge_into(&mut local, &b).unwrap());
+        assert_eq!(local, merged);
+    }
+
+    #[test]
+    fn test_parse_propfind() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/dav/flows/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>https://example.com/dav/flows/0a1b2c3d/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype><d:getetag>"dir"</d:getetag></d:prop></d:propstat>
+  </d:response>
+  <D:response xmlns:D="DAV:">
+    <D:href>/dav/flows/20261001.log</D:href>
+    <D:propstat><D:prop>
+      <D:resourcetype/>
+      <D:getcontentlength>42</D:getcontentlength>
+      <D:getlastmodified>Thu, 01 Oct 2026 10:00:00 GMT</D:getlastmodified>
+    </D:prop></D:propstat>
+  </D:response>
+</d:multistatus>"#;
+        assert_eq!(
+            parse_propfind(xml, "/dav/flows/"),
+            [
+                RemoteEntry {
+                    name: "0a1b2c3d".to_string(),
+                    is_dir: true,
+                    version: "\"dir\"".to_string(),
+                },
+                RemoteEntry {
+                    name: "20261001.log".to_string(),
+                    is_dir: false,
+                    version: "42-Thu, 01 Oct 2026 10:00:00 GMT".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -1,23 +1,44 @@
-use chrono::{Local, TimeZone};
-use chrono::LocalResult::Single;
+use chrono::NaiveDate;
+#[cfg(target_os = "macos")]
+use core_foundation::base::TCFType;
+#[cfg(target_os = "macos")]
 use core_foundation::{dictionary::*, number::*, string::*};
+#[cfg(target_os = "macos")]
 use core_graphics::display::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "macos")]
 use std::ffi::{c_void, CString};
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::io;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(target_os = "macos")]
 use std::ptr;
 use tracing::debug;
 
-use crate::utils::{get_day_start_timestamp, get_log_file_dir_str};
+use crate::clock::{get_report_clock, Clock, SystemClock};
+use crate::config::MonitorConfig;
+use crate::corrections::{apply_corrections, load_corrections};
+use crate::retention::{read_day_summary_in, AppDaySummary};
+use crate::timeline::{get_smoothing_rules, smooth_app_usages};
+use crate::utils::{get_log_file_dir_str, read_log_lines};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[allow(dead_code)]
 pub enum EventType {
     CameToFront,
     ShutDown,
     StopMonitoring,
+    /// A label set by the user. The label is stored in place of the path.
+    Tag,
+    /// No keyboard or mouse input since this time. Ends the current session like `StopMonitoring`.
+    Idle,
+    /// The app in front is still in front
+    Heartbeat,
+    /// The IANA timezone the following events were recorded in, stored in place of the path
+    Zone,
+    /// The title of the window in front, stored in place of the path. Empty for a window without one.
+    Title,
 }
 
 impl EventType {
@@ -26,11 +47,67 @@ impl EventType {
             EventType::CameToFront => 0,
             EventType::ShutDown => 1,
             EventType::StopMonitoring => 2,
+            EventType::Tag => 3,
+            EventType::Idle => 4,
+            EventType::Heartbeat => 5,
+            EventType::Zone => 6,
+            EventType::Title => 7,
+        }
+    }
+
+    pub fn from_int(value: i32) -> Option<EventType> {
+        match value {
+            0 => Some(EventType::CameToFront),
+            1 => Some(EventType::ShutDown),
+            2 => Some(EventType::StopMonitoring),
+            3 => Some(EventType::Tag),
+            4 => Some(EventType::Idle),
+            5 => Some(EventType::Heartbeat),
+            6 => Some(EventType::Zone),
+            7 => Some(EventType::Title),
+            _ => None,
+        }
+    }
+
+    /// Orders events with the same timestamp when merging day logs: sessions end before the next one starts
+    pub(crate) fn get_merge_rank(&self) -> u8 {
+        match self {
+            EventType::ShutDown | EventType::StopMonitoring | EventType::Idle => 0,
+            EventType::Tag | EventType::Heartbeat | EventType::Zone => 1,
+            EventType::CameToFront => 2,
+            // The title of the app that came to front at the same time
+            EventType::Title => 3,
         }
     }
 }
 
-#[derive(Serialize)]
+/// A single line of a day log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub event_type: EventType,
+    pub timestamp: u64,
+    pub path: String,
+}
+
+impl Event {
+    /// Parse a decrypted day log line. Malformed lines return `None`.
+    pub fn parse(line: &str) -> Option<Event> {
+        let mut parts = line.splitn(3, ',');
+        let event_type = EventType::from_int(parts.next()?.parse().ok()?)?;
+        let timestamp = parts.next()?.parse().ok()?;
+        let path = parts.next().unwrap_or("").to_string();
+        if matches!(event_type, EventType::CameToFront | EventType::Tag | EventType::Zone) && path.is_empty() {
+            return None;
+        }
+        Some(Event {
+            event_type,
+            timestamp,
+            path,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct AppUsage {
     pub name: String,
     pub path: String,
@@ -39,13 +116,13 @@ pub struct AppUsage {
 }
 
 /// Get app name from path by querying a map or parsing the path
-fn get_app_name_from_path(path: &str) -> Option<String> {
+pub fn get_app_name_from_path(path: &str) -> Option<String> {
     // TODO: query the app name from a map
 
     let path = Path::new(path);
     let components: Vec<&str> = path.iter().filter_map(|os_str| os_str.to_str()).collect();
 
-    #[cfg(target_os = "macos")]
+    // Not only on macOS, as paths recorded on a Mac may be read elsewhere, e.g. after syncing
     {
         // macOS
         // Attention: components[0] is "/".
@@ -78,115 +155,337 @@ fn get_app_name_from_path(path: &str) -> Option<String> {
         .map(|s| s.replace(".exe", ""))
 }
 
-/// Get all app usages from local log file
-///
-/// Each app usage contains the app name, path, total time in seconds, and durations
-pub fn get_app_usages_from_log(
-    start_timestamp: u64,
-    end_timestamp: u64,
-) -> io::Result<Vec<AppUsage>> {
-    let mut current_app_name: Option<String> = None;
-    let mut app_usages: Vec<AppUsage> = Vec::new();
+/// The day logs and summaries in a data directory, with days in the timezone of a clock
+pub struct LogStore<'a> {
+    pub data_dir: String,
+    pub clock: &'a dyn Clock,
+    /// The clock whose days the day logs and their summaries hold, the system clock that named them
+    pub log_clock: &'a dyn Clock,
+    /// How long after the last heartbeat or app switch a session still counts, see [`aggregate_app_usages`]
+    pub session_gap_millis: u64,
+}
 
-    // Find all log files between start_time and end_time
-    let mut temp_timestamp = start_timestamp;
-    let mut log_files = Vec::new();
-    while temp_timestamp <= get_day_start_timestamp(end_timestamp).unwrap_or(end_timestamp) + 86399000 {
-        match Local.timestamp_millis_opt(temp_timestamp as i64) {
-            Single(date) => {
-                let log_file_name = format!("{}/{}.log", get_log_file_dir_str(), date.format("%Y%m%d"));
-                log_files.push(log_file_name);
-            }
-            _ => continue
+impl<'a> LogStore<'a> {
+    pub fn new<S: Into<String>>(data_dir: S, clock: &'a dyn Clock) -> LogStore<'a> {
+        LogStore {
+            data_dir: data_dir.into(),
+            clock,
+            log_clock: &SystemClock,
+            session_gap_millis: SESSION_GAP_MILLIS.load(Ordering::SeqCst),
         }
-        
-        temp_timestamp += 86400000;
     }
 
-    debug!("Query log: StartTimestamp: {}, EndTimestamp: {}\nLogFiles: {:?}", start_timestamp, end_timestamp, log_files);
+    /// Use another session gap than the one of the config
+    pub fn with_session_gap(mut self, session_gap_millis: u64) -> LogStore<'a> {
+        self.session_gap_millis = session_gap_millis;
+        self
+    }
 
-    for log_file_name in log_files {
-        let file = File::open(log_file_name)?;
-        let reader = io::BufReader::new(file);
+    /// Read day logs that were recorded with another system clock, e.g. fixtures
+    pub fn with_log_clock(mut self, log_clock: &'a dyn Clock) -> LogStore<'a> {
+        self.log_clock = log_clock;
+        self
+    }
 
-        // Read each line of the log file, and get all durations for each app
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split(',').collect();
+    fn get_log_file_path(&self, date: NaiveDate) -> String {
+        format!("{}/{}.log", self.data_dir, date.format("%Y%m%d"))
+    }
 
-            let event_type: &str = parts[0];
-            let timestamp: u64 = match parts[1].parse() {
-                Ok(t) => t,
-                Err(_) => continue,
+    /// Get the dates and day start timestamps of all days between start_timestamp and end_timestamp
+    fn get_log_days(&self, start_timestamp: u64, end_timestamp: u64) -> Vec<(NaiveDate, u64)> {
+        let (Some(mut date), Some(end_date)) = (self.clock.get_date(start_timestamp), self.clock.get_date(end_timestamp))
+        else {
+            return Vec::new();
+        };
+        // Step by date, as days with DST changes are shorter or longer than 24 hours
+        let mut log_days = Vec::new();
+        while date <= end_date {
+            if let Some(day_start) = self.clock.get_date_start_timestamp(date) {
+                log_days.push((date, day_start));
+            }
+            match date.succ_opt() {
+                Some(next_date) => date = next_date,
+                None => break,
+            }
+        }
+        log_days
+    }
+
+    /// Get the dates of the day logs that may have events between start_timestamp and end_timestamp
+    ///
+    /// Day logs are named by the date where the events were recorded, which may be the day before or
+    /// after in the timezone of the clock, so the neighbouring days are included.
+    fn get_log_dates(&self, start_timestamp: u64, end_timestamp: u64) -> Vec<NaiveDate> {
+        let log_days = self.get_log_days(start_timestamp, end_timestamp);
+        let mut dates: Vec<NaiveDate> = log_days.first().and_then(|(date, _)| date.pred_opt()).into_iter().collect();
+        dates.extend(log_days.iter().map(|(date, _)| *date));
+        dates.extend(log_days.last().and_then(|(date, _)| date.succ_opt()));
+        dates
+    }
+
+    /// Read the events of the day logs of some dates between start_timestamp and end_timestamp, ordered by time
+    fn read_events(&self, dates: &[NaiveDate], start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        for &date in dates {
+            // Days without a raw log have either not been recorded or been compacted
+            let lines = match read_log_lines(self.get_log_file_path(date)) {
+                Ok(lines) => lines,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
             };
 
-            // Skip events before start_time or after end_time
-            if timestamp < start_timestamp || timestamp > end_timestamp {
+            for line in lines {
+                // Skip malformed lines, and events before start_time or after end_time
+                match Event::parse(&line) {
+                    Some(event) if event.timestamp >= start_timestamp && event.timestamp <= end_timestamp => {
+                        events.push(event)
+                    }
+                    _ => continue,
+                }
+            }
+        }
+        // After travelling west, a day log gets events from after the next day's log started
+        events.sort_by_key(|event| event.timestamp);
+        Ok(events)
+    }
+
+    /// Get all raw events between start_timestamp and end_timestamp, in the order they were written
+    pub fn get_events(&self, start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<Event>> {
+        let dates = self.get_log_dates(start_timestamp, end_timestamp);
+        debug!("Query log: StartTimestamp: {}, EndTimestamp: {}\nDates: {:?}", start_timestamp, end_timestamp, dates);
+        self.read_events(&dates, start_timestamp, end_timestamp)
+    }
+
+    /// Get all app usages between start_timestamp and end_timestamp from the raw day logs only, as recorded
+    /// and ignoring compacted days. The result is not sorted.
+    ///
+    /// Durations are clipped to the range. Sessions crossing its start or end count with the part inside.
+    pub fn get_raw_app_usages(&self, start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<AppUsage>> {
+        let events = self.read_events(&self.get_log_dates(start_timestamp, end_timestamp), 0, u64::MAX)?;
+        Ok(clip_app_usages(aggregate_app_usages(events, self.session_gap_millis), start_timestamp, end_timestamp))
+    }
+
+    /// Get the app usages of the raw day logs like [`LogStore::get_raw_app_usages`], with the corrections of
+    /// the data directory applied, as reports show them
+    pub fn get_corrected_app_usages(&self, start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<AppUsage>> {
+        let events = self.read_events(&self.get_log_dates(start_timestamp, end_timestamp), 0, u64::MAX)?;
+        let app_usages = apply_corrections(
+            aggregate_app_usages(events, self.session_gap_millis),
+            &load_corrections(&self.data_dir)?,
+        );
+        Ok(clip_app_usages(app_usages, start_timestamp, end_timestamp))
+    }
+
+    /// Get all app usages between start_timestamp and end_timestamp, with corrections, longest first
+    ///
+    /// Days that have been compacted by the retention policy only contribute to the total time.
+    pub fn get_app_usages(&self, start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<AppUsage>> {
+        let mut app_usages = self.get_corrected_app_usages(start_timestamp, end_timestamp)?;
+
+        // A summary holds what its day log held, a day of the log clock
+        for date in self.get_log_dates(start_timestamp, end_timestamp) {
+            if Path::new(&self.get_log_file_path(date)).exists() {
                 continue;
             }
+            let Some(day_start) = self.log_clock.get_date_start_timestamp(date) else {
+                continue;
+            };
+            let date = date.format("%Y%m%d").to_string();
+            if let Some(summary) = read_day_summary_in(&self.data_dir, &date)? {
+                add_summarized_usages(&mut app_usages, &summary, day_start, start_timestamp, end_timestamp);
+            }
+        }
+
+        // Sort by total time
+        app_usages.sort_by_key(|app| std::cmp::Reverse(app.total_secs));
+
+        Ok(app_usages)
+    }
+}
+
+/// Get all raw events between start_timestamp and end_timestamp, in the order they were written
+pub fn get_events_from_log(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<Event>> {
+    let clock = get_report_clock();
+    LogStore::new(get_log_file_dir_str(), clock.as_ref()).get_events(start_timestamp, end_timestamp)
+}
+
+/// Set the end time of the last duration of an app
+fn end_current_duration(app_usages: &mut [AppUsage], app_name: &Option<String>, timestamp: u64) {
+    if let Some(prev_app_name) = app_name {
+        if let Some(app) = app_usages.iter_mut().find(|app| &app.name == prev_app_name) {
+            if let Some((start_time, end_time)) = app.durations.last_mut() {
+                // An event from before the session started, e.g. after the clock was set back, ends it right away
+                *end_time = timestamp.max(*start_time);
+            }
+        }
+    }
+}
+
+/// Get all app usages from the raw day logs only, ignoring compacted days
+///
+/// Durations are clipped to the range, and the result is not sorted.
+pub fn get_raw_app_usages(
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> io::Result<Vec<AppUsage>> {
+    let clock = get_report_clock();
+    LogStore::new(get_log_file_dir_str(), clock.as_ref()).get_raw_app_usages(start_timestamp, end_timestamp)
+}
+
+/// Add up the durations of an app into its total time
+pub(crate) fn set_total_secs(app: &mut AppUsage) {
+    let total_millis: u64 = app.durations.iter().map(|(start_time, end_time)| end_time - start_time).sum();
+    app.total_secs = total_millis / 1000;
+}
+
+/// Cut the durations to the part between start_timestamp and end_timestamp, dropping apps without any
+fn clip_app_usages(app_usages: Vec<AppUsage>, start_timestamp: u64, end_timestamp: u64) -> Vec<AppUsage> {
+    let mut clipped_usages = Vec::new();
+    for mut app in app_usages {
+        app.durations = app
+            .durations
+            .iter()
+            .filter(|(start_time, end_time)| *start_time <= end_timestamp && *end_time >= start_timestamp)
+            .map(|(start_time, end_time)| ((*start_time).max(start_timestamp), (*end_time).min(end_timestamp)))
+            .collect();
+        if !app.durations.is_empty() {
+            set_total_secs(&mut app);
+            clipped_usages.push(app);
+        }
+    }
+    clipped_usages
+}
+
+/// The session gap of the default heartbeat interval and gap threshold
+pub const DEFAULT_SESSION_GAP_MILLIS: u64 = 70_000;
+
+/// How long after the last heartbeat or app switch a session still counts, if the logs have heartbeats
+static SESSION_GAP_MILLIS: AtomicU64 = AtomicU64::new(DEFAULT_SESSION_GAP_MILLIS);
+
+/// Use the heartbeat interval and gap threshold of the config for the reports of new log stores
+pub fn init_session_gap(config: &MonitorConfig) {
+    let gap_millis = (config.heartbeat_interval_secs + config.gap_threshold_secs) * 1000;
+    SESSION_GAP_MILLIS.store(gap_millis, Ordering::SeqCst);
+}
+
+/// Turn events, in the order they were written, into the durations and total time of each app
+///
+/// Heartbeats extend the open session. A session that has seen a heartbeat and whose next event comes
+/// later than `session_gap_millis` allows, e.g. after the process was killed, ends at its last heartbeat.
+/// Sessions without heartbeats, e.g. from days logged before them or imported from other trackers,
+/// stay open until the next event.
+pub fn aggregate_app_usages(events: Vec<Event>, session_gap_millis: u64) -> Vec<AppUsage> {
+    let mut current_app_name: Option<String> = None;
+    // The last event that showed the current app was still in front
+    let mut last_seen_timestamp: u64 = 0;
+    // Whether the current session was written by a monitor that sends heartbeats
+    let mut has_heartbeat = false;
+    let mut app_usages: Vec<AppUsage> = Vec::new();
 
+    // Go through the events, and get all durations for each app
+    for event in events {
+        // The current session already ends at its last heartbeat if it was cut short
+        if has_heartbeat && event.timestamp.saturating_sub(last_seen_timestamp) > session_gap_millis {
+            current_app_name = None;
+        }
+        match event.event_type {
             // When switching apps
-            if event_type == "0" {
-                let app_path = parts[2];
-                let app_name = get_app_name_from_path(app_path).unwrap_or(String::from("Unknown"));
+            EventType::CameToFront => {
+                let app_name = get_app_name_from_path(&event.path).unwrap_or(String::from("Unknown"));
 
                 // Set end time for previous app
-                if let Some(prev_app_name) = &current_app_name {
-                    app_usages
-                        .iter_mut()
-                        .find(|app| &app.name == prev_app_name)
-                        .map(|app| {
-                            app.durations
-                                .last_mut()
-                                .map(|(_, _end_time)| *_end_time = timestamp)
-                        });
-                }
+                end_current_duration(&mut app_usages, &current_app_name, event.timestamp);
+                last_seen_timestamp = event.timestamp;
+                has_heartbeat = false;
+
                 // Set start time for current app
                 current_app_name = Some(app_name.clone());
-                if let Some(app) = app_usages.iter_mut().find(|app| &app.name == &app_name) {
-                    app.durations.push((timestamp, timestamp));
+                if let Some(app) = app_usages.iter_mut().find(|app| app.name == app_name) {
+                    app.durations.push((event.timestamp, event.timestamp));
                 } else {
                     app_usages.push(AppUsage {
                         name: app_name,
-                        path: app_path.to_string(),
+                        path: event.path,
                         total_secs: 0,
-                        durations: vec![(timestamp, timestamp)],
+                        durations: vec![(event.timestamp, event.timestamp)],
                     });
                 }
             }
             // When shutting down or not in use
-            else if event_type == "1" || event_type == "2" {
+            EventType::ShutDown | EventType::StopMonitoring | EventType::Idle => {
                 // Set end time for current app
-                if let Some(prev_app_name) = &current_app_name {
-                    app_usages
-                        .iter_mut()
-                        .find(|app| &app.name == prev_app_name)
-                        .map(|app| {
-                            app.durations
-                                .last_mut()
-                                .map(|(_, _end_time)| *_end_time = timestamp)
-                        });
-                }
+                end_current_duration(&mut app_usages, &current_app_name, event.timestamp);
                 current_app_name = None;
+                has_heartbeat = false;
             }
+            EventType::Heartbeat => {
+                end_current_duration(&mut app_usages, &current_app_name, event.timestamp);
+                last_seen_timestamp = event.timestamp;
+                has_heartbeat = current_app_name.is_some();
+            }
+            // Tags, timezones and window titles don't change the app in front
+            EventType::Tag | EventType::Zone | EventType::Title => {}
         }
     }
 
     // Calculate total time for each app
     for app in app_usages.iter_mut() {
-        let mut total_microsecs: u64 = 0;
-        for (_start_time, _end_time) in app.durations.iter() {
-            total_microsecs += _end_time - _start_time;
-        }
-        app.total_secs = total_microsecs / 1000;
+        set_total_secs(app);
     }
 
-    // Sort by total time
-    app_usages.sort_by(|a, b| b.total_secs.cmp(&a.total_secs));
+    app_usages
+}
 
-    Ok(app_usages)
+/// Add the part of a compacted day's summary that falls into the queried range
+fn add_summarized_usages(
+    app_usages: &mut Vec<AppUsage>,
+    summary: &[AppDaySummary],
+    day_start: u64,
+    start_timestamp: u64,
+    end_timestamp: u64,
+) {
+    for app_summary in summary {
+        let mut secs: u64 = 0;
+        for (hour, hour_secs) in app_summary.hourly_secs.iter().enumerate() {
+            let hour_start = day_start + hour as u64 * 3600000;
+            let hour_end = hour_start + 3600000;
+            let overlap = end_timestamp
+                .min(hour_end)
+                .saturating_sub(start_timestamp.max(hour_start));
+            secs += hour_secs * overlap / 3600000;
+        }
+        if secs == 0 {
+            continue;
+        }
+
+        if let Some(app) = app_usages.iter_mut().find(|app| app.name == app_summary.name) {
+            app.total_secs += secs;
+        } else {
+            app_usages.push(AppUsage {
+                name: app_summary.name.clone(),
+                path: app_summary.path.clone(),
+                total_secs: secs,
+                durations: Vec::new(),
+            });
+        }
+    }
+}
+
+/// Get all app usages from local log file
+///
+/// Each app usage contains the app name, path, total time in seconds, and durations.
+/// Days that have been compacted by the retention policy only contribute to the total time.
+/// Short sessions are smoothed by the rules of the config.
+pub fn get_app_usages_from_log(
+    start_timestamp: u64,
+    end_timestamp: u64,
+) -> io::Result<Vec<AppUsage>> {
+    let clock = get_report_clock();
+    let app_usages = LogStore::new(get_log_file_dir_str(), clock.as_ref()).get_app_usages(start_timestamp, end_timestamp)?;
+    Ok(smooth_app_usages(app_usages, &get_smoothing_rules()))
 }
 
+#[cfg(target_os = "macos")]
 pub fn get_window_property<T: FromCFType>(
     dic_ref: CFDictionaryRef,
     key: &str,
@@ -210,10 +509,12 @@ pub fn get_window_property<T: FromCFType>(
     }
 }
 
+#[cfg(target_os = "macos")]
 pub trait FromCFType: Sized {
     fn from_cf_type(cf_type: *const c_void) -> Option<Self>;
 }
 
+#[cfg(target_os = "macos")]
 impl FromCFType for i32 {
     fn from_cf_type(cf_type: *const c_void) -> Option<Self> {
         let number_ref = cf_type as CFNumberRef;
@@ -233,53 +534,151 @@ impl FromCFType for i32 {
     }
 }
 
+#[cfg(target_os = "macos")]
+impl FromCFType for String {
+    fn from_cf_type(cf_type: *const c_void) -> Option<Self> {
+        if cf_type.is_null() {
+            return None;
+        }
+        let string = unsafe { CFString::wrap_under_get_rule(cf_type as CFStringRef) };
+        Some(string.to_string())
+    }
+}
+
+/// Get a property of the frontmost window, e.g. `kCGWindowOwnerPID`
+#[cfg(target_os = "macos")]
+fn get_frontmost_window_property<T: FromCFType>(key: &str) -> Result<T, &'static str> {
+    const OPTIONS: CGWindowListOption =
+        kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements;
+    let window_list_info = unsafe { CGWindowListCopyWindowInfo(OPTIONS, kCGNullWindowID) };
+    if window_list_info.is_null() {
+        return Err("Failed to copy window list info");
+    }
+
+    let count = unsafe { CFArrayGetCount(window_list_info) };
+    if count == 0 {
+        unsafe { CFRelease(window_list_info as *const _) };
+        return Err("No windows found");
+    }
+
+    let mut front_window_property: Option<Result<T, &'static str>> = None;
+    let mut last_layer: i32 = 0;
+
+    for i in 0..count {
+        let dic_ref = unsafe { CFArrayGetValueAtIndex(window_list_info, i) as CFDictionaryRef };
+        if dic_ref.is_null() {
+            continue;
+        }
+
+        if let Ok(layer) = get_window_property(dic_ref, "kCGWindowLayer") {
+            if layer == 0 && last_layer != 0 && get_window_property::<i32>(dic_ref, "kCGWindowOwnerPID").is_ok() {
+                front_window_property = Some(get_window_property(dic_ref, key));
+                break;
+            }
+            last_layer = layer;
+        }
+    }
+
+    unsafe { CFRelease(window_list_info as *const _) };
+
+    front_window_property.unwrap_or(Err("Failed to get frontmost window"))
+}
+
 /// Get the PID of the frontmost window
 ///
-/// This function is only available on macOS
+/// This function is available on macOS, and on Linux under X11 with `xdotool` installed
 pub fn get_frontmost_window_pid() -> Result<i32, &'static str> {
     #[cfg(target_os = "macos")]
     {
-        const OPTIONS: CGWindowListOption =
-            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements;
-        let window_list_info = unsafe { CGWindowListCopyWindowInfo(OPTIONS, kCGNullWindowID) };
-        if window_list_info.is_null() {
-            return Err("Failed to copy window list info");
+        get_frontmost_window_property("kCGWindowOwnerPID")
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let output = std::process::Command::new("xdotool")
+            .args(["getactivewindow", "getwindowpid"])
+            .output()
+            .map_err(|_| "Failed to execute xdotool")?;
+        if !output.status.success() {
+            return Err("Failed to get active window");
         }
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| "Failed to parse active window PID")
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Err("This function is only available on macOS and Linux")
+    }
+}
 
-        let count = unsafe { CFArrayGetCount(window_list_info) };
-        if count == 0 {
-            unsafe { CFRelease(window_list_info as *const _) };
-            return Err("No windows found");
+/// Get the title of the frontmost window, on a single line
+///
+/// This function is available on macOS, where titles need the screen recording permission, and on Linux
+/// under X11 with `xdotool` installed
+pub fn get_frontmost_window_title() -> Result<String, &'static str> {
+    #[cfg(target_os = "macos")]
+    let title: Result<String, &'static str> = get_frontmost_window_property("kCGWindowName");
+
+    #[cfg(target_os = "linux")]
+    let title: Result<String, &'static str> = {
+        let output = std::process::Command::new("xdotool")
+            .args(["getactivewindow", "getwindowname"])
+            .output()
+            .map_err(|_| "Failed to execute xdotool")?;
+        if !output.status.success() {
+            return Err("Failed to get active window");
         }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
 
-        let mut front_window_pid: Option<i32> = None;
-        let mut last_layer: i32 = 0;
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    let title: Result<String, &'static str> = Err("This function is only available on macOS and Linux");
 
-        for i in 0..count {
-            let dic_ref = unsafe { CFArrayGetValueAtIndex(window_list_info, i) as CFDictionaryRef };
-            if dic_ref.is_null() {
-                continue;
-            }
+    // A line break would end the line of the event
+    title.map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+}
 
-            if let Ok(layer) = get_window_property(dic_ref, "kCGWindowLayer") {
-                if layer == 0 && last_layer != 0 {
-                    if let Ok(pid) = get_window_property(dic_ref, "kCGWindowOwnerPID") {
-                        front_window_pid = Some(pid);
-                        break;
-                    }
-                }
-                last_layer = layer;
-            }
-        }
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceSecondsSinceLastEventType(state_id: i32, event_type: u32) -> f64;
+}
 
-        unsafe { CFRelease(window_list_info as *const _) };
+/// Get the time since the last keyboard or mouse input, in milliseconds
+///
+/// This function is available on macOS, and on Linux under X11 with `xprintidle` installed
+pub fn get_idle_millis() -> Result<u64, &'static str> {
+    #[cfg(target_os = "macos")]
+    {
+        // kCGEventSourceStateCombinedSessionState and kCGAnyInputEventType
+        let secs = unsafe { CGEventSourceSecondsSinceLastEventType(0, !0) };
+        if secs.is_finite() && secs >= 0.0 {
+            Ok((secs * 1000.0) as u64)
+        } else {
+            Err("Failed to get idle time")
+        }
+    }
 
-        front_window_pid.ok_or("Failed to get frontmost window PID")
+    #[cfg(target_os = "linux")]
+    {
+        let output = std::process::Command::new("xprintidle")
+            .output()
+            .map_err(|_| "Failed to execute xprintidle")?;
+        if !output.status.success() {
+            return Err("Failed to get idle time");
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| "Failed to parse idle time")
     }
 
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
-        Err("This function is only available on macOS")
+        Err("This function is only available on macOS and Linux")
     }
 }
 
@@ -299,4 +698,94 @@ mod tests {
             Some("MyApp".to_string())
         );
     }
+
+    #[test]
+    fn test_parse_event() {
+        let event = Event::parse("0,1720000000000,/Applications/My, App.app").unwrap();
+        assert_eq!(event.event_type, EventType::CameToFront);
+        assert_eq!(event.timestamp, 1720000000000);
+        assert_eq!(event.path, "/Applications/My, App.app");
+
+        let event = Event::parse("1,1720000000000").unwrap();
+        assert_eq!(event.event_type, EventType::ShutDown);
+        assert_eq!(event.path, "");
+
+        assert!(Event::parse("0,1720000000000").is_none());
+
+        let event = Event::parse("3,1720000000000,deep work").unwrap();
+        assert_eq!(event.event_type, EventType::Tag);
+        assert_eq!(event.path, "deep work");
+        assert!(Event::parse("3,1720000000000").is_none());
+
+        let event = Event::parse("7,1720000000000,main.rs, flows").unwrap();
+        assert_eq!(event.event_type, EventType::Title);
+        assert_eq!(event.path, "main.rs, flows");
+        // A window without a title
+        assert_eq!(Event::parse("7,1720000000000").unwrap().path, "");
+        assert!(Event::parse("9,1720000000000").is_none());
+        assert!(Event::parse("0,abc,/Applications/MyApp.app").is_none());
+        assert!(Event::parse("").is_none());
+    }
+
+    #[test]
+    fn test_heartbeat_ends_session() {
+        let event = |event_type, timestamp: u64, path: &str| Event {
+            event_type,
+            timestamp,
+            path: path.to_string(),
+        };
+        // Killed after the heartbeat at 120s, started again an hour later
+        let events = vec![
+            event(EventType::CameToFront, 0, "/usr/bin/code"),
+            event(EventType::Heartbeat, 60_000, ""),
+            event(EventType::Heartbeat, 120_000, ""),
+            event(EventType::CameToFront, 3_720_000, "/usr/bin/firefox"),
+            event(EventType::Heartbeat, 3_780_000, ""),
+            event(EventType::ShutDown, 3_800_000, ""),
+        ];
+        let app_usages = aggregate_app_usages(events, DEFAULT_SESSION_GAP_MILLIS);
+        let code = app_usages.iter().find(|app| app.name == "code").unwrap();
+        assert_eq!(code.durations, vec![(0, 120_000)]);
+        let firefox = app_usages.iter().find(|app| app.name == "firefox").unwrap();
+        assert_eq!(firefox.durations, vec![(3_720_000, 3_800_000)]);
+
+        // Without heartbeats, sessions last until the next event
+        let events = vec![
+            event(EventType::CameToFront, 0, "/usr/bin/code"),
+            event(EventType::CameToFront, 3_720_000, "/usr/bin/firefox"),
+        ];
+        let code = aggregate_app_usages(events, DEFAULT_SESSION_GAP_MILLIS).into_iter().find(|app| app.name == "code").unwrap();
+        assert_eq!(code.durations, vec![(0, 3_720_000)]);
+    }
+
+    #[test]
+    fn test_heartbeat_gap_per_session() {
+        let event = |event_type, timestamp: u64, path: &str| Event {
+            event_type,
+            timestamp,
+            path: path.to_string(),
+        };
+        let events = vec![
+            // A day logged before heartbeats
+            event(EventType::CameToFront, 0, "/usr/bin/code"),
+            event(EventType::CameToFront, 3_600_000, "/usr/bin/firefox"),
+            event(EventType::ShutDown, 7_200_000, ""),
+            // A session imported from another tracker
+            event(EventType::CameToFront, 10_000_000, "/usr/bin/slack"),
+            event(EventType::ShutDown, 13_600_000, ""),
+            // A day with heartbeats, killed after the one at 120s
+            event(EventType::CameToFront, 86_400_000, "/usr/bin/terminal"),
+            event(EventType::Heartbeat, 86_460_000, ""),
+            event(EventType::Heartbeat, 86_520_000, ""),
+            event(EventType::CameToFront, 90_000_000, "/usr/bin/mail"),
+            event(EventType::ShutDown, 90_600_000, ""),
+        ];
+        let app_usages = aggregate_app_usages(events, DEFAULT_SESSION_GAP_MILLIS);
+        let durations = |name: &str| app_usages.iter().find(|app| app.name == name).unwrap().durations.clone();
+        assert_eq!(durations("code"), vec![(0, 3_600_000)]);
+        assert_eq!(durations("firefox"), vec![(3_600_000, 7_200_000)]);
+        assert_eq!(durations("slack"), vec![(10_000_000, 13_600_000)]);
+        assert_eq!(durations("terminal"), vec![(86_400_000, 86_520_000)]);
+        assert_eq!(durations("mail"), vec![(90_000_000, 90_600_000)]);
+    }
 }
@@ -0,0 +1,512 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::clock::get_report_clock;
+use crate::config::ReportsConfig;
+use crate::corrections::load_corrections;
+use crate::projects::{assign_projects, get_projects};
+use crate::rules::{apply_rules, get_rules, RuleSet};
+use crate::sys_monitor::{get_app_name_from_path, AppUsage, Event, EventType, LogStore};
+use crate::utils::get_log_file_dir_str;
+
+/// Time an app was in front without interruption
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// How short sessions are cleaned up in reports
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SmoothingRules {
+    pub min_session_millis: u64,
+    pub merge_gap_millis: u64,
+}
+
+impl SmoothingRules {
+    pub fn from_config(config: &ReportsConfig) -> SmoothingRules {
+        SmoothingRules {
+            min_session_millis: config.min_session_secs * 1000,
+            merge_gap_millis: config.merge_gap_secs * 1000,
+        }
+    }
+
+    fn is_off(&self) -> bool {
+        self.min_session_millis == 0 && self.merge_gap_millis == 0
+    }
+}
+
+static MIN_SESSION_MILLIS: AtomicU64 = AtomicU64::new(0);
+static MERGE_GAP_MILLIS: AtomicU64 = AtomicU64::new(0);
+
+/// Use the smoothing rules of the config for the reports
+pub fn init_smoothing(config: &ReportsConfig) {
+    let rules = SmoothingRules::from_config(config);
+    MIN_SESSION_MILLIS.store(rules.min_session_millis, Ordering::SeqCst);
+    MERGE_GAP_MILLIS.store(rules.merge_gap_millis, Ordering::SeqCst);
+}
+
+pub fn get_smoothing_rules() -> SmoothingRules {
+    SmoothingRules {
+        min_session_millis: MIN_SESSION_MILLIS.load(Ordering::SeqCst),
+        merge_gap_millis: MERGE_GAP_MILLIS.load(Ordering::SeqCst),
+    }
+}
+
+/// The sessions of all apps, ordered by start time
+pub fn get_sessions(app_usages: &[AppUsage]) -> Vec<Session> {
+    let mut sessions: Vec<Session> = app_usages
+        .iter()
+        .flat_map(|app| {
+            app.durations.iter().map(|&(start, end)| Session {
+                name: app.name.clone(),
+                path: app.path.clone(),
+                start,
+                end,
+            })
+        })
+        .collect();
+    sessions.sort_by_key(|session| (session.start, session.end));
+    sessions
+}
+
+/// Join sessions of the same app that follow each other at most `merge_gap_millis` apart
+fn merge_sessions(sessions: Vec<Session>, merge_gap_millis: u64) -> Vec<Session> {
+    let mut merged: Vec<Session> = Vec::with_capacity(sessions.len());
+    for session in sessions {
+        match merged.last_mut() {
+            Some(last) if last.name == session.name && session.start <= last.end + merge_gap_millis => {
+                last.end = last.end.max(session.end);
+            }
+            _ => merged.push(session),
+        }
+    }
+    merged
+}
+
+/// Drop sessions shorter than `min_session_millis`, giving their time to the session right before,
+/// or else right after. Short sessions without a neighbour, e.g. before going idle, are dropped.
+fn drop_short_sessions(sessions: Vec<Session>, min_session_millis: u64) -> Vec<Session> {
+    let mut kept: Vec<Session> = Vec::with_capacity(sessions.len());
+    // The start of dropped time that goes to the next session, if it follows right after
+    let mut unclaimed: Option<(u64, u64)> = None;
+    for mut session in sessions {
+        if session.end - session.start < min_session_millis {
+            match kept.last_mut() {
+                Some(last) if last.end == session.start => last.end = session.end,
+                _ => match unclaimed {
+                    Some((start, end)) if end == session.start => unclaimed = Some((start, session.end)),
+                    _ => unclaimed = Some((session.start, session.end)),
+                },
+            }
+            continue;
+        }
+        if let Some((start, end)) = unclaimed.take() {
+            if end == session.start {
+                session.start = start;
+            }
+        }
+        kept.push(session);
+    }
+    kept
+}
+
+/// Clean up sessions by the rules, keeping them ordered by start time
+///
+/// Same app sessions are joined before and after dropping short ones, so flicking away from an app and
+/// back leaves one session.
+pub fn smooth_sessions(sessions: Vec<Session>, rules: &SmoothingRules) -> Vec<Session> {
+    if rules.is_off() {
+        return sessions;
+    }
+    let sessions = merge_sessions(sessions, rules.merge_gap_millis);
+    let sessions = drop_short_sessions(sessions, rules.min_session_millis);
+    merge_sessions(sessions, rules.merge_gap_millis)
+}
+
+/// Apply the rules to the durations of app usages, longest first
+///
+/// Time from compacted days, which has no durations, is kept as it is.
+pub fn smooth_app_usages(app_usages: Vec<AppUsage>, rules: &SmoothingRules) -> Vec<AppUsage> {
+    if rules.is_off() {
+        return app_usages;
+    }
+    let sessions = smooth_sessions(get_sessions(&app_usages), rules);
+    let mut smoothed_usages = Vec::new();
+    for mut app in app_usages {
+        let duration_millis: u64 = app.durations.iter().map(|(start, end)| end - start).sum();
+        let summarized_secs = app.total_secs.saturating_sub(duration_millis / 1000);
+        app.durations = sessions
+            .iter()
+            .filter(|session| session.name == app.name)
+            .map(|session| (session.start, session.end))
+            .collect();
+        if app.durations.is_empty() && summarized_secs == 0 {
+            continue;
+        }
+        let smoothed_millis: u64 = app.durations.iter().map(|(start, end)| end - start).sum();
+        app.total_secs = smoothed_millis / 1000 + summarized_secs;
+        smoothed_usages.push(app);
+    }
+    smoothed_usages.sort_by_key(|app| std::cmp::Reverse(app.total_secs));
+    smoothed_usages
+}
+
+/// What the user was doing during a timeline entry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// An app was in front
+    App,
+    /// No keyboard or mouse input
+    Idle,
+    /// Nothing was tracked, e.g. while asleep, locked or paused, or while the daemon wasn't running
+    Away,
+}
+
+/// A part of the timeline, with the app and window title for `app` entries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub kind: EntryKind,
+    pub name: Option<String>,
+    pub path: Option<String>,
+    /// The title of the window in front, if it was recorded
+    pub title: Option<String>,
+    /// The project the time counts for, by the project rules, the rules file or a correction
+    pub project: Option<String>,
+    /// By the rules file, or the correction that added the time
+    #[serde(default)]
+    pub category: Option<String>,
+    /// By the rules file
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl TimelineEntry {
+    fn gap(kind: EntryKind, start: u64, end: u64) -> TimelineEntry {
+        TimelineEntry {
+            kind,
+            name: None,
+            path: None,
+            title: None,
+            project: None,
+            category: None,
+            tags: Vec::new(),
+            start,
+            end,
+        }
+    }
+}
+
+/// A window title as recorded: from `start` until the title or the app in front changed
+struct TitleSpan {
+    name: String,
+    title: Option<String>,
+    start: u64,
+    end: u64,
+}
+
+/// Get the window titles of the apps in front, ordered by time
+fn get_title_spans(events: &[Event]) -> Vec<TitleSpan> {
+    let mut spans: Vec<TitleSpan> = Vec::new();
+    let mut current_app_name: Option<String> = None;
+    let mut open = false;
+    for event in events {
+        let ends_title = match event.event_type {
+            EventType::CameToFront => {
+                current_app_name = Some(get_app_name_from_path(&event.path).unwrap_or(String::from("Unknown")));
+                true
+            }
+            EventType::ShutDown | EventType::StopMonitoring | EventType::Idle => {
+                current_app_name = None;
+                true
+            }
+            EventType::Title => true,
+            EventType::Tag | EventType::Heartbeat | EventType::Zone => false,
+        };
+        if ends_title && open {
+            if let Some(span) = spans.last_mut() {
+                span.end = event.timestamp;
+            }
+            open = false;
+        }
+        if let (EventType::Title, Some(name)) = (event.event_type, &current_app_name) {
+            spans.push(TitleSpan {
+                name: name.clone(),
+                title: Some(event.path.clone()).filter(|title| !title.is_empty()),
+                start: event.timestamp,
+                end: u64::MAX,
+            });
+            open = true;
+        }
+    }
+    spans
+}
+
+/// Split an app session where its window title changed
+///
+/// Each title goes on until the next one, and the first one also covers the start of the session,
+/// e.g. a short session of another app it took over when smoothing.
+fn split_by_title(session: &Session, title_spans: &[TitleSpan]) -> Vec<TimelineEntry> {
+    let titles: Vec<&TitleSpan> = title_spans
+        .iter()
+        .filter(|span| span.name == session.name && span.start <= session.end && span.end > session.start)
+        .collect();
+    let mut entries: Vec<TimelineEntry> = Vec::new();
+    for (index, span) in titles.iter().enumerate() {
+        let start = if index == 0 { session.start } else { span.start.max(session.start) };
+        let end = titles.get(index + 1).map_or(session.end, |next| next.start.min(session.end));
+        match entries.last_mut() {
+            Some(last) if last.title == span.title => last.end = end,
+            // A title that changed again at once
+            _ if start == end && index > 0 => {}
+            _ => entries.push(TimelineEntry {
+                kind: EntryKind::App,
+                name: Some(session.name.clone()),
+                path: Some(session.path.clone()),
+                title: span.title.clone(),
+                project: None,
+                category: None,
+                tags: Vec::new(),
+                start,
+                end,
+            }),
+        }
+    }
+    if entries.is_empty() {
+        entries.push(TimelineEntry {
+            kind: EntryKind::App,
+            name: Some(session.name.clone()),
+            path: Some(session.path.clone()),
+            title: None,
+            project: None,
+            category: None,
+            tags: Vec::new(),
+            start: session.start,
+            end: session.end,
+        });
+    }
+    entries
+}
+
+/// Turn sessions, ordered by start time, into a timeline with window titles and the gaps between them
+///
+/// `events` are the raw events around the sessions, for the titles and the reason of each gap.
+pub fn build_timeline(sessions: &[Session], events: &[Event]) -> Vec<TimelineEntry> {
+    let title_spans = get_title_spans(events);
+    let mut timeline: Vec<TimelineEntry> = Vec::new();
+    let mut previous_end: Option<u64> = None;
+    for session in sessions {
+        if let Some(gap_start) = previous_end.filter(|end| *end < session.start) {
+            // Going idle ends a session with an idle event, anything else leaves it untracked
+            let idle = events.iter().any(|event| {
+                event.event_type == EventType::Idle && event.timestamp >= gap_start && event.timestamp < session.start
+            });
+            let kind = if idle { EntryKind::Idle } else { EntryKind::Away };
+            timeline.push(TimelineEntry::gap(kind, gap_start, session.start));
+        }
+        timeline.extend(split_by_title(session, &title_spans));
+        previous_end = Some(previous_end.map_or(session.end, |end| end.max(session.end)));
+    }
+    timeline
+}
+
+/// The timeline as recorded, and as reports show it after smoothing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timelines {
+    pub raw: Vec<TimelineEntry>,
+    pub smoothed: Vec<TimelineEntry>,
+}
+
+/// How long before the range the title of a session crossing its start is looked for
+const TITLE_LOOKBACK_MILLIS: u64 = 24 * 3600 * 1000;
+
+/// Get both timelines of the local logs between start_timestamp and end_timestamp
+pub fn get_timelines(start_timestamp: u64, end_timestamp: u64) -> io::Result<Timelines> {
+    get_timelines_with_rules(start_timestamp, end_timestamp, &get_rules())
+}
+
+/// Get both timelines with a set of rules instead of the rules file, e.g. to try them out
+pub fn get_timelines_with_rules(start_timestamp: u64, end_timestamp: u64, rules: &RuleSet) -> io::Result<Timelines> {
+    let clock = get_report_clock();
+    let store = LogStore::new(get_log_file_dir_str(), clock.as_ref());
+    let raw_sessions = get_sessions(&store.get_corrected_app_usages(start_timestamp, end_timestamp)?);
+    // Titles of sessions that started before the range
+    let events = store.get_events(start_timestamp.saturating_sub(TITLE_LOOKBACK_MILLIS), end_timestamp)?;
+    let smoothed_sessions = smooth_sessions(raw_sessions.clone(), &get_smoothing_rules());
+    let projects = get_projects();
+    let corrections = load_corrections(&store.data_dir)?;
+    let label = |timeline: Vec<TimelineEntry>| {
+        assign_projects(apply_rules(timeline, rules, &corrections, clock.as_ref()), &projects, &corrections)
+    };
+    Ok(Timelines {
+        raw: label(build_timeline(&raw_sessions, &events)),
+        smoothed: label(build_timeline(&smoothed_sessions, &events)),
+    })
+}
+
+/// Get the smoothed timeline of the local logs between start_timestamp and end_timestamp, ordered by time
+pub fn get_timeline(start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<TimelineEntry>> {
+    Ok(get_timelines(start_timestamp, end_timestamp)?.smoothed)
+}
+
+/// Add up the time of each label of the entries of a timeline, e.g. their projects, longest first
+pub(crate) fn add_up_labels<'a>(
+    timeline: &'a [TimelineEntry],
+    get_labels: impl Fn(&'a TimelineEntry) -> Vec<&'a str>,
+) -> Vec<(String, u64)> {
+    let mut label_millis: HashMap<&str, u64> = HashMap::new();
+    for entry in timeline {
+        for label in get_labels(entry) {
+            *label_millis.entry(label).or_default() += entry.end - entry.start;
+        }
+    }
+    let mut totals: Vec<(String, u64)> = label_millis
+        .into_iter()
+        .map(|(label, millis)| (label.to_string(), millis / 1000))
+        .collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys_monitor::{aggregate_app_usages, DEFAULT_SESSION_GAP_MILLIS};
+
+    fn session(name: &str, start_secs: u64, end_secs: u64) -> Session {
+        Session {
+            name: name.to_string(),
+            path: format!("/usr/bin/{}", name),
+            start: start_secs * 1000,
+            end: end_secs * 1000,
+        }
+    }
+
+    #[test]
+    fn test_smooth_sessions() {
+        let rules = SmoothingRules {
+            min_session_millis: 5000,
+            merge_gap_millis: 3000,
+        };
+        let sessions = vec![
+            // Flicking past the terminal and back to the editor
+            session("code", 0, 100),
+            session("terminal", 100, 101),
+            session("code", 101, 200),
+            // A short gap, e.g. the screen was locked for a moment
+            session("code", 202, 300),
+            // A short session goes to the app before it
+            session("finder", 300, 302),
+            session("firefox", 302, 400),
+            // Or after it, e.g. after being idle. Without either, it is dropped.
+            session("slack", 500, 501),
+            session("mail", 501, 600),
+            session("slack", 700, 701),
+        ];
+        assert_eq!(
+            smooth_sessions(sessions.clone(), &rules),
+            vec![session("code", 0, 302), session("firefox", 302, 400), session("mail", 500, 600)]
+        );
+        assert_eq!(smooth_sessions(sessions.clone(), &SmoothingRules::default()), sessions);
+    }
+
+    #[test]
+    fn test_smooth_app_usages() {
+        let app_usages = vec![
+            AppUsage {
+                name: "code".to_string(),
+                path: "/usr/bin/code".to_string(),
+                // An hour from a compacted day on top of the durations
+                total_secs: 3600 + 199,
+                durations: vec![(0, 100_000), (101_000, 200_000)],
+            },
+            AppUsage {
+                name: "terminal".to_string(),
+                path: "/usr/bin/terminal".to_string(),
+                total_secs: 1,
+                durations: vec![(100_000, 101_000)],
+            },
+        ];
+        let rules = SmoothingRules {
+            min_session_millis: 5000,
+            merge_gap_millis: 0,
+        };
+        let smoothed = smooth_app_usages(app_usages, &rules);
+        assert_eq!(smoothed.len(), 1);
+        assert_eq!(smoothed[0].durations, vec![(0, 200_000)]);
+        assert_eq!(smoothed[0].total_secs, 3600 + 200);
+    }
+
+    fn event(event_type: EventType, secs: u64, path: &str) -> Event {
+        Event {
+            event_type,
+            timestamp: secs * 1000,
+            path: path.to_string(),
+        }
+    }
+
+    fn entry(name: &str, title: Option<&str>, start_secs: u64, end_secs: u64) -> TimelineEntry {
+        TimelineEntry {
+            kind: EntryKind::App,
+            name: Some(name.to_string()),
+            path: Some(format!("/usr/bin/{}", name)),
+            title: title.map(String::from),
+            project: None,
+            category: None,
+            tags: Vec::new(),
+            start: start_secs * 1000,
+            end: end_secs * 1000,
+        }
+    }
+
+    #[test]
+    fn test_build_timeline() {
+        let events = vec![
+            event(EventType::CameToFront, 0, "/usr/bin/code"),
+            event(EventType::Title, 0, "main.rs"),
+            event(EventType::Title, 50, "lib.rs"),
+            event(EventType::CameToFront, 100, "/usr/bin/terminal"),
+            event(EventType::Title, 100, ""),
+            event(EventType::CameToFront, 101, "/usr/bin/code"),
+            event(EventType::Title, 101, "lib.rs"),
+            event(EventType::Idle, 200, ""),
+            event(EventType::CameToFront, 300, "/usr/bin/code"),
+            event(EventType::Title, 300, "lib.rs"),
+            event(EventType::ShutDown, 400, ""),
+            // Recorded without titles
+            event(EventType::CameToFront, 500, "/usr/bin/firefox"),
+            event(EventType::StopMonitoring, 600, ""),
+        ];
+        let sessions = get_sessions(&aggregate_app_usages(events.clone(), DEFAULT_SESSION_GAP_MILLIS));
+        assert_eq!(
+            build_timeline(&sessions, &events),
+            vec![
+                entry("code", Some("main.rs"), 0, 50),
+                entry("code", Some("lib.rs"), 50, 100),
+                entry("terminal", None, 100, 101),
+                entry("code", Some("lib.rs"), 101, 200),
+                TimelineEntry::gap(EntryKind::Idle, 200_000, 300_000),
+                entry("code", Some("lib.rs"), 300, 400),
+                TimelineEntry::gap(EntryKind::Away, 400_000, 500_000),
+                entry("firefox", None, 500, 600),
+            ]
+        );
+
+        // The flick to the terminal becomes part of the editor session, with the title of the editor
+        let rules = SmoothingRules {
+            min_session_millis: 5000,
+            merge_gap_millis: 0,
+        };
+        assert_eq!(
+            build_timeline(&smooth_sessions(sessions, &rules), &events)[..2],
+            [entry("code", Some("main.rs"), 0, 50), entry("code", Some("lib.rs"), 50, 200)]
+        );
+    }
+}
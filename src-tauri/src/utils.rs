@@ -1,24 +1,29 @@
-use chrono::{Local, NaiveDateTime, TimeZone};
-use chrono::LocalResult::Single;
+use chrono::{Local, NaiveDate};
 use std::env;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{fmt, Registry};
 
-use crate::sys_monitor::EventType;
+use crate::clock::{get_report_clock, Clock, SystemClock};
+use crate::crypto::{open_line, seal_line};
+use crate::events::publish_event;
+use crate::log_writer::{append_log_line, with_log_files_closed};
+use crate::metrics::record_log_write_error;
+use crate::sys_monitor::{Event, EventType};
 
 pub fn get_log_file_path() -> PathBuf {
-    let home_dir = env::var("HOME").unwrap();
-    let current_date = Local::now();
-    let log_file_name = format!("{}.log", current_date.format("%Y%m%d"));
-    let log_file_path_str = format!("{}/Documents/Flows/{}", home_dir, log_file_name);
-    let mut path = PathBuf::from(&log_file_path_str);
-    path.pop();
-    if !path.exists() {
-        std::fs::create_dir_all(&path).unwrap();
-    }
-    PathBuf::from(&log_file_path_str)
+    get_log_file_path_at(get_current_timestamp())
+}
+
+/// Get the path of the day log an event at `timestamp` belongs to
+pub fn get_log_file_path_at(timestamp: u64) -> PathBuf {
+    let date = SystemClock.get_date(timestamp).unwrap_or_else(|| Local::now().date_naive());
+    PathBuf::from(format!("{}/{}.log", get_log_file_dir_str(), date.format("%Y%m%d")))
 }
 
 pub fn get_log_file_dir_str() -> String {
@@ -27,17 +32,122 @@ pub fn get_log_file_dir_str() -> String {
 }
 
 pub fn write_to_file(event_type: EventType, timestamp: u64, path: &str) {
-    let log_file_path = get_log_file_path();
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(log_file_path)
-        .unwrap();
-    if event_type == EventType::StopMonitoring || event_type == EventType::ShutDown {
-        writeln!(file, "{},{}", event_type.to_int(), timestamp).unwrap();
-    } else if event_type == EventType::CameToFront {
-        writeln!(file, "{},{},{}", event_type.to_int(), timestamp, path).unwrap();
+    let line = match event_type {
+        EventType::CameToFront | EventType::Tag | EventType::Zone | EventType::Title => format!("{},{},{}", event_type.to_int(), timestamp, path),
+        _ => format!("{},{}", event_type.to_int(), timestamp),
+    };
+    let line = match seal_line(&line) {
+        Ok(line) => line,
+        Err(e) => {
+            error!("Failed to encrypt event: {}", e);
+            record_log_write_error();
+            return;
+        }
+    };
+
+    // Backdated events, e.g. the end of a session before sleep, go to the log of their own day
+    let log_file_path = get_log_file_path_at(timestamp);
+    // The process may exit right after these
+    let durable = matches!(event_type, EventType::ShutDown | EventType::StopMonitoring);
+    if let Err(e) = append_log_line(&log_file_path, &line, durable) {
+        error!("Failed to write to {:?}: {}", log_file_path, e);
+        record_log_write_error();
+        return;
     }
+
+    publish_event(&Event {
+        event_type,
+        timestamp,
+        path: path.to_string(),
+    });
+}
+
+/// Read all lines of a day log or summary, decrypting them if needed
+///
+/// Lines that can't be decrypted, e.g. after a torn write, are logged and skipped, so they don't
+/// hide the rest of the file.
+pub fn read_log_lines<P: AsRef<Path>>(path: P) -> io::Result<Vec<String>> {
+    let path = path.as_ref();
+    let content = fs::read(path)?;
+    Ok(String::from_utf8_lossy(&content)
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| match open_line(line) {
+            Ok(line) => Some(line),
+            Err(e) => {
+                warn!("Skipping line {} of {:?}: {}", index + 1, path, e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// An exclusive lock on the files of a directory, held until it is dropped
+pub(crate) struct DirLock {
+    _file: File,
+}
+
+/// Wait for the lock on the files of the directory a file is in, e.g. to rewrite it
+///
+/// It is an OS lock on `files.lock`, so the daemon, the app and the CLI never write the same files at once.
+pub(crate) fn lock_dir_of<P: AsRef<Path>>(path: P) -> io::Result<DirLock> {
+    let file = open_lock_file(get_dir_of(path.as_ref()))?;
+    file.lock()?;
+    Ok(DirLock { _file: file })
+}
+
+/// The directory a file is in, the current one for a bare file name
+pub(crate) fn get_dir_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Open the `files.lock` of a directory without locking it yet
+pub(crate) fn open_lock_file(dir: &Path) -> io::Result<File> {
+    fs::create_dir_all(dir)?;
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join("files.lock"))
+}
+
+/// Rewrite the raw lines of a file in place
+///
+/// `update` gets the lines as stored and returns whether it changed them. A missing file is
+/// treated as empty. The new content is written to a temporary file first, so a crash never
+/// leaves a half written log behind. The day log appended to is closed meanwhile and reopened
+/// with the next event, and the directory is locked against other processes.
+pub fn rewrite_log_file<P, F>(path: P, update: F) -> io::Result<bool>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut Vec<String>) -> io::Result<bool>,
+{
+    let path = path.as_ref();
+    with_log_files_closed(|| {
+        let _lock = lock_dir_of(path)?;
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut lines: Vec<String> = content.lines().map(String::from).collect();
+        if !update(&mut lines)? {
+            return Ok(false);
+        }
+
+        let temp_path = path.with_extension("tmp");
+        let mut content = lines.join("\n");
+        content.push('\n');
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(content.as_bytes())?;
+        temp_file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        Ok(true)
+    })
 }
 
 pub fn get_current_timestamp() -> u64 {
@@ -47,15 +157,68 @@ pub fn get_current_timestamp() -> u64 {
         .as_millis() as u64
 }
 
+/// Get the start of the day a timestamp falls on in reports
 pub fn get_day_start_timestamp(timestamp: u64) -> Option<u64> {
-    let date = match Local.timestamp_millis_opt(timestamp as i64) {
-        Single(date) => date,
-        _ => return None
-    };
-    let navie_local_date = date.date_naive();
-    let navie_start_of_day: NaiveDateTime = navie_local_date.and_hms_opt(0, 0, 0)?;
-    match navie_start_of_day.and_local_timezone(Local) {
-        Single(start_of_day) => Some(start_of_day.timestamp_millis() as u64),
-        _ => None
+    get_report_clock().get_day_start_timestamp(timestamp)
+}
+
+/// Get the timestamp of midnight at the start of a date in reports
+pub fn get_date_start_timestamp(date: NaiveDate) -> Option<u64> {
+    get_report_clock().get_date_start_timestamp(date)
+}
+
+/// Get the date a timestamp falls on in reports, see [`get_report_clock`]
+pub fn get_date(timestamp: u64) -> Option<NaiveDate> {
+    get_report_clock().get_date(timestamp)
+}
+
+/// Log to stdout and to a file in `log_dir` that rolls over daily
+pub fn init_tracing(log_dir: &str, file_name: &str) -> tracing_appender::non_blocking::WorkerGuard {
+    let file_appender = tracing_appender::rolling::daily(log_dir, file_name);
+    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+
+    let console_layer = fmt::layer()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stdout);
+
+    let file_layer = fmt::layer()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer(file_writer);
+
+    let subscriber = Registry::default().with(console_layer).with(file_layer);
+
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to set subscriber");
+
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::TryLockError;
+
+    #[test]
+    fn test_read_log_lines_skips_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("20261001.log");
+        let mut content = b"0,1000,/usr/bin/code\nenc:not-base64\n".to_vec();
+        content.extend_from_slice(b"\xff\xfe\n2,2000\n");
+        fs::write(&path, content).unwrap();
+        let lines = read_log_lines(&path).unwrap();
+        assert_eq!(lines.first().map(String::as_str), Some("0,1000,/usr/bin/code"));
+        assert_eq!(lines.last().map(String::as_str), Some("2,2000"));
+        assert!(lines.iter().all(|line| !line.starts_with("enc:")));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_lock_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corrections.json");
+        let lock = lock_dir_of(&path).unwrap();
+        let other = File::open(dir.path().join("files.lock")).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+    }
+}
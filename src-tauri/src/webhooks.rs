@@ -0,0 +1,384 @@
+use chrono::{Duration as ChronoDuration, Local, NaiveDate};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::clock::get_report_clock;
+use crate::config::{AppLimit, WebhookConfig, WebhookEventKind};
+use crate::events::subscribe_events;
+use crate::sys_monitor::{get_app_name_from_path, get_app_usages_from_log, AppUsage, Event, EventType};
+use crate::utils::get_date_start_timestamp;
+
+/// Total time of an app in a daily summary
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppTotal {
+    pub name: String,
+    pub total_secs: u64,
+}
+
+/// The JSON payload of a webhook, e.g. `{"event":"app_switch","timestamp":...,"name":"Safari",...}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    AppSwitch {
+        timestamp: u64,
+        name: String,
+        path: String,
+    },
+    IdleStart {
+        timestamp: u64,
+    },
+    IdleEnd {
+        timestamp: u64,
+    },
+    LimitExceeded {
+        timestamp: u64,
+        name: String,
+        limit_secs: u64,
+        total_secs: u64,
+    },
+    DailySummary {
+        date: String,
+        total_secs: u64,
+        apps: Vec<AppTotal>,
+    },
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::AppSwitch { .. } => WebhookEventKind::AppSwitch,
+            WebhookEvent::IdleStart { .. } => WebhookEventKind::IdleStart,
+            WebhookEvent::IdleEnd { .. } => WebhookEventKind::IdleEnd,
+            WebhookEvent::LimitExceeded { .. } => WebhookEventKind::LimitExceeded,
+            WebhookEvent::DailySummary { .. } => WebhookEventKind::DailySummary,
+        }
+    }
+
+    /// The app the event is about, if it is about a single app
+    fn app_name(&self) -> Option<&str> {
+        match self {
+            WebhookEvent::AppSwitch { name, .. } | WebhookEvent::LimitExceeded { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a hook wants an event. The app filter only applies to events about a single app.
+fn is_wanted(hook: &WebhookConfig, event: &WebhookEvent) -> bool {
+    if !hook.events.is_empty() && !hook.events.contains(&event.kind()) {
+        return false;
+    }
+    match event.app_name() {
+        Some(name) if !hook.apps.is_empty() => hook.apps.iter().any(|app| app.eq_ignore_ascii_case(name)),
+        _ => true,
+    }
+}
+
+/// POST a payload, retrying with exponential backoff. Returns whether it was delivered.
+fn deliver(hook: &WebhookConfig, payload: &str) -> bool {
+    let mut delay = Duration::from_millis(hook.retry_delay_ms);
+    for attempt in 0..=hook.max_retries {
+        if attempt > 0 {
+            thread::sleep(delay);
+            delay *= 2;
+        }
+        let result = ureq::post(&hook.url)
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", "application/json")
+            .send_string(payload);
+        match result {
+            Ok(_) => return true,
+            // Client errors won't go away by retrying
+            Err(ureq::Error::Status(status, _)) if (400..500).contains(&status) && status != 429 => {
+                warn!("Webhook {} rejected the payload with status {}", hook.url, status);
+                return false;
+            }
+            Err(e) => warn!("Failed to deliver webhook to {} (attempt {}): {}", hook.url, attempt + 1, e),
+        }
+    }
+    error!("Gave up delivering webhook to {}", hook.url);
+    false
+}
+
+/// Sends webhook events to the configured hooks, each from its own thread
+pub struct WebhookDispatcher {
+    hooks: Vec<(WebhookConfig, Sender<String>)>,
+}
+
+impl WebhookDispatcher {
+    pub fn start(hooks: &[WebhookConfig]) -> WebhookDispatcher {
+        let hooks = hooks
+            .iter()
+            .map(|hook| {
+                let (sender, receiver) = channel::<String>();
+                let thread_hook = hook.clone();
+                // Deliveries to one hook stay in order, and a slow hook doesn't hold up the others
+                thread::spawn(move || {
+                    for payload in receiver {
+                        deliver(&thread_hook, &payload);
+                    }
+                });
+                (hook.clone(), sender)
+            })
+            .collect();
+        WebhookDispatcher { hooks }
+    }
+
+    pub fn dispatch(&self, event: &WebhookEvent) {
+        let payload = match serde_json::to_string(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize webhook event: {}", e);
+                return;
+            }
+        };
+        for (hook, sender) in &self.hooks {
+            if is_wanted(hook, event) {
+                let _ = sender.send(payload.clone());
+            }
+        }
+    }
+}
+
+/// Turns the events written to the day logs into webhook events, which the D-Bus signals follow too
+#[derive(Default)]
+pub struct EventTranslator {
+    idle: bool,
+}
+
+impl EventTranslator {
+    pub fn translate(&mut self, event: &Event) -> Vec<WebhookEvent> {
+        let mut webhook_events = Vec::new();
+        match event.event_type {
+            EventType::CameToFront => {
+                // The first app after being idle marks the end of it
+                if self.idle {
+                    self.idle = false;
+                    webhook_events.push(WebhookEvent::IdleEnd {
+                        timestamp: event.timestamp,
+                    });
+                }
+                webhook_events.push(WebhookEvent::AppSwitch {
+                    timestamp: event.timestamp,
+                    name: get_app_name_from_path(&event.path).unwrap_or(String::from("Unknown")),
+                    path: event.path.clone(),
+                });
+            }
+            EventType::Idle => {
+                self.idle = true;
+                webhook_events.push(WebhookEvent::IdleStart {
+                    timestamp: event.timestamp,
+                });
+            }
+            // Not tracking anymore ends being idle too
+            EventType::ShutDown | EventType::StopMonitoring => {
+                if self.idle {
+                    self.idle = false;
+                    webhook_events.push(WebhookEvent::IdleEnd {
+                        timestamp: event.timestamp,
+                    });
+                }
+            }
+            EventType::Tag | EventType::Heartbeat | EventType::Zone | EventType::Title => {}
+        }
+        webhook_events
+    }
+}
+
+/// Get a `LimitExceeded` event for each limit that has been exceeded and wasn't reported yet today
+fn check_limits(
+    limits: &[AppLimit],
+    app_usages: &[AppUsage],
+    reported: &mut HashSet<String>,
+    timestamp: u64,
+) -> Vec<WebhookEvent> {
+    let mut webhook_events = Vec::new();
+    for limit in limits {
+        let limit_secs = limit.daily_minutes * 60;
+        let total_secs = app_usages
+            .iter()
+            .filter(|app| app.name.eq_ignore_ascii_case(&limit.app))
+            .map(|app| app.total_secs)
+            .sum();
+        if total_secs > limit_secs && reported.insert(limit.app.to_lowercase()) {
+            webhook_events.push(WebhookEvent::LimitExceeded {
+                timestamp,
+                name: limit.app.clone(),
+                limit_secs,
+                total_secs,
+            });
+        }
+    }
+    webhook_events
+}
+
+fn get_daily_summary(date: NaiveDate) -> io::Result<WebhookEvent> {
+    let invalid_date = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid date: {}", date));
+    let start_timestamp = get_date_start_timestamp(date).ok_or_else(invalid_date)?;
+    let end_timestamp = get_date_start_timestamp(date + ChronoDuration::days(1)).ok_or_else(invalid_date)?;
+    let apps: Vec<AppTotal> = get_app_usages_from_log(start_timestamp, end_timestamp)?
+        .into_iter()
+        .map(|app| AppTotal {
+            name: app.name,
+            total_secs: app.total_secs,
+        })
+        .collect();
+    Ok(WebhookEvent::DailySummary {
+        date: date.format("%Y-%m-%d").to_string(),
+        total_secs: apps.iter().map(|app| app.total_secs).sum(),
+        apps,
+    })
+}
+
+/// Send webhooks for the events the monitor writes, for exceeded limits and for each day that ends
+///
+/// Starts the threads and returns.
+pub fn start_webhooks(hooks: &[WebhookConfig], limits: &[AppLimit]) {
+    if hooks.is_empty() {
+        return;
+    }
+    info!("Sending webhooks to {} URLs", hooks.len());
+    let dispatcher = Arc::new(WebhookDispatcher::start(hooks));
+
+    let event_dispatcher = Arc::clone(&dispatcher);
+    let receiver = subscribe_events();
+    thread::spawn(move || {
+        let mut translator = EventTranslator::default();
+        for event in receiver {
+            for webhook_event in translator.translate(&event) {
+                event_dispatcher.dispatch(&webhook_event);
+            }
+        }
+    });
+
+    let limits = limits.to_vec();
+    thread::spawn(move || {
+        let get_today = || {
+            let clock = get_report_clock();
+            clock.get_date(clock.now()).unwrap_or_else(|| Local::now().date_naive())
+        };
+        let mut current_date = get_today();
+        let mut reported = HashSet::new();
+        loop {
+            thread::sleep(Duration::from_secs(60));
+
+            let today = get_today();
+            if today != current_date {
+                match get_daily_summary(current_date) {
+                    Ok(summary) => dispatcher.dispatch(&summary),
+                    Err(e) => error!("Failed to get the summary of {}: {}", current_date, e),
+                }
+                current_date = today;
+                reported.clear();
+            }
+
+            if limits.is_empty() {
+                continue;
+            }
+            let clock = get_report_clock();
+            let now = clock.now();
+            let day_start = clock.get_date_start_timestamp(today).unwrap_or(now);
+            match get_app_usages_from_log(day_start, now) {
+                Ok(app_usages) => {
+                    for webhook_event in check_limits(&limits, &app_usages, &mut reported, now) {
+                        dispatcher.dispatch(&webhook_event);
+                    }
+                }
+                Err(e) => error!("Failed to check limits: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: EventType, timestamp: u64, path: &str) -> Event {
+        Event {
+            event_type,
+            timestamp,
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut translator = EventTranslator::default();
+        assert_eq!(
+            translator.translate(&event(EventType::CameToFront, 1, "/usr/bin/code")),
+            [WebhookEvent::AppSwitch {
+                timestamp: 1,
+                name: "code".to_string(),
+                path: "/usr/bin/code".to_string(),
+            }]
+        );
+        assert_eq!(
+            translator.translate(&event(EventType::Idle, 2, "")),
+            [WebhookEvent::IdleStart { timestamp: 2 }]
+        );
+        let webhook_events = translator.translate(&event(EventType::CameToFront, 3, "/usr/bin/code"));
+        assert_eq!(webhook_events[0], WebhookEvent::IdleEnd { timestamp: 3 });
+        assert_eq!(webhook_events[1].kind(), WebhookEventKind::AppSwitch);
+        assert!(translator.translate(&event(EventType::Tag, 4, "focus")).is_empty());
+
+        // Stopping while idle ends it, once
+        translator.translate(&event(EventType::Idle, 5, ""));
+        assert_eq!(
+            translator.translate(&event(EventType::ShutDown, 6, "")),
+            [WebhookEvent::IdleEnd { timestamp: 6 }]
+        );
+        assert!(translator.translate(&event(EventType::ShutDown, 7, "")).is_empty());
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = [AppLimit {
+            app: "Safari".to_string(),
+            daily_minutes: 1,
+        }];
+        let mut app_usages = vec![AppUsage {
+            name: "Safari".to_string(),
+            path: "/Applications/Safari.app".to_string(),
+            total_secs: 60,
+            durations: Vec::new(),
+        }];
+        let mut reported = HashSet::new();
+        assert!(check_limits(&limits, &app_usages, &mut reported, 1).is_empty());
+
+        app_usages[0].total_secs = 61;
+        assert_eq!(check_limits(&limits, &app_usages, &mut reported, 2).len(), 1);
+        // Only once a day
+        assert!(check_limits(&limits, &app_usages, &mut reported, 3).is_empty());
+    }
+
+    #[test]
+    fn test_is_wanted() {
+        let hook = WebhookConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            events: vec![WebhookEventKind::AppSwitch, WebhookEventKind::DailySummary],
+            apps: vec!["code".to_string()],
+            ..WebhookConfig::default()
+        };
+        let app_switch = |name: &str| WebhookEvent::AppSwitch {
+            timestamp: 1,
+            name: name.to_string(),
+            path: String::new(),
+        };
+        assert!(is_wanted(&hook, &app_switch("Code")));
+        assert!(!is_wanted(&hook, &app_switch("Safari")));
+        assert!(!is_wanted(&hook, &WebhookEvent::IdleStart { timestamp: 1 }));
+        let summary = WebhookEvent::DailySummary {
+            date: "2026-10-01".to_string(),
+            total_secs: 0,
+            apps: Vec::new(),
+        };
+        assert!(is_wanted(&hook, &summary));
+    }
+}
@@ -0,0 +1,236 @@
+use chrono::{NaiveDate, TimeZone};
+use chrono_tz::America::Los_Angeles;
+use chrono_tz::Europe::Berlin;
+use chrono_tz::Tz;
+use flows::clock::{Clock, FixedClock, ZoneHistory};
+use flows::sys_monitor::{AppUsage, LogStore};
+
+fn at_in(timezone: Tz, date: &str, time: &str) -> u64 {
+    let local = format!("{} {}", date, time);
+    let local = chrono::NaiveDateTime::parse_from_str(&local, "%Y-%m-%d %H:%M").unwrap();
+    timezone.from_local_datetime(&local).earliest().unwrap().timestamp_millis() as u64
+}
+
+/// Local time in Berlin, where most fixture logs were recorded
+fn at(date: &str, time: &str) -> u64 {
+    at_in(Berlin, date, time)
+}
+
+fn berlin() -> FixedClock<Tz> {
+    FixedClock {
+        now: at("2026-11-01", "12:00"),
+        timezone: Berlin,
+    }
+}
+
+/// The fixture logs of a scenario, recorded where the clock is
+fn fixture<'a>(clock: &'a dyn Clock, scenario: &str) -> LogStore<'a> {
+    LogStore::new(
+        format!("{}/tests/fixtures/aggregator/{}", env!("CARGO_MANIFEST_DIR"), scenario),
+        clock,
+    )
+    .with_log_clock(clock)
+}
+
+/// Total seconds by app name, in order of name
+fn totals(app_usages: &[AppUsage]) -> Vec<(&str, u64)> {
+    let mut totals: Vec<(&str, u64)> = app_usages.iter().map(|app| (app.name.as_str(), app.total_secs)).collect();
+    totals.sort();
+    totals
+}
+
+/// The whole day of a date, as the reports query it
+fn day(clock: &dyn Clock, date: &str) -> (u64, u64) {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+    let start = clock.get_date_start_timestamp(date).unwrap();
+    let next_start = clock.get_date_start_timestamp(date.succ_opt().unwrap()).unwrap();
+    (start, next_start - 1)
+}
+
+#[test]
+fn test_app_switches() {
+    let clock = berlin();
+    let (start, end) = day(&clock, "2026-03-10");
+    let app_usages = fixture(&clock, "switches").get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 5400), ("firefox", 1800)]);
+    // Longest first, and tags don't interrupt a session
+    assert_eq!(app_usages[0].name, "code");
+    assert_eq!(
+        app_usages[0].durations,
+        vec![
+            (at("2026-03-10", "09:00"), at("2026-03-10", "09:30")),
+            (at("2026-03-10", "10:00"), at("2026-03-10", "11:00")),
+        ]
+    );
+    assert_eq!(app_usages[1].durations, vec![(at("2026-03-10", "09:30"), at("2026-03-10", "10:00"))]);
+}
+
+#[test]
+fn test_shutdowns_and_idle() {
+    let clock = berlin();
+    let (start, end) = day(&clock, "2026-03-10");
+    let app_usages = fixture(&clock, "shutdowns").get_app_usages(start, end).unwrap();
+    // Sleep, pausing and idle time count for no app
+    assert_eq!(totals(&app_usages), vec![("code", 1800), ("firefox", 3300)]);
+}
+
+#[test]
+fn test_heartbeats() {
+    let clock = berlin();
+    let (start, end) = day(&clock, "2026-03-10");
+    let app_usages = fixture(&clock, "heartbeats").get_app_usages(start, end).unwrap();
+    // The editor's session was cut short after its last heartbeat
+    assert_eq!(totals(&app_usages), vec![("code", 120), ("firefox", 90)]);
+
+    // Unless the session gap allows for an hour without heartbeats
+    let app_usages = fixture(&clock, "heartbeats").with_session_gap(3_600_000).get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 3600), ("firefox", 90)]);
+}
+
+#[test]
+fn test_midnight() {
+    let clock = berlin();
+    let store = fixture(&clock, "midnight");
+
+    // The editor stayed in front from 23:30 until 00:30, so each day gets its part
+    let (start, end) = day(&clock, "2026-03-11");
+    let app_usages = store.get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 1799)]);
+    assert_eq!(app_usages[0].durations, vec![(at("2026-03-11", "23:30"), end)]);
+
+    let (start, end) = day(&clock, "2026-03-12");
+    let app_usages = store.get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 1800), ("firefox", 1800)]);
+
+    let (start, _) = day(&clock, "2026-03-11");
+    let app_usages = store.get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 3600), ("firefox", 1800)]);
+
+    // Raw events are not clipped or carried over
+    let events = store.get_events(at("2026-03-12", "00:00"), at("2026-03-12", "23:00")).unwrap();
+    assert_eq!(events.len(), 2);
+}
+
+#[test]
+fn test_dst_days() {
+    let clock = berlin();
+    let store = fixture(&clock, "dst");
+
+    // Clocks go forward at 02:00 on March 29, so the day has 23 hours
+    let (start, end) = day(&clock, "2026-03-29");
+    assert_eq!(end + 1 - start, 23 * 3_600_000);
+    let app_usages = store.get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 4500)]);
+    // Ranges starting late in the day before don't skip the short day
+    let app_usages = store
+        .get_app_usages(at("2026-03-28", "23:30"), at("2026-03-30", "12:00"))
+        .unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 4500)]);
+
+    // Clocks go back at 03:00 on October 25, so the day has 25 hours
+    let (start, end) = day(&clock, "2026-10-25");
+    assert_eq!(end + 1 - start, 25 * 3_600_000);
+    let app_usages = store.get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 4 * 3600)]);
+    // Ranges starting after midnight read the long day only once
+    let app_usages = store
+        .get_app_usages(at("2026-10-25", "00:30"), at("2026-10-26", "12:00"))
+        .unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 4 * 3600)]);
+}
+
+#[test]
+fn test_malformed_lines() {
+    let clock = berlin();
+    let (start, end) = day(&clock, "2026-03-10");
+    let store = fixture(&clock, "malformed");
+    let app_usages = store.get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 1800), ("firefox", 1800)]);
+    // Only the complete events are read: the two sessions and the shutdown, as the tag has no label
+    assert_eq!(store.get_events(start, end).unwrap().len(), 3);
+}
+
+#[test]
+fn test_compacted_days() {
+    let clock = berlin();
+    let store = fixture(&clock, "compacted");
+    // March 9 only has a summary: 50 minutes from 09:00 and 10 from 10:00, of which the range has half the first hour
+    let app_usages = store
+        .get_app_usages(at("2026-03-09", "09:30"), at("2026-03-10", "23:00"))
+        .unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 2100), ("firefox", 1800)]);
+    assert!(app_usages.iter().find(|app| app.name == "code").unwrap().durations.is_empty());
+}
+
+#[test]
+fn test_timezone_of_clock() {
+    // In New York, the early Berlin morning is still the night before
+    let clock = FixedClock {
+        now: at("2026-11-01", "12:00"),
+        timezone: chrono_tz::America::New_York,
+    };
+    let store = fixture(&clock, "switches");
+    assert_eq!(clock.get_date(at("2026-03-10", "09:00")), NaiveDate::from_ymd_opt(2026, 3, 10));
+    assert_eq!(clock.get_date(at("2026-03-10", "04:00")), NaiveDate::from_ymd_opt(2026, 3, 9));
+    // The day logs are named by the day in the clock's timezone, so the events of March 10 in Berlin
+    // are looked up in the March 10 log as long as they fall on March 10 in New York as well
+    let start = clock.get_day_start_timestamp(at("2026-03-10", "09:00")).unwrap();
+    let app_usages = store.get_app_usages(start, start + 86_399_999).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 5400), ("firefox", 1800)]);
+    assert_eq!(clock.now(), at("2026-11-01", "12:00"));
+}
+
+#[test]
+fn test_travel() {
+    // Flew from Berlin to San Francisco on March 10, and the day log of March 10 went on there
+    let data_dir = format!("{}/tests/fixtures/aggregator/travel", env!("CARGO_MANIFEST_DIR"));
+    let history = ZoneHistory::load(&data_dir).unwrap().unwrap();
+    assert_eq!(history.get_zone(at("2026-03-10", "09:00")), Berlin);
+    assert_eq!(history.get_zone(at_in(Los_Angeles, "2026-03-10", "20:00")), Los_Angeles);
+
+    // In the zones the events were recorded in, March 10 lasted from midnight in Berlin to midnight in San Francisco
+    let (start, end) = day(&history, "2026-03-10");
+    assert_eq!(start, at("2026-03-10", "00:00"));
+    assert_eq!(end + 1, at_in(Los_Angeles, "2026-03-11", "00:00"));
+    let app_usages = LogStore::new(data_dir.as_str(), &history).get_app_usages(start, end).unwrap();
+    assert_eq!(totals(&app_usages), vec![("code", 7200), ("firefox", 3600)]);
+
+    // Reports in Berlin time put the evening in San Francisco on March 11, although it is in the log of March 10
+    let clock = berlin();
+    let store = fixture(&clock, "travel");
+    let (start, end) = day(&clock, "2026-03-10");
+    assert_eq!(totals(&store.get_app_usages(start, end).unwrap()), vec![("code", 3600)]);
+    let (start, end) = day(&clock, "2026-03-11");
+    assert_eq!(totals(&store.get_app_usages(start, end).unwrap()), vec![("code", 3600), ("firefox", 3600)]);
+    let events = store.get_events(start, end).unwrap();
+    assert!(events.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+
+    // Flying west after midnight doesn't go back to the day before
+    let departure = at("2026-03-11", "01:00");
+    let history = ZoneHistory::new(vec![(0, Berlin), (departure, Los_Angeles)]).unwrap();
+    assert_eq!(history.get_date(departure + 3_600_000), NaiveDate::from_ymd_opt(2026, 3, 11));
+    assert_eq!(
+        history.get_date_start_timestamp(NaiveDate::from_ymd_opt(2026, 3, 11).unwrap()),
+        Some(at("2026-03-11", "00:00"))
+    );
+    assert_eq!(
+        history.get_date_start_timestamp(NaiveDate::from_ymd_opt(2026, 3, 12).unwrap()),
+        Some(at_in(Los_Angeles, "2026-03-12", "00:00"))
+    );
+}
+
+#[test]
+fn test_corrections() {
+    let clock = berlin();
+    let (start, end) = day(&clock, "2026-03-10");
+    let store = fixture(&clock, "corrected");
+    // A meeting from 10:30 to 11:30 takes over the end of the code session, and the browser was research
+    assert_eq!(
+        totals(&store.get_app_usages(start, end).unwrap()),
+        vec![("Meeting", 3600), ("Research", 1800), ("code", 3600)]
+    );
+    // The day log stays as recorded
+    let events = store.get_events(start, end).unwrap();
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[1].path, "/usr/bin/firefox");
+}
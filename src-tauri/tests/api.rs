@@ -0,0 +1,107 @@
+use flows::api::{ApiServer, OPENAPI};
+use flows::monitor::Monitor;
+use flows::sys_monitor::EventType;
+use flows::utils::{get_current_timestamp, write_to_file};
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::thread;
+
+const TOKEN: &str = "test-token";
+
+/// Point the data directory, which follows HOME, at an empty directory shared by all tests
+fn set_up_home() {
+    static HOME_DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+    let home_dir = HOME_DIR.get_or_init(|| tempfile::tempdir().unwrap());
+    std::env::set_var("HOME", home_dir.path());
+}
+
+fn get(port: u16, path: &str, token: Option<&str>) -> (u16, Value) {
+    let mut request = ureq::get(&format!("http://127.0.0.1:{}{}", port, path));
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    match request.call() {
+        Ok(response) => (response.status(), response.into_json().unwrap()),
+        Err(ureq::Error::Status(status, response)) => (status, response.into_json().unwrap()),
+        Err(e) => panic!("Request failed: {}", e),
+    }
+}
+
+#[test]
+fn test_api() {
+    set_up_home();
+
+    let now = get_current_timestamp();
+    write_to_file(EventType::CameToFront, now - 30_000, "/Applications/Safari.app/Contents/MacOS/Safari");
+    write_to_file(EventType::CameToFront, now - 20_000, "/usr/bin/code");
+    write_to_file(EventType::StopMonitoring, now - 10_000, "");
+
+    let server = ApiServer::bind(0, TOKEN.to_string()).unwrap();
+    let port = server.port();
+    thread::spawn(move || server.run(&Monitor::new()));
+
+    // Everything but the description needs the token
+    let (status, body) = get(port, "/api/v1/today", None);
+    assert_eq!(status, 401);
+    assert!(body["error"].is_string());
+    assert_eq!(get(port, "/api/v1/today", Some("wrong")).0, 401);
+
+    let (status, body) = get(port, "/api/v1/openapi.json", None);
+    assert_eq!(status, 200);
+    assert_eq!(body, serde_json::from_str::<Value>(OPENAPI).unwrap());
+
+    let (status, body) = get(port, "/api/v1/current", Some(TOKEN));
+    assert_eq!(status, 200);
+    assert!(body["path"].is_null());
+
+    let range = format!("start_timestamp={}&end_timestamp={}", now - 60_000, now);
+    let (status, body) = get(port, &format!("/api/v1/usages?{}", range), Some(TOKEN));
+    assert_eq!(status, 200);
+    let usages = body.as_array().unwrap();
+    assert_eq!(usages.len(), 2);
+    assert!(usages.iter().all(|usage| usage["total_secs"] == 10));
+
+    let (status, body) = get(port, &format!("/api/v1/events?{}", range), Some(TOKEN));
+    assert_eq!(status, 200);
+    let event_types: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["event_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(event_types, ["came_to_front", "came_to_front", "stop_monitoring"]);
+
+    let (status, _) = get(port, "/api/v1/today", Some(TOKEN));
+    assert_eq!(status, 200);
+
+    assert_eq!(get(port, "/api/v1/usages?start_timestamp=1", Some(TOKEN)).0, 400);
+    assert_eq!(get(port, "/api/v1/unknown", Some(TOKEN)).0, 404);
+}
+
+#[test]
+fn test_metrics() {
+    set_up_home();
+
+    let server = ApiServer::bind(0, TOKEN.to_string()).unwrap().with_metrics(true);
+    let port = server.port();
+    thread::spawn(move || server.run(&Monitor::new()));
+
+    let url = format!("http://127.0.0.1:{}/metrics", port);
+    assert!(matches!(ureq::get(&url).call(), Err(ureq::Error::Status(401, _))));
+    let response = ureq::get(&url)
+        .set("Authorization", &format!("Bearer {}", TOKEN))
+        .call()
+        .unwrap();
+    assert!(response.content_type().starts_with("text/plain"));
+    let body = response.into_string().unwrap();
+    for metric_type in [
+        "flows_app_seconds_today gauge",
+        "flows_idle gauge",
+        "flows_monitor_check_duration_seconds summary",
+        "flows_log_write_errors_total counter",
+        "flows_sleep_gaps_total counter",
+    ] {
+        assert!(body.contains(&format!("# TYPE {}\n", metric_type)), "{}", metric_type);
+    }
+    assert!(body.contains("flows_paused 0\n"));
+}
@@ -0,0 +1,51 @@
+use flows::autostart::{Autostart, AutostartBackend};
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn test_autostart_backends() {
+    let home_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home_dir.path());
+    std::env::remove_var("XDG_CONFIG_HOME");
+
+    for (backend, label, entry_path) in [
+        (AutostartBackend::LaunchAgent, "com.entropy.flows", "Library/LaunchAgents/com.entropy.flows.plist"),
+        (AutostartBackend::XdgAutostart, "flows", ".config/autostart/flows.desktop"),
+        (AutostartBackend::SystemdUser, "flows-daemon", ".config/systemd/user/flows-daemon.service"),
+    ] {
+        let autostart = Autostart {
+            backend,
+            label: label.to_string(),
+            program: PathBuf::from("/opt/Flows Beta/flows"),
+            keep_alive: backend == AutostartBackend::SystemdUser,
+            update_system: false,
+        };
+        assert!(!autostart.is_enabled());
+        autostart.enable().unwrap();
+        assert!(autostart.is_enabled(), "{:?}", backend);
+        // Enabling twice is fine
+        autostart.enable().unwrap();
+
+        let content = fs::read_to_string(home_dir.path().join(entry_path)).unwrap();
+        assert!(content.contains("/opt/Flows Beta/flows"), "{}", content);
+
+        autostart.disable().unwrap();
+        assert!(!autostart.is_enabled());
+        assert!(!home_dir.path().join(entry_path).exists());
+        autostart.disable().unwrap();
+    }
+
+    // Turned off in the desktop settings
+    let autostart = Autostart {
+        backend: AutostartBackend::XdgAutostart,
+        label: "flows".to_string(),
+        program: PathBuf::from("/usr/bin/flows"),
+        keep_alive: false,
+        update_system: false,
+    };
+    autostart.enable().unwrap();
+    let entry_path = autostart.get_entry_path();
+    let content = fs::read_to_string(&entry_path).unwrap();
+    fs::write(&entry_path, content.replace("X-GNOME-Autostart-enabled=true", "X-GNOME-Autostart-enabled=false")).unwrap();
+    assert!(!autostart.is_enabled());
+}
@@ -0,0 +1,126 @@
+#![cfg(target_os = "linux")]
+
+use flows::dbus::{start_dbus_service, BUS_NAME, INTERFACE_NAME, OBJECT_PATH};
+use flows::monitor::Monitor;
+use flows::sys_monitor::EventType;
+use flows::utils::{get_current_timestamp, write_to_file};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use zbus::blocking::{Connection, ConnectionBuilder, Proxy};
+
+/// A `dbus-daemon` of its own, so the test doesn't depend on or disturb the session bus
+struct PrivateBus {
+    process: Child,
+    address: String,
+}
+
+impl PrivateBus {
+    fn start(dir: &Path) -> Option<PrivateBus> {
+        let config_path = dir.join("bus.conf");
+        fs::write(
+            &config_path,
+            format!(
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#,
+                dir.join("bus").display()
+            ),
+        )
+        .unwrap();
+
+        let mut process = match Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config_path.display()))
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(process) => process,
+            Err(e) => {
+                eprintln!("Skipping, dbus-daemon is not available: {}", e);
+                return None;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(process.stdout.take().unwrap()).read_line(&mut address).unwrap();
+        Some(PrivateBus {
+            process,
+            address: address.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+#[test]
+fn test_dbus_service() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", temp_dir.path());
+    let Some(bus) = PrivateBus::start(temp_dir.path()) else {
+        return;
+    };
+
+    let _service = start_dbus_service(Arc::new(Monitor::new()), Some(&bus.address)).unwrap();
+    let connection: Connection = ConnectionBuilder::address(bus.address.as_str()).unwrap().build().unwrap();
+    let proxy = Proxy::new(&connection, BUS_NAME, OBJECT_PATH, INTERFACE_NAME).unwrap();
+
+    let current: (String, String) = proxy.call("GetCurrentApp", &()).unwrap();
+    assert_eq!(current, (String::new(), String::new()));
+    let totals: Vec<(String, u64)> = proxy.call("GetTodayTotals", &()).unwrap();
+    assert!(totals.is_empty());
+    assert!(proxy.call::<_, _, bool>("Pause", &()).unwrap());
+    assert!(!proxy.call::<_, _, bool>("Pause", &()).unwrap());
+    assert!(proxy.call::<_, _, bool>("Resume", &()).unwrap());
+
+    // Read signals on another thread, so a missing signal fails instead of hanging
+    let (sender, receiver) = channel();
+    let focus_changes = proxy.receive_signal("FocusChanged").unwrap();
+    let idle_changes = proxy.receive_signal("IdleChanged").unwrap();
+    thread::spawn(move || {
+        for message in focus_changes {
+            let body: (String, String) = message.body().unwrap();
+            sender.send(body).unwrap();
+        }
+    });
+    let (idle_sender, idle_receiver) = channel();
+    thread::spawn(move || {
+        for message in idle_changes {
+            let (idle,): (bool,) = message.body().unwrap();
+            idle_sender.send(idle).unwrap();
+        }
+    });
+
+    let now = get_current_timestamp();
+    write_to_file(EventType::CameToFront, now, "/usr/bin/code");
+    write_to_file(EventType::Idle, now + 1, "");
+    write_to_file(EventType::CameToFront, now + 2, "/usr/bin/code");
+
+    let timeout = Duration::from_secs(5);
+    assert_eq!(
+        receiver.recv_timeout(timeout).unwrap(),
+        ("code".to_string(), "/usr/bin/code".to_string())
+    );
+    assert_eq!(receiver.recv_timeout(timeout).unwrap(), (String::new(), String::new()));
+    assert_eq!(receiver.recv_timeout(timeout).unwrap().0, "code");
+    assert!(idle_receiver.recv_timeout(timeout).unwrap());
+    assert!(!idle_receiver.recv_timeout(timeout).unwrap());
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDate;
+use flows::export::{export, ExportFormat, ExportKind};
+use flows::utils::{get_date_start_timestamp, get_log_file_dir_str};
+use serde_json::json;
+use std::fs;
+
+/// A local time on a day of October 2026
+fn at(day: u32, hour: u64, minute: u64) -> u64 {
+    let date = NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+    get_date_start_timestamp(date).unwrap() + (hour * 60 + minute) * 60000
+}
+
+fn export_to_string(kind: ExportKind, format: ExportFormat, start: u64, end: u64) -> String {
+    let mut output = Vec::new();
+    export(&mut output, kind, format, start, end).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_export_sessions() {
+    let home_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home_dir.path());
+    let log_dir = get_log_file_dir_str();
+    fs::create_dir_all(&log_dir).unwrap();
+
+    // A session started before the export, and one going on past midnight
+    let lines = [
+        format!("0,{},/usr/bin/code", at(1, 9, 0)),
+        format!("0,{},/usr/bin/firefox", at(1, 10, 0)),
+        format!("2,{}", at(1, 10, 30)),
+        format!("0,{},/usr/bin/code", at(1, 23, 30)),
+    ];
+    fs::write(format!("{}/20261001.log", log_dir), lines.join("\n") + "\n").unwrap();
+    let lines = [
+        format!("1,{}", at(2, 0, 30)),
+        format!("0,{},/usr/bin/firefox", at(2, 8, 0)),
+        format!("2,{}", at(2, 9, 0)),
+    ];
+    fs::write(format!("{}/20261002.log", log_dir), lines.join("\n") + "\n").unwrap();
+
+    // Cut to the period, and split at midnight
+    let csv = export_to_string(ExportKind::Sessions, ExportFormat::Csv, at(1, 9, 30), at(2, 8, 30));
+    assert_eq!(
+        csv.lines().collect::<Vec<_>>(),
+        vec![
+            "name,path,start,end,secs".to_string(),
+            format!("code,/usr/bin/code,{},{},1800", at(1, 9, 30), at(1, 10, 0)),
+            format!("firefox,/usr/bin/firefox,{},{},1800", at(1, 10, 0), at(1, 10, 30)),
+            format!("code,/usr/bin/code,{},{},1800", at(1, 23, 30), at(2, 0, 0)),
+            format!("code,/usr/bin/code,{},{},1800", at(2, 0, 0), at(2, 0, 30)),
+            format!("firefox,/usr/bin/firefox,{},{},1800", at(2, 8, 0), at(2, 8, 30)),
+        ]
+    );
+
+    let sessions: serde_json::Value =
+        serde_json::from_str(&export_to_string(ExportKind::Sessions, ExportFormat::Json, at(2, 0, 0), at(2, 12, 0)))
+            .unwrap();
+    assert_eq!(
+        sessions,
+        json!([
+            {"name": "code", "path": "/usr/bin/code", "start": at(2, 0, 0), "end": at(2, 0, 30), "secs": 1800},
+            {"name": "firefox", "path": "/usr/bin/firefox", "start": at(2, 8, 0), "end": at(2, 9, 0), "secs": 3600},
+        ])
+    );
+
+    let daily: serde_json::Value =
+        serde_json::from_str(&export_to_string(ExportKind::Daily, ExportFormat::Json, at(2, 0, 0), at(2, 12, 0)))
+            .unwrap();
+    assert_eq!(
+        daily,
+        json!([
+            {"date": "2026-10-02", "name": "firefox", "path": "/usr/bin/firefox", "total_secs": 3600},
+            {"date": "2026-10-02", "name": "code", "path": "/usr/bin/code", "total_secs": 1800},
+        ])
+    );
+}
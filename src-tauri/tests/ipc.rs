@@ -0,0 +1,67 @@
+use flows::ipc::{get_socket_path, send_request, send_request_to, serve, serve_at, subscribe, Request};
+use flows::sys_monitor::EventType;
+use flows::utils::{get_current_timestamp, write_to_file};
+use serde_json::json;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_ipc() {
+    let home_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home_dir.path());
+    std::env::set_var("XDG_RUNTIME_DIR", home_dir.path());
+
+    thread::spawn(|| {
+        serve(|request| match request {
+            Request::Ping => Ok(json!("pong")),
+            Request::Tag { label } => {
+                write_to_file(EventType::Tag, get_current_timestamp(), &label);
+                Ok(json!(true))
+            }
+            _ => Err(io::Error::other("Unsupported")),
+        })
+    });
+    while !get_socket_path().exists() {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(send_request(&Request::Ping).unwrap(), json!("pong"));
+    assert_eq!(send_request(&Request::Pause).unwrap_err().to_string(), "Unsupported");
+
+    // Subscribers get the events written after they subscribed
+    let mut events = subscribe().unwrap();
+    send_request(&Request::Tag { label: "review".to_string() }).unwrap();
+    let event = events.next().unwrap().unwrap();
+    assert_eq!(event.event_type, EventType::Tag);
+    assert_eq!(event.path, "review");
+}
+
+#[test]
+fn test_forward_to_running_app() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let socket_path = temp_dir.path().join("flows-app.sock");
+    // Left behind by an app that crashed
+    std::fs::write(&socket_path, "").unwrap();
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let sender = std::sync::Mutex::new(sender);
+    let serve_path = socket_path.clone();
+    thread::spawn(move || {
+        serve_at(&serve_path, move |request| {
+            sender.lock().unwrap().send(format!("{:?}", request)).unwrap();
+            Ok(json!(null))
+        })
+    });
+
+    let mut result = send_request_to(&socket_path, &Request::ShowDashboard);
+    for _ in 0..100 {
+        if result.is_ok() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+        result = send_request_to(&socket_path, &Request::ShowDashboard);
+    }
+    assert_eq!(result.unwrap(), json!(null));
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), "ShowDashboard");
+}
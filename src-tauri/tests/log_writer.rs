@@ -0,0 +1,49 @@
+use chrono::NaiveDate;
+use flows::log_writer::{read_heartbeat, recover_logs, write_heartbeat};
+use flows::sys_monitor::EventType;
+use flows::utils::{get_date_start_timestamp, get_log_file_dir_str, read_log_lines, rewrite_log_file, write_to_file};
+use std::fs;
+
+#[test]
+fn test_recover_after_kill() {
+    let home_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home_dir.path());
+    fs::create_dir_all(get_log_file_dir_str()).unwrap();
+
+    let day_start = get_date_start_timestamp(NaiveDate::from_ymd_opt(2026, 10, 1).unwrap()).unwrap();
+    let at = |secs: u64| day_start + 36_000_000 + secs * 1000;
+    let log_path = format!("{}/20261001.log", get_log_file_dir_str());
+
+    // Killed while writing an app switch, two seconds after the last heartbeat
+    fs::write(&log_path, format!("0,{},/usr/bin/code\n0,{},/usr/bin/fir", at(0), at(60))).unwrap();
+    write_heartbeat(at(58)).unwrap();
+    assert_eq!(read_heartbeat(), Some(at(58)));
+
+    recover_logs().unwrap();
+    assert_eq!(
+        read_log_lines(&log_path).unwrap(),
+        [format!("0,{},/usr/bin/code", at(0)), format!("1,{}", at(58))]
+    );
+    // Nothing left to recover
+    recover_logs().unwrap();
+    assert_eq!(read_log_lines(&log_path).unwrap().len(), 2);
+
+    // Rewriting replaces the file, so the writer has to reopen it
+    write_to_file(EventType::CameToFront, at(100), "/usr/bin/code");
+    rewrite_log_file(&log_path, |_| Ok(true)).unwrap();
+    write_to_file(EventType::StopMonitoring, at(200), "");
+    assert_eq!(
+        read_log_lines(&log_path).unwrap()[2..],
+        [format!("0,{},/usr/bin/code", at(100)), format!("2,{}", at(200))]
+    );
+
+    // Another process replacing the file is noticed too
+    let replaced_path = format!("{}/20261001.tmp", get_log_file_dir_str());
+    fs::copy(&log_path, &replaced_path).unwrap();
+    fs::rename(&replaced_path, &log_path).unwrap();
+    write_to_file(EventType::CameToFront, at(300), "/usr/bin/firefox");
+    assert_eq!(
+        read_log_lines(&log_path).unwrap().last(),
+        Some(&format!("0,{},/usr/bin/firefox", at(300)))
+    );
+}
@@ -0,0 +1,159 @@
+use chrono::{Duration, NaiveDate};
+use chrono_tz::Asia::Tokyo;
+use flows::clock::{get_report_clock, init_report_clock, Clock, SystemClock, ZonedClock};
+use flows::config::{ReportsConfig, RetentionAction, RetentionConfig};
+use flows::retention::{get_archive_dir_str, read_day_summary, run_retention};
+use flows::sys_monitor::LogStore;
+use flows::utils::{get_current_timestamp, get_date_start_timestamp, get_log_file_dir_str};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// The tests change the home directory and the report timezone of the process
+static PROCESS_STATE: Mutex<()> = Mutex::new(());
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2020, 3, day).unwrap()
+}
+
+/// A local time on a day of March 2020
+fn at(day: u32, hour: u64, minute: u64) -> u64 {
+    get_date_start_timestamp(date(day)).unwrap() + (hour * 60 + minute) * 60000
+}
+
+/// The whole day, up to the start of the next one
+fn day(day: u32) -> (u64, u64) {
+    (at(day, 0, 0), get_date_start_timestamp(date(day + 1)).unwrap())
+}
+
+/// Total seconds by app name, in order of name
+fn totals(clock: &dyn Clock, start: u64, end: u64) -> Vec<(String, u64)> {
+    let app_usages = LogStore::new(get_log_file_dir_str(), clock).get_app_usages(start, end).unwrap();
+    let mut totals: Vec<(String, u64)> = app_usages.into_iter().map(|app| (app.name, app.total_secs)).collect();
+    totals.sort();
+    totals
+}
+
+#[test]
+fn test_compact_day_logs() {
+    let _state = PROCESS_STATE.lock().unwrap();
+    init_report_clock(&ReportsConfig::default());
+    let home_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home_dir.path());
+    let log_dir = get_log_file_dir_str();
+    fs::create_dir_all(&log_dir).unwrap();
+
+    // A working day, and a session going on past midnight into the next one
+    fs::write(
+        format!("{}/20200310.log", log_dir),
+        [
+            format!("0,{},/usr/bin/code", at(10, 9, 0)),
+            format!("3,{},focus", at(10, 9, 15)),
+            format!("0,{},/usr/bin/firefox", at(10, 10, 30)),
+            format!("2,{}", at(10, 11, 0)),
+            format!("0,{},/usr/bin/code", at(10, 23, 30)),
+        ]
+        .join("\n")
+            + "\n",
+    )
+    .unwrap();
+    fs::write(
+        format!("{}/20200311.log", log_dir),
+        [
+            format!("1,{}", at(11, 0, 30)),
+            format!("0,{},/usr/bin/firefox", at(11, 8, 0)),
+            format!("2,{}", at(11, 8, 45)),
+        ]
+        .join("\n")
+            + "\n",
+    )
+    .unwrap();
+
+    let ranges = [day(10), day(11), (at(10, 9, 0), at(11, 12, 0)), (at(10, 9, 30), at(10, 10, 0))];
+    let before: Vec<_> = ranges.iter().map(|&(start, end)| totals(&SystemClock, start, end)).collect();
+    assert_eq!(
+        before[0],
+        vec![("code".to_string(), 7200), ("firefox".to_string(), 1800)]
+    );
+    assert_eq!(
+        before[1],
+        vec![("code".to_string(), 1800), ("firefox".to_string(), 2700)]
+    );
+
+    run_retention(&RetentionConfig {
+        raw_days: Some(1),
+        action: RetentionAction::Archive,
+        tracing_log_days: None,
+    });
+
+    // The raw logs are archived, and the summaries add up to the same time
+    for stem in ["20200310", "20200311"] {
+        assert!(!Path::new(&format!("{}/{}.log", log_dir, stem)).exists());
+        assert!(Path::new(&format!("{}/{}.log.gz", get_archive_dir_str(), stem)).exists());
+    }
+    let summary = read_day_summary("20200310").unwrap().unwrap();
+    let code = summary.iter().find(|app| app.name == "code").unwrap();
+    assert_eq!(code.total_secs, 7200);
+    assert_eq!(code.hourly_secs[9..12], [3600, 1800, 0]);
+    assert_eq!(code.hourly_secs[23], 1800);
+    let after: Vec<_> = ranges.iter().map(|&(start, end)| totals(&SystemClock, start, end)).collect();
+    assert_eq!(after, before);
+
+    // Nothing is left to compact
+    run_retention(&RetentionConfig {
+        raw_days: Some(1),
+        action: RetentionAction::Delete,
+        tracing_log_days: None,
+    });
+    assert_eq!(read_day_summary("20200310").unwrap().unwrap().len(), summary.len());
+}
+
+#[test]
+fn test_compact_with_report_timezone() {
+    let _state = PROCESS_STATE.lock().unwrap();
+    let home_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home_dir.path());
+    let log_dir = get_log_file_dir_str();
+    fs::create_dir_all(&log_dir).unwrap();
+    // Reports in another timezone than the system one, which names the day logs
+    init_report_clock(&ReportsConfig {
+        timezone: Some("Asia/Tokyo".to_string()),
+        ..Default::default()
+    });
+
+    let today = SystemClock.get_date(get_current_timestamp()).unwrap();
+    let dates: Vec<NaiveDate> = (1..=3).rev().map(|days| today - Duration::days(days)).collect();
+    let at = |date: NaiveDate, hour: u64| SystemClock.get_date_start_timestamp(date).unwrap() + hour * 3600000;
+    let day_log = |date: NaiveDate, lines: [String; 2]| {
+        fs::write(format!("{}/{}.log", log_dir, date.format("%Y%m%d")), lines.join("\n") + "\n").unwrap();
+    };
+    day_log(dates[0], [format!("0,{},/usr/bin/code", at(dates[0], 20)), format!("2,{}", at(dates[0], 23))]);
+    day_log(dates[1], [format!("0,{},/usr/bin/firefox", at(dates[1], 1)), format!("2,{}", at(dates[1], 3))]);
+    day_log(dates[2], [format!("0,{},/usr/bin/code", at(dates[2], 12)), format!("2,{}", at(dates[2], 13))]);
+
+    let tokyo = ZonedClock { timezone: Tokyo };
+    let report_days: Vec<(u64, u64)> = (-1..=3)
+        .map(|days| {
+            let date = dates[0] + Duration::days(days);
+            let start = tokyo.get_date_start_timestamp(date).unwrap();
+            (start, tokyo.get_date_start_timestamp(date + Duration::days(1)).unwrap())
+        })
+        .collect();
+    let clock = get_report_clock();
+    let before: Vec<_> = report_days.iter().map(|&(start, end)| totals(clock.as_ref(), start, end)).collect();
+
+    // Only the oldest day log is compacted, the report days around it still read the next one
+    run_retention(&RetentionConfig {
+        raw_days: Some(2),
+        action: RetentionAction::Delete,
+        tracing_log_days: None,
+    });
+    assert!(!Path::new(&format!("{}/{}.log", log_dir, dates[0].format("%Y%m%d"))).exists());
+    assert!(Path::new(&format!("{}/{}.log", log_dir, dates[1].format("%Y%m%d"))).exists());
+    let summary = read_day_summary(&dates[0].format("%Y%m%d").to_string()).unwrap().unwrap();
+    assert_eq!(summary[0].hourly_secs[20..23], [3600, 3600, 3600]);
+
+    let after: Vec<_> = report_days.iter().map(|&(start, end)| totals(clock.as_ref(), start, end)).collect();
+    assert_eq!(after, before);
+    init_report_clock(&ReportsConfig::default());
+}
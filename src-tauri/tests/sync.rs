@@ -0,0 +1,77 @@
+use chrono::NaiveDate;
+use flows::config::SyncConfig;
+use flows::sync::{combine_app_usages, get_device_app_usages, get_device_id, sync_with, DeviceInfo, FolderRemote};
+use flows::utils::{get_date_start_timestamp, get_log_file_dir_str, read_log_lines};
+use std::fs;
+use std::path::Path;
+
+fn use_home(home: &Path) -> DeviceInfo {
+    std::env::set_var("HOME", home);
+    fs::create_dir_all(get_log_file_dir_str()).unwrap();
+    DeviceInfo {
+        id: get_device_id().unwrap(),
+        name: home.file_name().unwrap().to_string_lossy().to_string(),
+    }
+}
+
+fn write_day_log(lines: &[String]) {
+    let mut content = lines.join("\n");
+    content.push('\n');
+    fs::write(format!("{}/20261001.log", get_log_file_dir_str()), content).unwrap();
+}
+
+#[test]
+fn test_sync_two_devices() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let remote = FolderRemote::new(temp_dir.path().join("remote"));
+    let day_start = get_date_start_timestamp(NaiveDate::from_ymd_opt(2026, 10, 1).unwrap()).unwrap();
+    let at = |secs: u64| day_start + 36_000_000 + secs * 1000;
+
+    // The laptop used the editor for an hour, the desktop the editor and a browser
+    let laptop = use_home(&temp_dir.path().join("laptop"));
+    write_day_log(&[
+        format!("0,{},/usr/bin/code", at(0)),
+        format!("2,{}", at(3600)),
+    ]);
+    let summary = sync_with(&remote, &laptop).unwrap();
+    assert_eq!((summary.uploaded, summary.downloaded), (1, 0));
+
+    let desktop = use_home(&temp_dir.path().join("desktop"));
+    assert_ne!(desktop.id, laptop.id);
+    write_day_log(&[
+        format!("0,{},/usr/bin/code", at(7200)),
+        format!("0,{},/usr/bin/firefox", at(7800)),
+        format!("1,{}", at(9000)),
+    ]);
+    let summary = sync_with(&remote, &desktop).unwrap();
+    assert_eq!((summary.uploaded, summary.downloaded), (1, 1));
+    // Nothing changed, so nothing is transferred again
+    assert_eq!(sync_with(&remote, &desktop).unwrap().downloaded, 0);
+
+    use_home(&temp_dir.path().join("laptop"));
+    let summary = sync_with(&remote, &laptop).unwrap();
+    assert_eq!((summary.uploaded, summary.downloaded), (0, 1));
+    let desktop_copy = format!("{}/devices/{}/20261001.log", get_log_file_dir_str(), desktop.id);
+    assert_eq!(read_log_lines(&desktop_copy).unwrap().len(), 3);
+
+    let config = SyncConfig {
+        device_name: Some("laptop".to_string()),
+        ..SyncConfig::default()
+    };
+    let device_app_usages = get_device_app_usages(&config, day_start, day_start + 86_399_999).unwrap();
+    assert_eq!(device_app_usages.len(), 2);
+    assert_eq!(device_app_usages[0].0, laptop);
+    assert_eq!(device_app_usages[1].0, desktop);
+    assert_eq!(device_app_usages[1].1.len(), 2);
+
+    let combined = combine_app_usages(device_app_usages.into_iter().map(|(_, app_usages)| app_usages));
+    let totals: Vec<(&str, u64)> = combined.iter().map(|app| (app.name.as_str(), app.total_secs)).collect();
+    assert_eq!(totals, [("code", 4200), ("firefox", 1200)]);
+    assert_eq!(combined[0].durations, [(at(0), at(3600)), (at(7200), at(7800))]);
+
+    // A restored backup lost an event the remote still has: the next sync brings it back
+    write_day_log(&[format!("0,{},/usr/bin/code", at(0))]);
+    sync_with(&remote, &laptop).unwrap();
+    let local_lines = read_log_lines(format!("{}/20261001.log", get_log_file_dir_str())).unwrap();
+    assert_eq!(local_lines, [format!("0,{},/usr/bin/code", at(0)), format!("2,{}", at(3600))]);
+}
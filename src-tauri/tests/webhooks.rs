@@ -0,0 +1,83 @@
+use flows::config::{WebhookConfig, WebhookEventKind};
+use flows::webhooks::{WebhookDispatcher, WebhookEvent};
+use serde_json::Value;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Response, Server};
+
+/// Start a local HTTP server standing in for a webhook receiver
+///
+/// It answers with the given statuses in turn, then with 200, and passes on each request body.
+fn start_stand_in(statuses: Vec<u16>) -> (String, Receiver<Value>) {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", server.server_addr());
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let mut statuses = statuses.into_iter();
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            sender.send(serde_json::from_str(&body).unwrap()).unwrap();
+            let status = statuses.next().unwrap_or(200);
+            request.respond(Response::empty(status)).unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+#[test]
+fn test_webhooks() {
+    let (retrying_url, retrying_requests) = start_stand_in(vec![503, 500]);
+    let (filtered_url, filtered_requests) = start_stand_in(Vec::new());
+    let dispatcher = WebhookDispatcher::start(&[
+        WebhookConfig {
+            url: retrying_url,
+            retry_delay_ms: 10,
+            ..WebhookConfig::default()
+        },
+        WebhookConfig {
+            url: filtered_url,
+            events: vec![WebhookEventKind::AppSwitch],
+            apps: vec!["Code".to_string()],
+            ..WebhookConfig::default()
+        },
+    ]);
+
+    dispatcher.dispatch(&WebhookEvent::IdleStart { timestamp: 1 });
+    dispatcher.dispatch(&WebhookEvent::AppSwitch {
+        timestamp: 2,
+        name: "code".to_string(),
+        path: "/usr/bin/code".to_string(),
+    });
+
+    // Two failed attempts, then the delivery, then the next event
+    let timeout = Duration::from_secs(5);
+    for _ in 0..3 {
+        let payload = retrying_requests.recv_timeout(timeout).unwrap();
+        assert_eq!(payload["event"], "idle_start");
+        assert_eq!(payload["timestamp"], 1);
+    }
+    assert_eq!(retrying_requests.recv_timeout(timeout).unwrap()["event"], "app_switch");
+
+    let payload = filtered_requests.recv_timeout(timeout).unwrap();
+    assert_eq!(payload["event"], "app_switch");
+    assert_eq!(payload["name"], "code");
+    assert!(filtered_requests.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn test_webhooks_give_up_on_client_errors() {
+    let (url, requests) = start_stand_in(vec![404]);
+    let dispatcher = WebhookDispatcher::start(&[WebhookConfig {
+        url,
+        retry_delay_ms: 10,
+        ..WebhookConfig::default()
+    }]);
+
+    dispatcher.dispatch(&WebhookEvent::IdleEnd { timestamp: 1 });
+    dispatcher.dispatch(&WebhookEvent::IdleStart { timestamp: 2 });
+    let timeout = Duration::from_secs(5);
+    assert_eq!(requests.recv_timeout(timeout).unwrap()["event"], "idle_end");
+    assert_eq!(requests.recv_timeout(timeout).unwrap()["event"], "idle_start");
+}
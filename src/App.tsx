@@ -11,6 +11,16 @@ export type AppUsage = {
   durations: [number, number][],
 };
 
+type DayRange = {
+  date: string,
+  start_timestamp: number,
+  end_timestamp: number,
+};
+
+function formatDate(date: Date): string {
+  return date.toLocaleDateString().replace(/\//g, '-');
+}
+
 function getTodayStartTimestamp(): number {
   const now = new Date();
   return (new Date(now.getFullYear(), now.getMonth(), now.getDate())).getTime();
@@ -20,6 +30,22 @@ const App: React.FC = () => {
   const [appUsages, setAppUsages] = useState<AppUsage[]>([]);
   const [startTimestamp, setStartTimestamp] = useState<number>(getTodayStartTimestamp());
   const [endTimestamp, setEndTimestamp] = useState<number>(getTodayStartTimestamp() + 86399000);
+  const [date, setDate] = useState<string>(formatDate(new Date(getTodayStartTimestamp())));
+
+  // Days follow the report timezone and travel, so ask the backend where a day starts and ends
+  async function showDayOf(timestamp: number) {
+    try {
+      const day: DayRange | null = await invoke('get_day_range_handler', { timestamp });
+      if (day) {
+        setStartTimestamp(day.start_timestamp);
+        setEndTimestamp(day.end_timestamp);
+        const [year, month, dayOfMonth] = day.date.split('-').map(Number);
+        setDate(formatDate(new Date(year, month - 1, dayOfMonth)));
+      }
+    } catch (error) {
+      console.error('Failed to get the day range:', error);
+    }
+  }
 
   async function registerGlobalShortcut() {
     try {
@@ -44,6 +70,10 @@ const App: React.FC = () => {
     }
   }
 
+  useEffect(() => {
+    showDayOf(Date.now());
+  }, []);
+
   useEffect(() => {
     document.addEventListener('contextmenu', (event) => {
       event.preventDefault();
@@ -63,24 +93,32 @@ const App: React.FC = () => {
   }, [startTimestamp, endTimestamp]);
 
   const handlePrevDay = () => {
-    setStartTimestamp((startTimestamp) => startTimestamp - 86400000);
-    setEndTimestamp((endTimestamp) => endTimestamp - 86400000);
+    showDayOf(startTimestamp - 1);
   };
 
   const handleNextDay = () => {
-    setStartTimestamp((startTimestamp) => startTimestamp + 86400000);
-    setEndTimestamp((endTimestamp) => endTimestamp + 86400000);
+    showDayOf(endTimestamp + 1);
   };
 
-  const formatDate = (date: Date): string => {
-    return date.toLocaleDateString().replace(/\//g, '-');
+  const handleExport = async () => {
+    try {
+      const filePath: string | null = await invoke('export_handler', { kind: 'sessions', startTimestamp, endTimestamp });
+      if (filePath) {
+        console.log(`Exported sessions to ${filePath}`);
+      }
+    } catch (error) {
+      console.error('Failed to export sessions:', error);
+    }
   };
 
   return (
     <div className="flex flex-col gap-3 p-5 h-screen justify-start dark:bg-gray-900">
       <div className="flex justify-between items-center select-none">
         <button onClick={handlePrevDay} className="text-white-800 dark:text-white p-2 rounded hover:bg-gray-300 dark:hover:bg-gray-600 transition duration-300"><img src="LeftArrow.svg" alt="expand" className="w-5 h-5" /></button>
-        <span className="text-xl dark:text-white">{formatDate(new Date(startTimestamp))}</span>
+        <div className="flex items-center gap-2">
+          <span className="text-xl dark:text-white">{date}</span>
+          <button onClick={handleExport} className="text-sm dark:text-white p-1 rounded hover:bg-gray-300 dark:hover:bg-gray-600 transition duration-300">Export</button>
+        </div>
         <button onClick={handleNextDay} className="text-white-800 dark:text-white p-2 rounded hover:bg-gray-300 dark:hover:bg-gray-600 transition duration-300"><img src="LeftArrow.svg" alt="expand" className="w-5 h-5 rotate-180" /></button>
       </div>
       <AppUsageComponent appUsages={appUsages} 